@@ -38,8 +38,8 @@ impl<T: Ord> BoundedMinHeap<T> {
     pub fn push(&mut self, item: T) {
         if self.heap.len() < self.capacity {
             self.heap.push(Reverse(item));
-        } else if let Some(&Reverse(ref min)) = self.heap.peek() {
-            if &item > min {
+        } else if let Some(Reverse(min)) = self.heap.peek() {
+            if item > *min {
                 self.heap.pop();
                 self.heap.push(Reverse(item));
             }
@@ -53,6 +53,11 @@ impl<T: Ord> BoundedMinHeap<T> {
         vec
     }
 
+    /// Iterates over the items in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.heap.iter().map(|Reverse(x)| x)
+    }
+
     /// Returns the number of items currently in the heap.
     pub fn len(&self) -> usize {
         self.heap.len()
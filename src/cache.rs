@@ -1,134 +1,273 @@
-use crate::types::ScanResults;
+use crate::collector::CollectorState;
+use crate::types::CacheStats;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+/// Bumped whenever the on-disk layout changes; caches from other versions are ignored.
+const CACHE_VERSION: u32 = 3;
+
+/// Default cache location: `~/.cache/spacemap`.
+pub fn default_cache_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".cache/spacemap")
+}
+
+/// Directory metadata that changes whenever an entry is added, removed or renamed.
+///
+/// Built from the directory's own `stat`, so checking it needs no `read_dir`.
+/// Modifying a file in place does not touch its parent directory; such
+/// changes are only picked up once the directory itself changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirStamp {
+    pub device: u64,
+    pub inode: u64,
+    /// mtime as (seconds, nanoseconds)
+    pub modified: (i64, i64),
+    /// ctime as (seconds, nanoseconds); always zero where unavailable
+    pub changed: (i64, i64),
+    /// Link count, which follows the number of subdirectories; zero where unavailable
+    pub links: u64,
+}
+
+impl DirStamp {
+    #[cfg(unix)]
+    pub fn new(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            modified: (metadata.mtime(), metadata.mtime_nsec()),
+            changed: (metadata.ctime(), metadata.ctime_nsec()),
+            links: metadata.nlink(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(metadata: &fs::Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| (d.as_secs() as i64, d.subsec_nanos() as i64))
+            .unwrap_or((0, 0));
+        Self {
+            device: 0,
+            inode: 0,
+            modified,
+            changed: (0, 0),
+            links: 0,
+        }
+    }
+}
+
+/// A regular file directly inside a cached directory.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CacheEntry {
-    pub dir_hash: String,
-    pub last_scan: SystemTime,
-    pub results: ScanResults,
+pub struct CachedFile {
+    pub name: OsString,
+    pub size: u64,
+    pub disk_size: u64,
+    pub modified: Option<SystemTime>,
+    /// `(dev, ino)` for files with more than one hard link
+    pub inode_key: Option<(u64, u64)>,
 }
 
-pub struct ScanCache {
-    cache_dir: PathBuf,
-    entries: HashMap<PathBuf, CacheEntry>,
+/// Aggregated stats of the files directly inside a directory, as the scan's
+/// collector saw them. Hard-linked files are left out; they are counted one
+/// by one so every inode is still counted once.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirSummary {
+    pub bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    /// Per-category stats, top files and the directory's own size
+    pub collector: CollectorState,
 }
 
-impl ScanCache {
-    pub fn new(cache_dir: Option<PathBuf>) -> io::Result<Self> {
-        let cache_dir = cache_dir.unwrap_or_else(|| {
-            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-            PathBuf::from(home).join(".cache/spacemap")
-        });
+/// One directory's listing, valid as long as its [`DirStamp`] matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDir {
+    pub stamp: DirStamp,
+    pub files: Vec<CachedFile>,
+    pub subdirs: Vec<OsString>,
+    pub summary: DirSummary,
+}
 
-        fs::create_dir_all(&cache_dir)?;
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    root: PathBuf,
+    dirs: HashMap<PathBuf, CachedDir>,
+}
 
-        let mut cache = Self {
-            cache_dir,
-            entries: HashMap::new(),
-        };
+/// Per-directory scan cache for one scan root.
+///
+/// Entries from the previous scan are handed out by [`DirCache::lookup`] when
+/// the directory's stamp still matches; everything the current scan reads is
+/// passed to [`DirCache::record`] and written back by [`DirCache::save`].
+/// Directories that were not visited this time (deleted, excluded or below
+/// `--max-depth`) are dropped.
+pub struct DirCache {
+    file: PathBuf,
+    root: PathBuf,
+    previous: HashMap<PathBuf, CachedDir>,
+    current: HashMap<PathBuf, CachedDir>,
+    hits: u64,
+    misses: u64,
+}
 
-        cache.load_entries()?;
-        Ok(cache)
-    }
+impl DirCache {
+    /// Open the cache for `root` in `cache_dir`.
+    ///
+    /// `options_key` identifies the options that shape a listing (excludes,
+    /// symlink handling); each distinct key gets its own cache file. Missing,
+    /// corrupt or outdated cache files start an empty cache.
+    pub fn open(cache_dir: &Path, root: &Path, options_key: &str) -> io::Result<Self> {
+        fs::create_dir_all(cache_dir)?;
 
-    fn load_entries(&mut self) -> io::Result<()> {
-        if let Ok(entries) = fs::read_dir(&self.cache_dir) {
-            for entry in entries.flatten() {
-                if entry.path().extension().and_then(|s| s.to_str()) == Some("cache") {
-                    if let Ok(cache_entry) = Self::read_cache_file(&entry.path()) {
-                        let scan_path = PathBuf::from(&cache_entry.results.scanned_path);
-                        self.entries.insert(scan_path, cache_entry);
-                    }
-                }
-            }
-        }
-        Ok(())
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(root.as_os_str().as_encoded_bytes());
+        hasher.update(b"\0");
+        hasher.update(options_key.as_bytes());
+        let file = cache_dir.join(format!("{}.dircache", hasher.finalize().to_hex()));
+
+        let previous = Self::read_cache_file(&file)
+            .ok()
+            .filter(|cached| cached.version == CACHE_VERSION && cached.root == root)
+            .map(|cached| cached.dirs)
+            .unwrap_or_default();
+
+        Ok(Self {
+            file,
+            root: root.to_path_buf(),
+            previous,
+            current: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        })
     }
 
-    fn read_cache_file(path: &Path) -> io::Result<CacheEntry> {
+    fn read_cache_file(path: &Path) -> io::Result<CacheFile> {
         let contents = fs::read(path)?;
         bincode::deserialize(&contents)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
-    fn write_cache_file(path: &Path, entry: &CacheEntry) -> io::Result<()> {
-        let contents = bincode::serialize(entry)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        fs::write(path, contents)
+    /// Path of the file this cache is saved to.
+    pub fn path(&self) -> &Path {
+        &self.file
     }
 
-    pub fn get(&self, path: &Path) -> Option<&CacheEntry> {
-        let entry = self.entries.get(path)?;
-
-        // Validate cache: check if directory has been modified
-        if let Ok(current_hash) = Self::compute_dir_hash(path) {
-            if entry.dir_hash == current_hash {
-                return Some(entry);
+    /// Take the cached listing of `dir` if it was cached with the same stamp.
+    pub fn lookup(&mut self, dir: &Path, stamp: &DirStamp) -> Option<CachedDir> {
+        match self.previous.remove(dir) {
+            Some(cached) if cached.stamp == *stamp => {
+                self.hits += 1;
+                Some(cached)
+            }
+            _ => {
+                self.misses += 1;
+                None
             }
         }
+    }
 
-        None // Cache invalid
+    /// Store the listing of `dir` as seen by the current scan.
+    pub fn record(&mut self, dir: PathBuf, listing: CachedDir) {
+        self.current.insert(dir, listing);
     }
 
-    pub fn put(&mut self, path: PathBuf, results: ScanResults) -> io::Result<()> {
-        let dir_hash = Self::compute_dir_hash(&path)?;
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
 
-        let entry = CacheEntry {
-            dir_hash,
-            last_scan: SystemTime::now(),
-            results,
+    /// Write the current scan's listings to disk.
+    pub fn save(self) -> io::Result<()> {
+        let cache_file = CacheFile {
+            version: CACHE_VERSION,
+            root: self.root,
+            dirs: self.current,
         };
+        let contents = bincode::serialize(&cache_file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        // Write to disk
-        let cache_file = self.cache_file_path(&path);
-        Self::write_cache_file(&cache_file, &entry)?;
+        // Write to a temporary file first so an interrupted save keeps the old cache
+        let tmp = self.file.with_extension("dircache.tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.file)
+    }
+}
 
-        // Update in-memory cache
-        self.entries.insert(path, entry);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
 
-        Ok(())
+    fn stamp(links: u64) -> DirStamp {
+        DirStamp {
+            device: 1,
+            inode: 2,
+            modified: (100, 0),
+            changed: (100, 0),
+            links,
+        }
     }
 
-    fn cache_file_path(&self, path: &Path) -> PathBuf {
-        // Create a safe filename from the path
-        let path_str = path.display().to_string();
-        let hash = blake3::hash(path_str.as_bytes());
-        let filename = format!("{}.cache", hash.to_hex());
-        self.cache_dir.join(filename)
+    fn listing(links: u64, files: &[(&str, u64)], subdirs: &[&str]) -> CachedDir {
+        CachedDir {
+            stamp: stamp(links),
+            files: files
+                .iter()
+                .map(|(name, size)| CachedFile {
+                    name: OsString::from(name),
+                    size: *size,
+                    disk_size: *size,
+                    modified: None,
+                    inode_key: None,
+                })
+                .collect(),
+            subdirs: subdirs.iter().map(OsString::from).collect(),
+            summary: DirSummary::default(),
+        }
     }
 
-    fn compute_dir_hash(path: &Path) -> io::Result<String> {
-        let metadata = fs::metadata(path)?;
-
-        // Hash based on: mtime + size
-        let mut hasher = blake3::Hasher::new();
+    #[test]
+    fn test_cache_round_trip_and_stamp_check() {
+        let cache_dir = TempDir::new().unwrap();
+        let root = Path::new("/scan");
 
-        if let Ok(modified) = metadata.modified() {
-            if let Ok(duration) = modified.duration_since(SystemTime::UNIX_EPOCH) {
-                hasher.update(&duration.as_secs().to_le_bytes());
-            }
-        }
+        let mut cache = DirCache::open(cache_dir.path(), root, "").unwrap();
+        assert!(cache.lookup(root, &stamp(2)).is_none());
+        cache.record(root.to_path_buf(), listing(2, &[("a", 10)], &["sub"]));
+        cache.record(root.join("sub"), listing(1, &[("b", 5)], &[]));
+        cache.save().unwrap();
 
-        hasher.update(&metadata.len().to_le_bytes());
+        let mut cache = DirCache::open(cache_dir.path(), root, "").unwrap();
+        let hit = cache.lookup(root, &stamp(2)).unwrap();
+        assert_eq!(hit.files[0].size, 10);
+        assert_eq!(hit.subdirs, vec![OsString::from("sub")]);
+        // A different link count invalidates just that directory
+        assert!(cache.lookup(&root.join("sub"), &stamp(2)).is_none());
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    }
 
-        // Also count immediate children (for quicker invalidation)
-        if let Ok(entries) = fs::read_dir(path) {
-            let count = entries.count();
-            hasher.update(&(count as u64).to_le_bytes());
-        }
+    #[test]
+    fn test_cache_keyed_by_options() {
+        let cache_dir = TempDir::new().unwrap();
+        let root = Path::new("/scan");
 
-        Ok(hasher.finalize().to_hex().to_string())
-    }
+        let mut cache = DirCache::open(cache_dir.path(), root, "exclude=target").unwrap();
+        cache.record(root.to_path_buf(), listing(0, &[], &[]));
+        cache.save().unwrap();
 
-    pub fn clear(&mut self) -> io::Result<()> {
-        fs::remove_dir_all(&self.cache_dir)?;
-        fs::create_dir_all(&self.cache_dir)?;
-        self.entries.clear();
-        Ok(())
+        let mut other = DirCache::open(cache_dir.path(), root, "").unwrap();
+        assert!(other.lookup(root, &stamp(0)).is_none());
     }
 }
@@ -1,17 +1,161 @@
 use crate::types::FileMetadata;
-use crate::config::SpacemapConfig;
+use crate::config::{CustomCategory, SpacemapConfig};
+use crate::exclude::ExcludeMatcher;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 pub trait Categorizer: Send + Sync {
     fn categorize(&self, metadata: &FileMetadata) -> Cow<'static, str>;
     fn get_label(&self, key: &str) -> String;
     fn clone_box(&self) -> Box<dyn Categorizer>;
+
+    /// Like [`Categorizer::categorize`], and whether the category was
+    /// detected from the file's content rather than its name or metadata.
+    fn classify(&self, metadata: &FileMetadata) -> (Cow<'static, str>, bool) {
+        (self.categorize(metadata), false)
+    }
+
+    /// Whether files must carry their modification time to be categorized.
+    fn needs_modified(&self) -> bool {
+        false
+    }
 }
 
+/// Categorizes files by type. A file gets the category of, in order:
+///
+/// 1. the first `[[categories]]` rule (an entry with paths, file names, size
+///    or age limits) it matches, in config order
+/// 2. its extension: the built-in map, extended by extension-only
+///    `[[categories]]` and overridden by `[[remaps]]`
+/// 3. its content, with [`TypeCategorizer::with_sniff`]
+/// 4. `Other`
 pub struct TypeCategorizer {
+    rules: Arc<[CategoryRule]>,
+    /// The scan root as given and canonicalised, to match `paths` rules
+    /// against absolute, normalised paths
+    root: Option<Arc<(PathBuf, PathBuf)>>,
     extension_map: HashMap<String, String>,
+    /// Read the first bytes of files the extension map doesn't cover
+    sniff: bool,
+}
+
+/// A compiled `[[categories]]` entry with conditions beyond extensions.
+pub struct CategoryRule {
+    category: String,
+    paths: Option<ExcludeMatcher>,
+    filenames: Option<GlobSet>,
+    extensions: HashSet<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_age_days: Option<u64>,
+    max_age_days: Option<u64>,
+}
+
+impl CategoryRule {
+    /// Compile `category`, or `None` if it only lists extensions.
+    pub fn new(category: &CustomCategory) -> Result<Option<Self>, CategoryError> {
+        if !category.is_rule() {
+            return Ok(None);
+        }
+        let invalid = |message: String| CategoryError {
+            category: category.name.clone(),
+            message,
+        };
+
+        let paths = match category.paths.is_empty() {
+            true => None,
+            false => Some(
+                ExcludeMatcher::new(Path::new("/"), &category.paths)
+                    .map_err(|e| invalid(format!("path '{}': {}", e.pattern, e.message)))?,
+            ),
+        };
+
+        let filenames = match category.filenames.is_empty() {
+            true => None,
+            false => {
+                let mut globs = GlobSetBuilder::new();
+                for pattern in &category.filenames {
+                    let glob = GlobBuilder::new(pattern)
+                        .literal_separator(true)
+                        .backslash_escape(true)
+                        .build()
+                        .map_err(|e| invalid(format!("file name '{}': {}", pattern, e.kind())))?;
+                    globs.add(glob);
+                }
+                Some(globs.build().map_err(|e| invalid(e.to_string()))?)
+            }
+        };
+
+        Ok(Some(Self {
+            category: category.name.clone(),
+            paths,
+            filenames,
+            extensions: category.extensions.iter().map(|ext| ext.to_lowercase()).collect(),
+            min_size: category.min_size,
+            max_size: category.max_size,
+            min_age_days: category.min_age_days,
+            max_age_days: category.max_age_days,
+        }))
+    }
+
+    /// Whether the file meets every condition of this rule; `path` is the
+    /// absolute path `paths` patterns are matched against.
+    pub fn matches(&self, metadata: &FileMetadata, path: &Path) -> bool {
+        if !self.extensions.is_empty() && !metadata.extension.as_ref().is_some_and(|ext| self.extensions.contains(ext)) {
+            return false;
+        }
+        if self.min_size.is_some_and(|min| metadata.size < min) || self.max_size.is_some_and(|max| metadata.size > max) {
+            return false;
+        }
+        if self.min_age_days.is_some() || self.max_age_days.is_some() {
+            let Some(modified) = metadata.modified else {
+                return false;
+            };
+            let days = days_since(modified);
+            if self.min_age_days.is_some_and(|min| days < min) || self.max_age_days.is_some_and(|max| days > max) {
+                return false;
+            }
+        }
+        if let Some(ref filenames) = self.filenames {
+            if !metadata.path.file_name().is_some_and(|name| filenames.is_match(name)) {
+                return false;
+            }
+        }
+        if let Some(ref paths) = self.paths {
+            if !paths.is_excluded(path, false) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A `[[categories]]` rule whose patterns don't compile.
+#[derive(Debug)]
+pub struct CategoryError {
+    pub category: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for CategoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "category '{}': {}", self.category, self.message)
+    }
+}
+
+impl std::error::Error for CategoryError {}
+
+fn days_since(modified: SystemTime) -> u64 {
+    SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+        / 86400
 }
 
 impl TypeCategorizer {
@@ -19,12 +163,20 @@ impl TypeCategorizer {
         Self::with_config(None)
     }
 
+    /// Categorizer for the config's categories and remaps. Rules whose
+    /// patterns don't compile are skipped; [`SpacemapConfig::load`] rejects
+    /// them.
     pub fn with_config(config: Option<&SpacemapConfig>) -> Self {
         let mut extension_map = Self::build_default_map();
+        let mut rules = Vec::new();
 
         if let Some(cfg) = config {
             // Apply custom categories
             for category in &cfg.categories {
+                if category.is_rule() {
+                    rules.extend(CategoryRule::new(category).ok().flatten());
+                    continue;
+                }
                 for ext in &category.extensions {
                     extension_map.insert(ext.to_lowercase(), category.name.clone());
                 }
@@ -38,7 +190,44 @@ impl TypeCategorizer {
             }
         }
 
-        Self { extension_map }
+        Self {
+            rules: rules.into(),
+            root: None,
+            extension_map,
+            sniff: false,
+        }
+    }
+
+    /// Categorize files found below `root`. `paths` rules are matched against
+    /// the file's path below the canonical root, so a root like `../data`
+    /// still matches absolute patterns; without a root, paths are matched as
+    /// they are.
+    pub fn with_root(mut self, root: &Path) -> Self {
+        let canonical = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        self.root = Some(Arc::new((root.to_path_buf(), canonical)));
+        self
+    }
+
+    /// The path `paths` rules are matched against.
+    fn rule_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        let Some(ref root) = self.root else {
+            return Cow::Borrowed(path);
+        };
+        let (given, canonical) = root.as_ref();
+        match path.strip_prefix(given) {
+            Ok(_) if given == canonical => Cow::Borrowed(path),
+            Ok(relative) if relative.as_os_str().is_empty() => Cow::Owned(canonical.clone()),
+            Ok(relative) => Cow::Owned(canonical.join(relative)),
+            Err(_) => Cow::Borrowed(path),
+        }
+    }
+
+    /// Detect the type of files with an unknown or missing extension from
+    /// their first bytes (`--sniff`). Detected files are categorized like
+    /// files with the extension their format usually has.
+    pub fn with_sniff(mut self, sniff: bool) -> Self {
+        self.sniff = sniff;
+        self
     }
 
     fn build_default_map() -> HashMap<String, String> {
@@ -75,7 +264,7 @@ impl TypeCategorizer {
         }
 
         // Archives
-        for ext in ["zip", "tar", "gz", "bz2", "7z", "rar", "xz", "zst", "tgz"] {
+        for ext in ["zip", "tar", "gz", "bz2", "7z", "rar", "xz", "zst", "tgz", "jar"] {
             map.insert(ext.to_string(), "Archives".to_string());
         }
 
@@ -116,26 +305,58 @@ impl TypeCategorizer {
     }
 }
 
+impl Default for TypeCategorizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Categorizer for TypeCategorizer {
     fn clone_box(&self) -> Box<dyn Categorizer> {
         Box::new(TypeCategorizer {
+            rules: self.rules.clone(),
+            root: self.root.clone(),
             extension_map: self.extension_map.clone(),
+            sniff: self.sniff,
         })
     }
 
     fn categorize(&self, metadata: &FileMetadata) -> Cow<'static, str> {
-        let category = metadata
-            .extension
-            .as_ref()
-            .and_then(|ext| self.extension_map.get(ext))
-            .map(|s| s.as_str())
-            .unwrap_or("Other");
-        Cow::Owned(category.to_string())
+        self.classify(metadata).0
+    }
+
+    fn classify(&self, metadata: &FileMetadata) -> (Cow<'static, str>, bool) {
+        if !self.rules.is_empty() {
+            let path = self.rule_path(&metadata.path);
+            if let Some(rule) = self.rules.iter().find(|rule| rule.matches(metadata, &path)) {
+                return (Cow::Owned(rule.category.clone()), false);
+            }
+        }
+
+        let by_extension = metadata.extension.as_ref().and_then(|ext| self.extension_map.get(ext));
+        if let Some(category) = by_extension {
+            return (Cow::Owned(category.clone()), false);
+        }
+
+        let by_content = self
+            .sniff
+            .then(|| crate::sniff::sniff(&metadata.path))
+            .flatten()
+            .and_then(|ext| self.extension_map.get(ext));
+        match by_content {
+            Some(category) => (Cow::Owned(category.clone()), true),
+            None => (Cow::Borrowed("Other"), false),
+        }
     }
 
     fn get_label(&self, key: &str) -> String {
         key.to_string()
     }
+
+    /// True when a `[[categories]]` rule has an age condition.
+    fn needs_modified(&self) -> bool {
+        self.rules.iter().any(|rule| rule.min_age_days.is_some() || rule.max_age_days.is_some())
+    }
 }
 
 pub struct SizeCategorizer {
@@ -243,14 +464,6 @@ impl AgeCategorizer {
         }
         &self.buckets[0].1
     }
-
-    fn days_since_modified(modified: SystemTime) -> u64 {
-        SystemTime::now()
-            .duration_since(modified)
-            .unwrap_or(Duration::from_secs(0))
-            .as_secs()
-            / 86400
-    }
 }
 
 impl Categorizer for AgeCategorizer {
@@ -262,7 +475,7 @@ impl Categorizer for AgeCategorizer {
 
     fn categorize(&self, metadata: &FileMetadata) -> Cow<'static, str> {
         if let Some(modified) = metadata.modified {
-            let days = Self::days_since_modified(modified);
+            let days = days_since(modified);
             Cow::Owned(self.find_bucket(days).to_string())
         } else {
             Cow::Borrowed("Unknown")
@@ -272,5 +485,123 @@ impl Categorizer for AgeCategorizer {
     fn get_label(&self, key: &str) -> String {
         key.to_string()
     }
+
+    fn needs_modified(&self) -> bool {
+        true
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn categorizer(config: &str) -> TypeCategorizer {
+        let config: SpacemapConfig = toml::from_str(config).unwrap();
+        config.validate().unwrap();
+        TypeCategorizer::with_config(Some(&config))
+    }
+
+    fn file(path: &str, size: u64, days_old: Option<u64>) -> FileMetadata {
+        let path = PathBuf::from(path);
+        FileMetadata {
+            extension: path.extension().map(|ext| ext.to_string_lossy().to_lowercase()),
+            path,
+            size,
+            disk_size: size,
+            modified: days_old.map(|days| SystemTime::now() - Duration::from_secs(days * 86400 + 60)),
+        }
+    }
+
+    #[test]
+    fn test_rules_take_priority_over_extensions() {
+        let categorizer = categorizer(
+            r#"
+            [[categories]]
+            name = "Dependencies"
+            paths = ["**/node_modules/**"]
+
+            [[categories]]
+            name = "Containers"
+            paths = ["/var/lib/docker"]
+
+            [[categories]]
+            name = "Logs"
+            filenames = ["*.log.*"]
+
+            [[categories]]
+            name = "Scripts"
+            extensions = ["js"]
+
+            [[remaps]]
+            extensions = ["ts"]
+            category = "Scripts"
+            "#,
+        );
+        let category = |path: &str| categorizer.categorize(&file(path, 1, None)).into_owned();
+
+        // Path rules beat the built-in map, extension-only categories and remaps
+        assert_eq!(category("/src/app/node_modules/react/index.js"), "Dependencies");
+        assert_eq!(category("/src/app/node_modules/types/index.d.ts"), "Dependencies");
+        assert_eq!(category("/var/lib/docker/overlay2/abc/layer.tar"), "Containers");
+        assert_eq!(category("/var/log/syslog.log.1"), "Logs");
+        assert_eq!(category("/var/log/nginx/access.log.2.gz"), "Logs");
+
+        // Everything else still goes through the extension map
+        assert_eq!(category("/src/app/main.js"), "Scripts");
+        assert_eq!(category("/src/app/main.ts"), "Scripts");
+        assert_eq!(category("/srv/var/lib/docker/notes.md"), "Documents");
+        assert_eq!(category("/var/log/syslog.log"), "Logs");
+        assert_eq!(category("/src/node_modules.txt"), "Documents");
+    }
+
+    #[test]
+    fn test_rules_first_match_and_all_conditions() {
+        let categorizer = categorizer(
+            r#"
+            [[categories]]
+            name = "Large videos"
+            extensions = ["mp4", "MKV"]
+            min_size = "1G"
+
+            [[categories]]
+            name = "Old downloads"
+            paths = ["re:^/home/[^/]+/Downloads/"]
+            min_age_days = 90
+
+            [[categories]]
+            name = "Downloads"
+            paths = ["/home/*/Downloads"]
+            max_size = "10M"
+            "#,
+        );
+        let category = |path: &str, size: u64, days: Option<u64>| categorizer.categorize(&file(path, size, days)).into_owned();
+        let gib = 1 << 30;
+
+        assert_eq!(category("/home/a/Videos/film.mkv", 2 * gib, None), "Large videos");
+        assert_eq!(category("/home/a/Videos/clip.mp4", 1024, None), "Videos");
+
+        // The first matching rule wins
+        assert_eq!(category("/home/a/Downloads/film.mp4", 2 * gib, Some(400)), "Large videos");
+        assert_eq!(category("/home/a/Downloads/setup.exe", 1024, Some(400)), "Old downloads");
+        assert_eq!(category("/home/a/Downloads/setup.exe", 1024, Some(3)), "Downloads");
+
+        // Files that fail a condition fall through to later rules and the extension map
+        assert_eq!(category("/home/a/Downloads/disk.iso", gib, Some(3)), "Disk Images");
+        assert_eq!(category("/home/a/Downloads/disk.iso", gib, None), "Disk Images");
+    }
+
+    #[test]
+    fn test_invalid_rule_patterns() {
+        let config: SpacemapConfig = toml::from_str("[[categories]]\nname = \"Bad\"\npaths = [\"re:(\"]\n").unwrap();
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.category, "Bad");
+
+        let config: SpacemapConfig = toml::from_str("[[categories]]\nname = \"Bad\"\nfilenames = [\"a[\"]\n").unwrap();
+        assert!(config.validate().is_err());
+        // Extension-only categories have nothing to compile
+        let config: SpacemapConfig = toml::from_str("[[categories]]\nname = \"Docs\"\nextensions = [\"a[\"]\n").unwrap();
+        assert!(config.validate().is_ok());
+    }
+}
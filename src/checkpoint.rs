@@ -1,11 +1,17 @@
+use crate::collector::CollectorState;
+use crate::cleanup::CleanupCandidate;
+use crate::duplicates::DuplicateFinder;
 use crate::scanner::ScanStats;
-use crate::types::ScanResults;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+/// Current checkpoint format. Checkpoints from older versions only carried
+/// progress counters and can't be resumed.
+pub const CHECKPOINT_VERSION: u8 = 3;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanCheckpoint {
     pub version: u8,
@@ -13,7 +19,14 @@ pub struct ScanCheckpoint {
     pub last_checkpoint: SystemTime,
     pub scanned_path: PathBuf,
     pub stats: CheckpointStats,
-    pub partial_results: Option<ScanResults>,
+    /// Options that shape the results, as `(name, value)` pairs; a checkpoint
+    /// is only resumed with identical options
+    pub options: Vec<(String, String)>,
+    /// Directories that were queued but not read yet
+    pub pending: Vec<PendingDir>,
+    /// Everything collected from the directories already read; `None` until
+    /// the first checkpoint after the scan started
+    pub state: Option<ScanState>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,11 +36,35 @@ pub struct CheckpointStats {
     pub dir_count: u64,
 }
 
+/// A directory still to be read, with its depth below the scan root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingDir {
+    pub path: PathBuf,
+    pub depth: usize,
+}
+
+/// Scan state at a checkpoint, enough to continue without rereading
+/// completed directories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanState {
+    pub stats: ScanStats,
+    pub collector: CollectorState,
+    /// Hard-linked inodes already counted
+    pub hard_links: Vec<(u64, u64)>,
+    /// Directories already entered, when following symlinks
+    pub visited_dirs: Vec<(u64, u64)>,
+    /// Size groups collected so far, with `--find-duplicates`
+    pub duplicates: Option<DuplicateFinder>,
+    /// Matching directories seen so far, with `--suggest-cleanup`
+    #[serde(default)]
+    pub cleanup: Option<Vec<CleanupCandidate>>,
+}
+
 impl ScanCheckpoint {
-    pub fn new(scanned_path: PathBuf) -> Self {
+    pub fn new(scanned_path: PathBuf, options: Vec<(String, String)>) -> Self {
         let now = SystemTime::now();
         Self {
-            version: 1,
+            version: CHECKPOINT_VERSION,
             started_at: now,
             last_checkpoint: now,
             scanned_path,
@@ -36,29 +73,81 @@ impl ScanCheckpoint {
                 file_count: 0,
                 dir_count: 0,
             },
-            partial_results: None,
+            options,
+            pending: Vec::new(),
+            state: None,
         }
     }
 
     pub fn load(path: &Path) -> io::Result<Self> {
         let contents = fs::read(path)?;
+
+        // `version` is serialized first, so older formats are recognized before
+        // the rest fails to decode
+        if let Some(&version) = contents.first() {
+            if version != CHECKPOINT_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("checkpoint format v{} is not supported (expected v{})", version, CHECKPOINT_VERSION),
+                ));
+            }
+        }
+
         bincode::deserialize(&contents)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
+    /// Write the checkpoint. The previous checkpoint stays intact until the
+    /// new one is complete.
     pub fn save(&self, path: &Path) -> io::Result<()> {
         let contents = bincode::serialize(self)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        fs::write(path, contents)
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, path)
     }
 
-    pub fn update_from_stats(&mut self, stats: &ScanStats) {
-        self.stats.total_bytes = stats.total_bytes;
-        self.stats.file_count = stats.file_count;
-        self.stats.dir_count = stats.dir_count;
+    /// Record the state of a running scan.
+    pub fn update(&mut self, pending: Vec<PendingDir>, state: ScanState) {
+        self.stats = CheckpointStats {
+            total_bytes: state.stats.total_bytes,
+            file_count: state.stats.file_count,
+            dir_count: state.stats.dir_count,
+        };
+        self.pending = pending;
+        self.state = Some(state);
         self.last_checkpoint = SystemTime::now();
     }
 
+    /// Why this checkpoint can't be resumed as a scan of `path` with
+    /// `options`, if it can't.
+    pub fn check_compatible(&self, path: &Path, options: &[(String, String)]) -> Result<(), String> {
+        if self.scanned_path != path {
+            return Err(format!("checkpoint is for {}", self.scanned_path.display()));
+        }
+
+        let differing: Vec<String> = options
+            .iter()
+            .filter_map(|(name, value)| {
+                let saved = self
+                    .options
+                    .iter()
+                    .find(|(saved_name, _)| saved_name == name)
+                    .map(|(_, saved_value)| saved_value.as_str());
+                (saved != Some(value.as_str())).then(|| {
+                    format!("{} ({} in checkpoint, now {})", name, saved.unwrap_or("unset"), value)
+                })
+            })
+            .collect();
+
+        if differing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("checkpoint was taken with different options: {}", differing.join(", ")))
+        }
+    }
+
     pub fn should_checkpoint(&self, interval_seconds: u64) -> bool {
         if let Ok(elapsed) = self.last_checkpoint.elapsed() {
             elapsed.as_secs() >= interval_seconds
@@ -73,15 +162,35 @@ mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    fn options(mode: &str) -> Vec<(String, String)> {
+        vec![
+            ("--by".to_string(), mode.to_string()),
+            ("--exclude".to_string(), "target".to_string()),
+        ]
+    }
+
     #[test]
     fn test_checkpoint_save_load() {
-        let checkpoint = ScanCheckpoint::new(PathBuf::from("/test/path"));
+        let checkpoint = ScanCheckpoint::new(PathBuf::from("/test/path"), options("type"));
 
         let temp_file = NamedTempFile::new().unwrap();
         checkpoint.save(temp_file.path()).unwrap();
 
         let loaded = ScanCheckpoint::load(temp_file.path()).unwrap();
         assert_eq!(loaded.scanned_path, PathBuf::from("/test/path"));
-        assert_eq!(loaded.version, 1);
+        assert_eq!(loaded.version, CHECKPOINT_VERSION);
+        assert_eq!(loaded.options, options("type"));
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_different_options() {
+        let checkpoint = ScanCheckpoint::new(PathBuf::from("/data"), options("type"));
+
+        assert!(checkpoint.check_compatible(Path::new("/data"), &options("type")).is_ok());
+
+        let err = checkpoint.check_compatible(Path::new("/data"), &options("size")).unwrap_err();
+        assert!(err.contains("--by (type in checkpoint, now size)"), "{}", err);
+
+        assert!(checkpoint.check_compatible(Path::new("/other"), &options("type")).is_err());
     }
 }
@@ -0,0 +1,639 @@
+use crate::engine::{scan, ScanError, ScanOptions};
+use crate::types::{Bucket, DirTotals, ScanResults, Totals};
+use colored::*;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// How removed paths are disposed of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovalMethod {
+    /// Move to the XDG trash, so it can be restored
+    Trash,
+    /// Delete permanently
+    Delete,
+}
+
+impl RemovalMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemovalMethod::Trash => "trash",
+            RemovalMethod::Delete => "delete",
+        }
+    }
+}
+
+/// Default trash: `$XDG_DATA_HOME/Trash`, i.e. `~/.local/share/Trash`.
+pub fn default_trash_dir() -> PathBuf {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("Trash"),
+        _ => {
+            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            PathBuf::from(home).join(".local/share/Trash")
+        }
+    }
+}
+
+/// Default audit log: `~/.local/state/spacemap/removals.log`.
+pub fn default_log_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(home).join(".local/state/spacemap/removals.log")
+}
+
+/// A trash directory following the freedesktop.org trash specification:
+/// removed items go to `files/`, with a `.trashinfo` record of their original
+/// path and deletion date in `info/`.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Move `path` into the trash and return where it ended up.
+    ///
+    /// Only works within one filesystem; items on another filesystem than the
+    /// trash fail with `ErrorKind::CrossesDevices`.
+    pub fn put(&self, path: &Path) -> io::Result<PathBuf> {
+        let original = absolute(path)?;
+        let name = original
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a filesystem root"))?
+            .to_string_lossy()
+            .into_owned();
+
+        let files = self.dir.join("files");
+        let info = self.dir.join("info");
+        fs::create_dir_all(&files)?;
+        fs::create_dir_all(&info)?;
+
+        let record = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&original),
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+        );
+
+        // Creating the info file reserves the name, as the spec requires
+        for attempt in 1u32.. {
+            let trash_name = if attempt == 1 { name.clone() } else { format!("{}.{}", name, attempt) };
+            let info_path = info.join(format!("{}.trashinfo", trash_name));
+            let target = files.join(&trash_name);
+            if target.symlink_metadata().is_ok() {
+                continue;
+            }
+
+            let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            info_file.write_all(record.as_bytes())?;
+
+            return match fs::rename(path, &target) {
+                Ok(()) => Ok(target),
+                Err(e) => {
+                    let _ = fs::remove_file(&info_path);
+                    Err(e)
+                }
+            };
+        }
+        unreachable!("trash name attempts are unbounded")
+    }
+}
+
+/// A path to remove, measured beforehand.
+#[derive(Debug, Clone)]
+pub struct CleanTarget {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub totals: Totals,
+    /// Per-category sizes, keyed like the buckets of a scan with the same
+    /// mode; empty if unknown
+    pub buckets: Vec<Bucket>,
+}
+
+/// Paths to remove and what removing them would free.
+#[derive(Debug, Clone, Default)]
+pub struct CleanPlan {
+    pub targets: Vec<CleanTarget>,
+}
+
+impl CleanPlan {
+    /// Measure `paths` with `options`. Paths inside another listed path are
+    /// dropped, and filesystem roots, the home directory and anything holding
+    /// the trash are refused.
+    pub fn new(paths: &[PathBuf], options: &ScanOptions, trash: &Trash) -> Result<Self, CleanError> {
+        let mut resolved: Vec<(PathBuf, PathBuf)> = Vec::new();
+        for path in paths {
+            let metadata = fs::symlink_metadata(path).map_err(|e| CleanError::Io(path.clone(), e))?;
+            let absolute = check_removable(path, metadata.is_dir(), trash)?;
+            resolved.push((path.clone(), absolute));
+        }
+
+        let mut targets = Vec::new();
+        for (path, absolute) in &resolved {
+            let nested = resolved
+                .iter()
+                .any(|(_, other)| other != absolute && absolute.starts_with(other));
+            if nested || targets.iter().any(|t: &CleanTarget| &t.path == path) {
+                continue;
+            }
+            targets.push(CleanTarget::measure(path, options)?);
+        }
+
+        Ok(Self { targets })
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.targets.iter().map(|t| t.totals.total_bytes).sum()
+    }
+
+    pub fn disk_bytes(&self) -> u64 {
+        self.targets.iter().map(|t| t.totals.disk_bytes).sum()
+    }
+
+    pub fn file_count(&self) -> u64 {
+        self.targets.iter().map(|t| t.totals.file_count).sum()
+    }
+}
+
+impl CleanTarget {
+    fn measure(path: &Path, options: &ScanOptions) -> Result<Self, CleanError> {
+        let metadata = fs::symlink_metadata(path).map_err(|e| CleanError::Io(path.to_path_buf(), e))?;
+
+        // A symlink is removed itself, never what it points to
+        if metadata.file_type().is_symlink() {
+            return Ok(Self {
+                path: path.to_path_buf(),
+                is_dir: false,
+                totals: Totals::default(),
+                buckets: Vec::new(),
+            });
+        }
+
+        let results = scan(path, options.clone().follow_symlinks(false).collect_tops(false))
+            .map_err(CleanError::Scan)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            is_dir: metadata.is_dir(),
+            totals: results.totals,
+            buckets: results.buckets,
+        })
+    }
+}
+
+/// What happened to one target.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Trashed(PathBuf),
+    Deleted,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CleanReport {
+    pub outcomes: Vec<(CleanTarget, Outcome)>,
+}
+
+impl CleanReport {
+    /// Targets that are gone.
+    pub fn removed(&self) -> impl Iterator<Item = &CleanTarget> {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, Outcome::Failed(_)))
+            .map(|(target, _)| target)
+    }
+
+    pub fn freed_bytes(&self) -> u64 {
+        self.removed().map(|t| t.totals.total_bytes).sum()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.outcomes.len() - self.removed().count()
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize)]
+struct LogEntry<'a> {
+    time: String,
+    method: RemovalMethod,
+    path: String,
+    bytes: u64,
+    disk_bytes: u64,
+    file_count: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trashed_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// Removes the targets of a [`CleanPlan`] and records every attempt, as one
+/// JSON object per line, in an audit log.
+pub struct Cleaner {
+    trash: Trash,
+    log_path: PathBuf,
+}
+
+impl Cleaner {
+    pub fn new(trash: Trash, log_path: PathBuf) -> Self {
+        Self { trash, log_path }
+    }
+
+    pub fn trash(&self) -> &Trash {
+        &self.trash
+    }
+
+    pub fn log_path(&self) -> &Path {
+        &self.log_path
+    }
+
+    /// Remove every target of `plan`. Failures don't stop the remaining
+    /// removals; they are reported in the returned [`CleanReport`].
+    ///
+    /// Fails without removing anything if the audit log can't be opened.
+    pub fn execute(&self, plan: &CleanPlan, method: RemovalMethod) -> Result<CleanReport, CleanError> {
+        let mut log = self.open_log().map_err(|e| CleanError::Log(self.log_path.clone(), e))?;
+        let mut report = CleanReport::default();
+
+        for target in &plan.targets {
+            let outcome = match method {
+                RemovalMethod::Trash => match self.trash.put(&target.path) {
+                    Ok(location) => Outcome::Trashed(location),
+                    Err(e) if e.kind() == io::ErrorKind::CrossesDevices => Outcome::Failed(
+                        "on a different filesystem than the trash; delete it permanently instead".to_string(),
+                    ),
+                    Err(e) => Outcome::Failed(e.to_string()),
+                },
+                RemovalMethod::Delete => {
+                    let result = if target.is_dir {
+                        fs::remove_dir_all(&target.path)
+                    } else {
+                        fs::remove_file(&target.path)
+                    };
+                    match result {
+                        Ok(()) => Outcome::Deleted,
+                        Err(e) => Outcome::Failed(e.to_string()),
+                    }
+                }
+            };
+
+            let entry = LogEntry {
+                time: chrono::Local::now().to_rfc3339(),
+                method,
+                // Absolute, so the log makes sense without knowing the working directory
+                path: std::path::absolute(&target.path)
+                    .unwrap_or_else(|_| target.path.clone())
+                    .display()
+                    .to_string(),
+                bytes: target.totals.total_bytes,
+                disk_bytes: target.totals.disk_bytes,
+                file_count: target.totals.file_count,
+                trashed_to: match &outcome {
+                    Outcome::Trashed(location) => Some(location.display().to_string()),
+                    _ => None,
+                },
+                error: match &outcome {
+                    Outcome::Failed(e) => Some(e),
+                    _ => None,
+                },
+            };
+            let line = serde_json::to_string(&entry).expect("log entries always serialize");
+            writeln!(log, "{}", line).map_err(|e| CleanError::Log(self.log_path.clone(), e))?;
+
+            report.outcomes.push((target.clone(), outcome));
+        }
+
+        Ok(report)
+    }
+
+    fn open_log(&self) -> io::Result<File> {
+        if let Some(parent) = self.log_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.log_path)
+    }
+}
+
+impl Default for Cleaner {
+    fn default() -> Self {
+        Self::new(Trash::new(default_trash_dir()), default_log_path())
+    }
+}
+
+#[derive(Debug)]
+pub enum CleanError {
+    Io(PathBuf, io::Error),
+    Protected(PathBuf, &'static str),
+    Scan(ScanError),
+    Log(PathBuf, io::Error),
+}
+
+impl std::fmt::Display for CleanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CleanError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            CleanError::Protected(path, reason) => write!(f, "Refusing to remove {}: {}", path.display(), reason),
+            CleanError::Scan(e) => write!(f, "{}", e),
+            CleanError::Log(path, e) => write!(f, "Failed to write audit log {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for CleanError {}
+
+/// Subtract removed targets from `results`: totals, matching buckets and the
+/// mount they were on, plus top files, top directories and duplicates.
+pub fn update_results<'a>(results: &mut ScanResults, removed: impl IntoIterator<Item = &'a CleanTarget>) {
+    for target in removed {
+        let totals = &target.totals;
+        results.totals.total_bytes = results.totals.total_bytes.saturating_sub(totals.total_bytes);
+        results.totals.disk_bytes = results.totals.disk_bytes.saturating_sub(totals.disk_bytes);
+        results.totals.file_count = results.totals.file_count.saturating_sub(totals.file_count);
+        results.totals.dir_count = results.totals.dir_count.saturating_sub(totals.dir_count);
+
+        for removed_bucket in &target.buckets {
+            if let Some(bucket) = results.buckets.iter_mut().find(|b| b.key == removed_bucket.key) {
+                bucket.bytes = bucket.bytes.saturating_sub(removed_bucket.bytes);
+                bucket.disk_bytes = bucket.disk_bytes.saturating_sub(removed_bucket.disk_bytes);
+                bucket.file_count = bucket.file_count.saturating_sub(removed_bucket.file_count);
+            }
+        }
+
+        let target_path = target.path.as_path();
+        let mount = results
+            .mounts
+            .iter_mut()
+            .filter(|m| target_path.starts_with(&m.mount_point))
+            .max_by_key(|m| m.mount_point.len());
+        if let Some(mount) = mount {
+            mount.bytes = mount.bytes.saturating_sub(totals.total_bytes);
+            mount.disk_bytes = mount.disk_bytes.saturating_sub(totals.disk_bytes);
+            mount.file_count = mount.file_count.saturating_sub(totals.file_count);
+        }
+
+        let inside = |path: &str| Path::new(path).starts_with(target_path);
+        results.top_files.retain(|f| !inside(&f.path));
+        results.top_dirs.retain(|d| !inside(&d.path));
+        for dir in &mut results.top_dirs {
+            let counts = match results.dir_totals {
+                DirTotals::Cumulative => target_path.starts_with(&dir.path),
+                DirTotals::SelfOnly => !target.is_dir && target_path.parent() == Some(Path::new(&dir.path)),
+            };
+            if counts {
+                dir.bytes = dir.bytes.saturating_sub(totals.total_bytes);
+                dir.disk_bytes = dir.disk_bytes.saturating_sub(totals.disk_bytes);
+            }
+        }
+
+        if let Some(groups) = results.duplicates.as_mut() {
+            for group in groups.iter_mut() {
+                group.paths.retain(|p| !inside(p));
+                group.wasted_space = group.size * (group.paths.len() as u64).saturating_sub(1);
+            }
+            groups.retain(|g| g.paths.len() > 1);
+        }
+    }
+
+    let size_mode = results.size_mode;
+    results.buckets.retain(|b| b.file_count > 0);
+    let total = size_mode.select(results.totals.total_bytes, results.totals.disk_bytes);
+    for bucket in &mut results.buckets {
+        let bytes = size_mode.select(bucket.bytes, bucket.disk_bytes);
+        bucket.percent = if total > 0 { bytes as f64 / total as f64 * 100.0 } else { 0.0 };
+    }
+    results.top_dirs.sort_by_key(|d| std::cmp::Reverse(size_mode.select(d.bytes, d.disk_bytes)));
+}
+
+pub fn print_plan(plan: &CleanPlan, method: RemovalMethod, use_color: bool) {
+    println!();
+    let title = match method {
+        RemovalMethod::Trash => "Move to trash",
+        RemovalMethod::Delete => "Delete permanently",
+    };
+    println!("  {}", style_text(title, "cyan", true, use_color));
+    println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
+
+    for target in &plan.targets {
+        let name = if target.is_dir {
+            format!("{}/", target.path.display())
+        } else {
+            target.path.display().to_string()
+        };
+        println!(
+            "  {:>12}  {:>8} files  {}",
+            style_text(&format_size(target.totals.total_bytes, BINARY), "yellow", false, use_color),
+            target.totals.file_count,
+            name
+        );
+    }
+
+    println!(
+        "  {}  {} in {} files ({} on disk)",
+        style_text("TOTAL", "green", true, use_color),
+        style_text(&format_size(plan.bytes(), BINARY), "green", true, use_color),
+        plan.file_count(),
+        format_size(plan.disk_bytes(), BINARY)
+    );
+    println!();
+}
+
+pub fn print_report(report: &CleanReport, log_path: &Path, use_color: bool) {
+    for (target, outcome) in &report.outcomes {
+        match outcome {
+            Outcome::Trashed(location) => println!(
+                "  {} {} -> {}",
+                style_text("trashed", "green", false, use_color),
+                target.path.display(),
+                location.display()
+            ),
+            Outcome::Deleted => println!("  {} {}", style_text("deleted", "green", false, use_color), target.path.display()),
+            Outcome::Failed(e) => println!(
+                "  {} {}: {}",
+                style_text("failed ", "red", true, use_color),
+                target.path.display(),
+                e
+            ),
+        }
+    }
+
+    println!();
+    println!(
+        "  {}  {} freed; logged to {}",
+        style_text("DONE", "green", true, use_color),
+        style_text(&format_size(report.freed_bytes(), BINARY), "green", true, use_color),
+        log_path.display()
+    );
+    if report.failures() > 0 {
+        println!(
+            "  {}",
+            style_text(&format!("{} path(s) could not be removed", report.failures()), "red", false, use_color)
+        );
+    }
+    println!();
+}
+
+/// Refuse filesystem roots, the home directory and anything holding the
+/// trash. Returns the absolute path otherwise.
+pub fn check_removable(path: &Path, is_dir: bool, trash: &Trash) -> Result<PathBuf, CleanError> {
+    let absolute = absolute(path).map_err(|e| CleanError::Io(path.to_path_buf(), e))?;
+    match protected(&absolute, is_dir, trash) {
+        Some(reason) => Err(CleanError::Protected(path.to_path_buf(), reason)),
+        None => Ok(absolute),
+    }
+}
+
+/// Why `path` must not be removed, if it must not.
+fn protected(path: &Path, is_dir: bool, trash: &Trash) -> Option<&'static str> {
+    if path.parent().is_none() {
+        return Some("it is a filesystem root");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        if is_dir && path == Path::new(&home) {
+            return Some("it is the home directory");
+        }
+    }
+    let trash_dir = absolute(trash.dir()).unwrap_or_else(|_| trash.dir().to_path_buf());
+    if trash_dir.starts_with(path) || path.starts_with(&trash_dir) {
+        return Some("it is or contains the trash");
+    }
+    None
+}
+
+/// `path` made absolute with symlinks in its parent resolved; the final
+/// component is kept as is, so a symlink still names the link.
+fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let absolute = std::path::absolute(path)?;
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => Ok(fs::canonicalize(parent)?.join(name)),
+        _ => Ok(absolute),
+    }
+}
+
+/// Percent-encode a path for a `.trashinfo` file.
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn style_text(text: &str, color: &str, bold: bool, use_color: bool) -> String {
+    if !use_color {
+        return text.to_string();
+    }
+
+    let colored = match color {
+        "red" => text.red(),
+        "green" => text.green(),
+        "yellow" => text.yellow(),
+        "cyan" => text.cyan(),
+        "bright_black" => text.bright_black(),
+        _ => text.normal(),
+    };
+
+    if bold {
+        colored.bold().to_string()
+    } else {
+        colored.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, Cleaner) {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("data/build/obj")).unwrap();
+        fs::write(dir.path().join("data/build/obj/a.o"), vec![0u8; 4000]).unwrap();
+        fs::write(dir.path().join("data/build/log.txt"), vec![0u8; 1000]).unwrap();
+        fs::write(dir.path().join("data/keep.rs"), vec![0u8; 500]).unwrap();
+        let cleaner = Cleaner::new(Trash::new(dir.path().join("Trash")), dir.path().join("log/removals.log"));
+        (dir, cleaner)
+    }
+
+    #[test]
+    fn test_trash_and_log() {
+        let (dir, cleaner) = setup();
+        let build = dir.path().join("data/build");
+        let plan = CleanPlan::new(&[build.clone(), build.join("log.txt")], &ScanOptions::new(), cleaner.trash()).unwrap();
+
+        // The nested path is covered by its parent
+        assert_eq!(plan.targets.len(), 1);
+        assert_eq!(plan.bytes(), 5000);
+        assert_eq!(plan.file_count(), 2);
+
+        let report = cleaner.execute(&plan, RemovalMethod::Trash).unwrap();
+        assert_eq!(report.freed_bytes(), 5000);
+        assert!(!build.exists());
+        assert!(dir.path().join("Trash/files/build/obj/a.o").exists());
+        let info = fs::read_to_string(dir.path().join("Trash/info/build.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=/"), "{}", info);
+        assert!(info.contains("/data/build\nDeletionDate="), "{}", info);
+
+        let log = fs::read_to_string(cleaner.log_path()).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(entry["method"], "trash");
+        assert_eq!(entry["bytes"], 5000);
+
+        // A second item with the same name gets a new trash name
+        fs::create_dir(&build).unwrap();
+        let plan = CleanPlan::new(std::slice::from_ref(&build), &ScanOptions::new(), cleaner.trash()).unwrap();
+        cleaner.execute(&plan, RemovalMethod::Trash).unwrap();
+        assert!(dir.path().join("Trash/files/build.2").exists());
+    }
+
+    #[test]
+    fn test_delete_updates_results() {
+        let (dir, cleaner) = setup();
+        let data = dir.path().join("data");
+        let options = ScanOptions::new();
+        let mut results = scan(&data, options.clone()).unwrap();
+
+        let plan = CleanPlan::new(&[data.join("build/obj")], &options, cleaner.trash()).unwrap();
+        let report = cleaner.execute(&plan, RemovalMethod::Delete).unwrap();
+        assert!(!data.join("build/obj").exists());
+
+        update_results(&mut results, report.removed());
+        let rescanned = scan(&data, options).unwrap();
+        assert_eq!(results.totals.total_bytes, rescanned.totals.total_bytes);
+        assert_eq!(results.totals.file_count, rescanned.totals.file_count);
+        let bytes = |r: &ScanResults| {
+            let mut buckets: Vec<_> = r.buckets.iter().map(|b| (b.key.clone(), b.bytes)).collect();
+            buckets.sort();
+            buckets
+        };
+        assert_eq!(bytes(&results), bytes(&rescanned));
+        assert!(results.top_files.iter().all(|f| !f.path.ends_with("a.o")));
+        let build = results.top_dirs.iter().find(|d| d.path.ends_with("build")).unwrap();
+        assert_eq!(build.bytes, 1000);
+    }
+
+    #[test]
+    fn test_refuses_protected_paths() {
+        let (dir, cleaner) = setup();
+        let trash = dir.path().join("Trash");
+        fs::create_dir_all(&trash).unwrap();
+
+        for path in [PathBuf::from("/"), trash, dir.path().to_path_buf()] {
+            let err = CleanPlan::new(&[path], &ScanOptions::new(), cleaner.trash()).unwrap_err();
+            assert!(matches!(err, CleanError::Protected(..)), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Cleanup suggestions (`--suggest-cleanup`): directories that a build or
+//! package tool can recreate, such as `node_modules` next to a
+//! `package.json`, ranked by the space removing them would reclaim.
+
+use crate::config::SpacemapConfig;
+use crate::types::{FileMetadata, SizeMode};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A kind of reclaimable directory.
+///
+/// Rules match either by directory name anywhere in the scan (`directory`) or
+/// one specific directory (`path`, where `~/` is the home directory).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupRule {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Files next to the directory, any of which shows it can be regenerated.
+    /// Without markers the directory is always considered regenerable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub markers: Vec<String>,
+    /// How to get it back, or a better way to reclaim the space
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl CleanupRule {
+    fn directory(name: &str, directory: &str, markers: &[&str], hint: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            directory: Some(directory.to_string()),
+            path: None,
+            markers: markers.iter().map(|m| m.to_string()).collect(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn path(name: &str, path: &str, hint: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            directory: None,
+            path: Some(path.to_string()),
+            markers: Vec::new(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    /// The rules used when the config doesn't change them.
+    pub fn builtin() -> Vec<CleanupRule> {
+        const PYTHON_PROJECT: &[&str] = &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"];
+        const GRADLE_PROJECT: &[&str] = &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"];
+
+        vec![
+            Self::directory("node_modules", "node_modules", &["package.json"], "npm install"),
+            Self::directory("Cargo target", "target", &["Cargo.toml"], "cargo build"),
+            Self::directory("Python bytecode", "__pycache__", &[], "recreated on import"),
+            Self::directory("Python virtualenv", ".venv", PYTHON_PROJECT, "recreate the virtualenv and reinstall"),
+            Self::directory("Python virtualenv", "venv", PYTHON_PROJECT, "recreate the virtualenv and reinstall"),
+            Self::directory("tox environments", ".tox", &["tox.ini", "pyproject.toml", "setup.cfg"], "tox"),
+            Self::directory("pytest cache", ".pytest_cache", &[], "recreated by pytest"),
+            Self::directory("mypy cache", ".mypy_cache", &[], "recreated by mypy"),
+            Self::directory("Gradle project cache", ".gradle", GRADLE_PROJECT, "gradle build"),
+            Self::path("Gradle caches", "~/.gradle/caches", "downloaded again by gradle"),
+            Self::path("pip cache", "~/.cache/pip", "pip cache purge"),
+            Self::path("npm cache", "~/.npm/_cacache", "npm cache clean --force"),
+            Self::path("Cargo registry cache", "~/.cargo/registry/cache", "downloaded again by cargo"),
+            Self::path("Go build cache", "~/.cache/go-build", "go clean -cache"),
+            Self::path("Docker build cache", "/var/lib/docker/buildkit", "docker builder prune"),
+        ]
+    }
+
+    /// Built-in rules with the config's `[[cleanup]]` rules applied: a rule
+    /// named like a built-in replaces it, and one with neither `directory`
+    /// nor `path` turns it off.
+    pub fn from_config(config: Option<&SpacemapConfig>) -> Vec<CleanupRule> {
+        let mut rules = Self::builtin();
+        for rule in config.map(|c| c.cleanup.as_slice()).unwrap_or_default() {
+            rules.retain(|r| r.name != rule.name);
+            if rule.directory.is_some() || rule.path.is_some() {
+                rules.push(rule.clone());
+            }
+        }
+        rules
+    }
+}
+
+/// Files seen so far inside one matching directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupCandidate {
+    pub path: PathBuf,
+    pub rule: String,
+    pub bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    pub newest: Option<SystemTime>,
+}
+
+/// A directory that can be removed and regenerated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupSuggestion {
+    pub rule: String,
+    pub path: String,
+    pub bytes: u64,
+    #[serde(default)]
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    /// Newest modification time of any file inside (RFC 3339)
+    pub last_modified: Option<String>,
+    /// Whole days since `last_modified`
+    pub age_days: Option<u64>,
+    /// The marker file that shows it can be regenerated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regenerated_from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Attributes every scanned file to the outermost directory matching a
+/// [`CleanupRule`], then keeps the directories that can be regenerated.
+pub struct CleanupAnalyzer {
+    rules: Vec<CleanupRule>,
+    root: PathBuf,
+    by_name: HashMap<OsString, usize>,
+    by_path: Vec<(PathBuf, usize)>,
+    candidates: Mutex<HashMap<PathBuf, CleanupCandidate>>,
+}
+
+impl CleanupAnalyzer {
+    pub fn new(root: &Path, rules: Vec<CleanupRule>) -> Self {
+        let home = dirs::home_dir();
+        let mut by_name = HashMap::new();
+        let mut by_path = Vec::new();
+        for (i, rule) in rules.iter().enumerate() {
+            if let Some(ref name) = rule.directory {
+                // Later rules, i.e. the config's, win
+                by_name.insert(OsString::from(name), i);
+            }
+            if let Some(ref path) = rule.path {
+                let path = match (path.strip_prefix("~/"), &home) {
+                    (Some(rest), Some(home)) => home.join(rest),
+                    (Some(_), None) => continue,
+                    (None, _) => PathBuf::from(path),
+                };
+                by_path.push((path, i));
+            }
+        }
+
+        Self {
+            rules,
+            root: root.to_path_buf(),
+            by_name,
+            by_path,
+            candidates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Continue from candidates saved in a checkpoint.
+    pub fn with_candidates(self, candidates: Vec<CleanupCandidate>) -> Self {
+        self.candidates
+            .lock()
+            .extend(candidates.into_iter().map(|c| (c.path.clone(), c)));
+        self
+    }
+
+    /// Candidates collected so far, for a checkpoint.
+    pub fn candidates(&self) -> Vec<CleanupCandidate> {
+        self.candidates.lock().values().cloned().collect()
+    }
+
+    pub fn add_file(&self, file: &FileMetadata) {
+        let Some((dir, rule)) = self.matching_dir(&file.path) else {
+            return;
+        };
+
+        let mut candidates = self.candidates.lock();
+        let candidate = candidates.entry(dir.clone()).or_insert_with(|| CleanupCandidate {
+            path: dir,
+            rule: self.rules[rule].name.clone(),
+            bytes: 0,
+            disk_bytes: 0,
+            file_count: 0,
+            newest: None,
+        });
+        candidate.bytes += file.size;
+        candidate.disk_bytes += file.disk_size;
+        candidate.file_count += 1;
+        candidate.newest = candidate.newest.max(file.modified);
+    }
+
+    /// The outermost directory above `path` that a rule matches.
+    fn matching_dir(&self, path: &Path) -> Option<(PathBuf, usize)> {
+        let by_path = self
+            .by_path
+            .iter()
+            .filter(|(dir, _)| path.starts_with(dir) && path != dir)
+            .min_by_key(|(dir, _)| dir.components().count())
+            .map(|(dir, rule)| (dir.clone(), *rule));
+
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut dir = self.root.clone();
+        let mut by_name = None;
+        // The last component is the file itself
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            if components.peek().is_none() {
+                break;
+            }
+            dir.push(component);
+            if let Some(&rule) = self.by_name.get(component.as_os_str()) {
+                by_name = Some((dir, rule));
+                break;
+            }
+        }
+
+        match (by_path, by_name) {
+            (Some(a), Some(b)) => Some(if b.0.starts_with(&a.0) { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Regenerable directories, largest first in `size_mode`.
+    pub fn suggestions(self, size_mode: SizeMode) -> Vec<CleanupSuggestion> {
+        let rules: HashMap<&str, &CleanupRule> = self.rules.iter().map(|r| (r.name.as_str(), r)).collect();
+        let now = SystemTime::now();
+
+        let mut suggestions: Vec<CleanupSuggestion> = self
+            .candidates
+            .into_inner()
+            .into_values()
+            .filter_map(|candidate| {
+                // Saved by a checkpoint taken with rules that no longer exist
+                let rule = rules.get(candidate.rule.as_str())?;
+                let regenerated_from = regenerable(&candidate.path, rule)?;
+                Some(CleanupSuggestion {
+                    path: candidate.path.display().to_string(),
+                    bytes: candidate.bytes,
+                    disk_bytes: candidate.disk_bytes,
+                    file_count: candidate.file_count,
+                    last_modified: candidate
+                        .newest
+                        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+                    age_days: candidate
+                        .newest
+                        .map(|t| now.duration_since(t).map(|d| d.as_secs() / 86400).unwrap_or(0)),
+                    regenerated_from,
+                    hint: rule.hint.clone(),
+                    rule: candidate.rule,
+                })
+            })
+            .collect();
+
+        suggestions.sort_by(|a, b| {
+            let size = |s: &CleanupSuggestion| size_mode.select(s.bytes, s.disk_bytes);
+            size(b).cmp(&size(a)).then_with(|| a.path.cmp(&b.path))
+        });
+        suggestions
+    }
+}
+
+/// `Some` with the marker found next to `dir` (or `Some(None)` for a rule
+/// without markers) if `dir` can be regenerated.
+fn regenerable(dir: &Path, rule: &CleanupRule) -> Option<Option<String>> {
+    if rule.markers.is_empty() {
+        return Some(None);
+    }
+    let parent = dir.parent()?;
+    rule.markers
+        .iter()
+        .map(|marker| parent.join(marker))
+        .find(|marker| marker.exists())
+        .map(|marker| Some(marker.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn file(path: PathBuf, size: u64) -> FileMetadata {
+        FileMetadata {
+            path,
+            size,
+            disk_size: size,
+            extension: None,
+            modified: Some(SystemTime::now()),
+        }
+    }
+
+    #[test]
+    fn test_suggests_regenerable_directories() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("app/node_modules/left-pad/node_modules")).unwrap();
+        fs::write(root.join("app/package.json"), "{}").unwrap();
+        fs::create_dir_all(root.join("crate/target/debug")).unwrap();
+        fs::write(root.join("crate/Cargo.toml"), "").unwrap();
+        // No Cargo.toml next to it, so not a build directory
+        fs::create_dir_all(root.join("photos/target")).unwrap();
+
+        let analyzer = CleanupAnalyzer::new(root, CleanupRule::builtin());
+        analyzer.add_file(&file(root.join("app/node_modules/left-pad/index.js"), 100));
+        analyzer.add_file(&file(root.join("app/node_modules/left-pad/node_modules/x.js"), 50));
+        analyzer.add_file(&file(root.join("app/index.js"), 1000));
+        analyzer.add_file(&file(root.join("crate/target/debug/crate"), 5000));
+        analyzer.add_file(&file(root.join("photos/target/cat.jpg"), 9000));
+
+        let suggestions = analyzer.suggestions(SizeMode::Apparent);
+        assert_eq!(suggestions.len(), 2);
+
+        assert_eq!(suggestions[0].rule, "Cargo target");
+        assert_eq!(suggestions[0].bytes, 5000);
+        assert_eq!(suggestions[0].age_days, Some(0));
+        assert!(suggestions[0].regenerated_from.as_ref().unwrap().ends_with("Cargo.toml"));
+
+        // Nested node_modules count towards the outermost one
+        assert_eq!(suggestions[1].rule, "node_modules");
+        assert_eq!(suggestions[1].path, root.join("app/node_modules").display().to_string());
+        assert_eq!(suggestions[1].bytes, 150);
+        assert_eq!(suggestions[1].file_count, 2);
+    }
+
+    #[test]
+    fn test_config_rules() {
+        let config: SpacemapConfig = toml::from_str(
+            r#"
+            [[cleanup]]
+            name = "node_modules"
+
+            [[cleanup]]
+            name = "Build output"
+            directory = "build"
+            markers = ["Makefile"]
+            hint = "make"
+            "#,
+        )
+        .unwrap();
+        let rules = CleanupRule::from_config(Some(&config));
+        assert!(!rules.iter().any(|r| r.name == "node_modules"));
+        assert!(rules.iter().any(|r| r.name == "Cargo target"));
+
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("p/build")).unwrap();
+        fs::write(root.join("p/Makefile"), "").unwrap();
+        fs::write(root.join("p/package.json"), "{}").unwrap();
+
+        let analyzer = CleanupAnalyzer::new(root, rules);
+        analyzer.add_file(&file(root.join("p/build/out.o"), 10));
+        analyzer.add_file(&file(root.join("p/node_modules/x.js"), 10));
+
+        let suggestions = analyzer.suggestions(SizeMode::Apparent);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].rule, "Build output");
+        assert_eq!(suggestions[0].hint.as_deref(), Some("make"));
+    }
+
+    #[test]
+    fn test_path_rules() {
+        let dir = TempDir::new().unwrap();
+        let cache = dir.path().join("cache/pip");
+        let rules = vec![CleanupRule::path("pip cache", &cache.display().to_string(), "pip cache purge")];
+
+        let analyzer = CleanupAnalyzer::new(dir.path(), rules);
+        analyzer.add_file(&file(cache.join("http/a"), 10));
+        analyzer.add_file(&file(cache.join("wheels/b"), 20));
+        analyzer.add_file(&file(dir.path().join("cache/other"), 40));
+
+        let suggestions = analyzer.suggestions(SizeMode::Apparent);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].path, cache.display().to_string());
+        assert_eq!(suggestions[0].bytes, 30);
+        assert_eq!(suggestions[0].regenerated_from, None);
+    }
+}
@@ -1,17 +1,23 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use spacemap::config::SpacemapConfig;
+use spacemap::output::OutputFormat;
+use spacemap::{DirTotals, ScanMode, ScanOptions, SizeMode};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "spacemap")]
 #[command(about = "A beautiful CLI disk space analyzer", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to scan (defaults to current directory)
     #[arg(value_name = "PATH")]
     pub path: Option<PathBuf>,
 
-    /// Categorization mode: type, size, or age
-    #[arg(long, value_name = "MODE", default_value = "type")]
-    pub by: String,
+    #[command(flatten)]
+    pub scan: ScanArgs,
 
     /// Show verbose output with drill-down sections
     #[arg(long, short)]
@@ -21,66 +27,79 @@ pub struct Cli {
     #[arg(long, default_value = "10")]
     pub top: usize,
 
-    /// Maximum depth for directory recursion
-    #[arg(long, value_name = "N")]
-    pub max_depth: Option<usize>,
-
-    /// Glob or regex patterns to exclude
-    #[arg(long, value_name = "PATTERN")]
-    pub exclude: Vec<String>,
-
-    /// Follow symbolic links (disabled by default)
-    #[arg(long)]
-    pub follow_symlinks: bool,
-
     /// Disable colored output
     #[arg(long)]
     pub no_color: bool,
 
-    /// Output JSON to stdout
+    /// Output JSON to stdout (same as --format json)
     #[arg(long)]
     pub json: bool,
 
-    /// Write JSON output to file
+    /// Output format: terminal, json, csv, tsv, html, or prometheus
+    #[arg(long, value_name = "FORMAT", default_value = "terminal")]
+    pub format: String,
+
+    /// Write output to FILE instead of stdout (JSON unless --format is given);
+    /// csv and tsv write one file per section into the directory FILE,
+    /// html writes a single self-contained page
     #[arg(long, value_name = "FILE")]
     pub output: Option<PathBuf>,
 
-    /// Custom size bucket boundaries (comma-separated bytes)
-    #[arg(long, value_name = "SPEC")]
-    pub size_buckets: Option<String>,
-
-    /// Custom age bucket boundaries (comma-separated days)
-    #[arg(long, value_name = "SPEC")]
-    pub age_buckets: Option<String>,
-
     /// Show progress indicator during scan
     #[arg(long)]
     pub progress: bool,
 
-    /// Enable parallel directory scanning for faster performance
+    /// Find and report duplicate files
     #[arg(long)]
-    pub parallel: bool,
+    pub find_duplicates: bool,
 
-    /// Number of threads for parallel scanning (0 = auto-detect)
-    #[arg(long, default_value = "0")]
-    pub threads: usize,
+    /// Suggest regenerable directories to remove (node_modules, target/, caches, ...)
+    #[arg(long)]
+    pub suggest_cleanup: bool,
 
-    /// Find and report duplicate files
+    /// Check the config's [[policy]] rules; exit with code 4 if any is violated
     #[arg(long)]
-    pub find_duplicates: bool,
+    pub check_policy: bool,
+
+    /// Write the policy check as JUnit XML to FILE
+    #[arg(long, value_name = "FILE", requires = "check_policy")]
+    pub junit: Option<PathBuf>,
+
+    /// Index every file into the SQLite database FILE; new scans are appended
+    #[arg(long, value_name = "FILE", conflicts_with = "resume")]
+    pub sqlite: Option<PathBuf>,
+
+    /// Keep the report up to date as files change, running the config's [[alert]] rules
+    #[arg(
+        long,
+        conflicts_with_all = ["checkpoint", "resume", "sqlite", "find_duplicates", "suggest_cleanup", "check_policy", "output"]
+    )]
+    pub watch: bool,
+
+    /// With --watch, seconds between full rescans when the OS watch limit is reached
+    #[arg(long, value_name = "SECONDS", default_value = "60", requires = "watch")]
+    pub watch_interval: u64,
 
-    /// Compare two scan results (provide two JSON file paths)
+    /// Compare two scans: JSON results files, or --sqlite indexes as FILE (latest scan) or FILE#ID
     #[arg(long, value_names = ["BEFORE", "AFTER"], num_args = 2)]
     pub compare: Option<Vec<std::path::PathBuf>>,
 
-    /// Use cached scan results (skip unchanged directories)
+    /// With --compare, exit with code 5 if the total grew by more than a size (10G) or percentage (5%)
+    #[arg(long, value_name = "SIZE|PERCENT", requires = "compare")]
+    pub fail_if_growth: Option<String>,
+
+    /// Reuse cached listings of directories that haven't changed since the last --cached scan
     #[arg(long)]
     pub cached: bool,
 
-    /// Cache directory (default: ~/.cache/spacemap)
+    /// Cache directory, also used for the scan history (default: ~/.cache/spacemap)
     #[arg(long, value_name = "DIR")]
     pub cache_dir: Option<std::path::PathBuf>,
 
+    /// Don't record this scan in the history shown by `spacemap history`
+    #[arg(long)]
+    pub no_history: bool,
+
     /// Write checkpoint to file for resumable scans
     #[arg(long, value_name = "FILE")]
     pub checkpoint: Option<std::path::PathBuf>,
@@ -92,25 +111,276 @@ pub struct Cli {
     /// Checkpoint interval in seconds (default: 300)
     #[arg(long, default_value = "300")]
     pub checkpoint_interval: u64,
+}
+
+/// Subcommands. Without one, spacemap scans PATH and prints a report.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Scan once and browse the results interactively
+    Tui(TuiArgs),
+    /// Move files or directories to the trash, or delete them permanently
+    Clean(CleanArgs),
+    /// Rescan PATH on an interval and serve Prometheus metrics over HTTP
+    Exporter(ExporterArgs),
+    /// Serve an HTTP API that runs scans as jobs and returns their results
+    Serve(ServeArgs),
+    /// Show how PATH grew over its recorded scans and when its disk fills up
+    History(HistoryArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TuiArgs {
+    /// Path to scan (defaults to current directory)
+    #[arg(value_name = "PATH")]
+    pub path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub scan: ScanArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ExporterArgs {
+    /// Path to scan (defaults to current directory)
+    #[arg(value_name = "PATH")]
+    pub path: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub scan: ScanArgs,
+
+    /// Address to serve /metrics on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9854")]
+    pub listen: String,
+
+    /// Seconds between scans
+    #[arg(long, value_name = "SECONDS", default_value = "300")]
+    pub interval: u64,
+
+    /// Number of largest directories to export
+    #[arg(long, default_value = "10")]
+    pub top: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// Scanned path (defaults to current directory)
+    #[arg(value_name = "PATH")]
+    pub path: Option<PathBuf>,
+
+    /// Number of most recent scans to show
+    #[arg(long, value_name = "N", default_value = "30")]
+    pub last: usize,
+
+    /// Cache directory the history is kept in (default: ~/.cache/spacemap)
+    #[arg(long, value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Output the scans and the trend as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Disable colored output
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Directory that scans may cover; repeat to allow several
+    #[arg(long, value_name = "DIR", required = true)]
+    pub root: Vec<PathBuf>,
+
+    /// Address to serve the API on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9855")]
+    pub listen: String,
+
+    /// Scans that may run at the same time
+    #[arg(long, value_name = "N", default_value = "2")]
+    pub workers: usize,
+
+    /// Scans that may wait for a worker before new ones are refused
+    #[arg(long, value_name = "N", default_value = "100")]
+    pub max_queued: usize,
+
+    /// Finished scans kept in memory for listing and comparison
+    #[arg(long, value_name = "N", default_value = "100")]
+    pub keep: usize,
 
     /// Path to config file (default: ~/.config/spacemap/config.toml)
     #[arg(long, value_name = "FILE")]
     pub config: Option<PathBuf>,
 }
 
-impl Cli {
+#[derive(Args, Debug)]
+pub struct CleanArgs {
+    /// Files or directories to remove
+    #[arg(value_name = "PATH", required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Delete permanently instead of moving to the trash
+    #[arg(long)]
+    pub permanent: bool,
+
+    /// Show what would be removed and freed, without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't ask for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+
+    /// Trash directory (default: $XDG_DATA_HOME/Trash or ~/.local/share/Trash)
+    #[arg(long, value_name = "DIR")]
+    pub trash_dir: Option<PathBuf>,
+
+    /// Audit log of removals (default: ~/.local/state/spacemap/removals.log)
+    #[arg(long, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+
+    /// Disable colored output
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+/// Options that control how a tree is scanned, shared by every command that scans.
+#[derive(Args, Debug)]
+pub struct ScanArgs {
+    /// Categorization mode: type, size, or age
+    #[arg(long, value_name = "MODE", default_value = "type")]
+    pub by: String,
+
+    /// Size to report: apparent (file length) or disk (allocated blocks, like du)
+    #[arg(long, value_name = "MODE", default_value = "apparent")]
+    pub size_mode: String,
+
+    /// Directory totals: cumulative (everything below a directory) or self (direct files only)
+    #[arg(long, value_name = "MODE", default_value = "cumulative")]
+    pub dir_totals: String,
+
+    /// Maximum depth for directory recursion
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Patterns to exclude: gitignore-style globs relative to PATH, or re:<regex>
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Follow symbolic links (disabled by default)
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Stay on the filesystem of PATH and skip mount points below it
+    #[arg(short = 'x', long)]
+    pub one_file_system: bool,
+
+    /// Detect the type of files with unknown extensions from their first bytes (--by type)
+    #[arg(long)]
+    pub sniff: bool,
+
+    /// Custom size bucket boundaries (comma-separated bytes)
+    #[arg(long, value_name = "SPEC")]
+    pub size_buckets: Option<String>,
+
+    /// Custom age bucket boundaries (comma-separated days)
+    #[arg(long, value_name = "SPEC")]
+    pub age_buckets: Option<String>,
+
+    /// Enable parallel directory scanning for faster performance
+    #[arg(long)]
+    pub parallel: bool,
+
+    /// Number of threads for parallel scanning (0 = auto-detect)
+    #[arg(long, default_value = "0")]
+    pub threads: usize,
+
+    /// Path to config file (default: ~/.config/spacemap/config.toml)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+}
+
+impl ScanArgs {
     pub fn validate(&self) -> Result<(), String> {
-        match self.by.as_str() {
-            "type" | "size" | "age" => Ok(()),
-            _ => Err(format!("Invalid --by mode: {}. Must be type, size, or age", self.by)),
+        self.mode()?;
+        self.size_mode()?;
+        self.dir_totals()?;
+        Ok(())
+    }
+
+    pub fn mode(&self) -> Result<ScanMode, String> {
+        self.by.parse()
+    }
+
+    pub fn size_mode(&self) -> Result<SizeMode, String> {
+        self.size_mode.parse()
+    }
+
+    pub fn dir_totals(&self) -> Result<DirTotals, String> {
+        self.dir_totals.parse()
+    }
+
+    /// Scan options set from these flags; output-specific options are left at
+    /// their defaults.
+    pub fn options(&self, config: Option<SpacemapConfig>) -> ScanOptions {
+        ScanOptions::new()
+            .mode(self.mode().unwrap_or_default())
+            .size_mode(self.size_mode().unwrap_or_default())
+            .dir_totals(self.dir_totals().unwrap_or_default())
+            .config(config)
+            .size_buckets(self.size_buckets.as_ref().and_then(|s| parse_size_buckets(s)))
+            .age_buckets(self.age_buckets.as_ref().and_then(|s| parse_age_buckets(s)))
+            .max_depth(self.max_depth)
+            .exclude(self.exclude.clone())
+            .follow_symlinks(self.follow_symlinks)
+            .one_file_system(self.one_file_system)
+            .parallel(self.parallel)
+            .threads(self.threads)
+            .sniff(self.sniff)
+    }
+}
+
+impl Cli {
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    pub fn format(&self) -> Result<OutputFormat, String> {
+        let format = self.format.parse()?;
+        if self.json || (self.output.is_some() && format == OutputFormat::Terminal) {
+            return Ok(OutputFormat::Json);
         }
+        Ok(format)
     }
 
+    /// Whether the output is for machines, which rules out progress and
+    /// status messages on the terminal.
+    pub fn is_machine_output(&self) -> bool {
+        self.format().is_ok_and(|f| f != OutputFormat::Terminal)
+    }
+}
+
+impl HistoryArgs {
     pub fn get_path(&self) -> PathBuf {
         self.path.clone().unwrap_or_else(|| PathBuf::from("."))
     }
+}
 
-    pub fn should_output_json(&self) -> bool {
-        self.json || self.output.is_some()
+impl TuiArgs {
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| PathBuf::from("."))
+    }
+}
+
+impl ExporterArgs {
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| PathBuf::from("."))
     }
 }
+
+fn parse_size_buckets(spec: &str) -> Option<Vec<u64>> {
+    let buckets: Result<Vec<u64>, _> = spec.split(',').map(|s| s.trim().parse::<u64>()).collect();
+    buckets.ok()
+}
+
+fn parse_age_buckets(spec: &str) -> Option<Vec<u64>> {
+    let buckets: Result<Vec<u64>, _> = spec.split(',').map(|s| s.trim().parse::<u64>()).collect();
+    buckets.ok()
+}
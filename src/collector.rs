@@ -1,14 +1,19 @@
 use crate::bounded_heap::BoundedMinHeap;
 use crate::categorize::Categorizer;
-use crate::types::{Bucket, DirEntry, FileEntry, FileMetadata};
+use crate::types::{Bucket, DirEntry, DirTotals, FileEntry, FileMetadata, SizeMode};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// A file with its size, ordered by size for use in BoundedMinHeap.
-#[derive(Debug, Clone, Eq, PartialEq)]
+///
+/// `size` is the ranking key (apparent or on-disk, depending on the size mode).
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 struct FileWithSize {
     path: PathBuf,
     size: u64,
+    bytes: u64,
+    disk_bytes: u64,
 }
 
 impl Ord for FileWithSize {
@@ -28,6 +33,26 @@ impl PartialOrd for FileWithSize {
 struct DirWithSize {
     path: PathBuf,
     size: u64,
+    bytes: u64,
+    disk_bytes: u64,
+}
+
+/// Aggregated totals for one category.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct CategoryStats {
+    bytes: u64,
+    disk_bytes: u64,
+    file_count: u64,
+    /// Files categorized by their content with `--sniff`
+    content_files: u64,
+    content_bytes: u64,
+}
+
+/// Apparent and on-disk bytes accumulated for one directory.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct DirSize {
+    bytes: u64,
+    disk_bytes: u64,
 }
 
 impl Ord for DirWithSize {
@@ -42,6 +67,18 @@ impl PartialOrd for DirWithSize {
     }
 }
 
+/// Everything a collector has accumulated so far, for checkpoints.
+///
+/// The categorizer and settings are not included; a state is restored into a
+/// collector built with the same options.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollectorState {
+    category_stats: HashMap<String, CategoryStats>,
+    category_extensions: HashMap<String, HashMap<String, u64>>,
+    top_files: Vec<FileWithSize>,
+    dir_accumulator: HashMap<PathBuf, DirSize>,
+}
+
 /// Results from single-pass collection.
 pub struct CollectionResults {
     pub buckets: Vec<Bucket>,
@@ -58,8 +95,8 @@ pub struct CollectionResults {
 pub struct SinglePassCollector {
     categorizer: Box<dyn Categorizer>,
 
-    // Category aggregation: category -> (total_bytes, file_count)
-    category_stats: HashMap<String, (u64, u64)>,
+    // Category aggregation: category -> totals
+    category_stats: HashMap<String, CategoryStats>,
 
     // Extension tracking per category: category -> (extension -> count)
     category_extensions: HashMap<String, HashMap<String, u64>>,
@@ -71,7 +108,17 @@ pub struct SinglePassCollector {
     // Note: We need to accumulate ALL directory sizes to be accurate,
     // but we use a bounded heap for the final top-N selection
     // Using PathBuf directly (not u32 IDs) for better parallel merge performance
-    dir_accumulator: HashMap<PathBuf, u64>,
+    // Only immediate parents are accumulated here; cumulative totals are rolled
+    // up once in finalize(), after any merges, instead of walking every
+    // file's ancestors during the scan.
+    dir_accumulator: HashMap<PathBuf, DirSize>,
+
+    // Self-only or cumulative directory totals, and where rollup stops
+    dir_totals: DirTotals,
+    root: Option<PathBuf>,
+
+    // Which size (apparent or allocated) drives top-N rankings
+    size_mode: SizeMode,
 
     // Capacity for top-N tracking
     top_n: usize,
@@ -96,9 +143,28 @@ impl SinglePassCollector {
             dir_accumulator: HashMap::new(),
             top_n,
             should_collect_tops,
+            size_mode: SizeMode::Apparent,
+            dir_totals: DirTotals::SelfOnly,
+            root: None,
         }
     }
 
+    /// Choose self-only or cumulative directory totals.
+    ///
+    /// Cumulative totals are rolled up through every ancestor of a file,
+    /// stopping at `root` (the scan root).
+    pub fn with_dir_totals(mut self, dir_totals: DirTotals, root: PathBuf) -> Self {
+        self.dir_totals = dir_totals;
+        self.root = Some(root);
+        self
+    }
+
+    /// Rank top files and directories by `size_mode` instead of apparent size.
+    pub fn with_size_mode(mut self, size_mode: SizeMode) -> Self {
+        self.size_mode = size_mode;
+        self
+    }
+
     /// Process a single file during the scan.
     ///
     /// This is called once for each file encountered during directory traversal.
@@ -106,18 +172,27 @@ impl SinglePassCollector {
     /// 1. Categorizes the file and updates category statistics
     /// 2. Potentially adds the file to the top-N heap
     /// 3. Accumulates the file's size to its parent directory
-    pub fn process_file(&mut self, metadata: FileMetadata) {
+    ///
+    /// Returns the key of the category the file was filed under.
+    pub fn process_file(&mut self, metadata: &FileMetadata) -> String {
         let size = metadata.size;
+        let disk_size = metadata.disk_size;
 
         // 1. Categorize and aggregate
-        let category = self.categorizer.categorize(&metadata).into_owned();
-        let entry = self.category_stats.entry(category.clone()).or_insert((0, 0));
-        entry.0 += size;
-        entry.1 += 1;
+        let (category, by_content) = self.categorizer.classify(metadata);
+        let category = category.into_owned();
+        let entry = self.category_stats.entry(category.clone()).or_default();
+        entry.bytes += size;
+        entry.disk_bytes += disk_size;
+        entry.file_count += 1;
+        if by_content {
+            entry.content_files += 1;
+            entry.content_bytes += size;
+        }
 
         // Track extensions for this category
         if let Some(ref ext) = metadata.extension {
-            let ext_map = self.category_extensions.entry(category.clone()).or_insert_with(HashMap::new);
+            let ext_map = self.category_extensions.entry(category.clone()).or_default();
             *ext_map.entry(ext.clone()).or_insert(0) += 1;
         }
 
@@ -125,34 +200,98 @@ impl SinglePassCollector {
             // 2. Track top files
             self.top_files_heap.push(FileWithSize {
                 path: metadata.path.clone(),
-                size,
+                size: self.size_mode.select(size, disk_size),
+                bytes: size,
+                disk_bytes: disk_size,
             });
 
             // 3. Accumulate directory sizes
             if let Some(parent) = metadata.path.parent() {
-                *self.dir_accumulator.entry(parent.to_path_buf()).or_insert(0) += size;
+                let dir = self.dir_accumulator.entry(parent.to_path_buf()).or_default();
+                dir.bytes += size;
+                dir.disk_bytes += disk_size;
             }
         }
+
+        category
+    }
+
+    /// Snapshot the accumulated data.
+    pub fn state(&self) -> CollectorState {
+        CollectorState {
+            category_stats: self.category_stats.clone(),
+            category_extensions: self.category_extensions.clone(),
+            top_files: self.top_files_heap.iter().cloned().collect(),
+            dir_accumulator: self.dir_accumulator.clone(),
+        }
+    }
+
+    /// Continue from a snapshot taken by [`state`](Self::state), replacing
+    /// anything collected so far.
+    pub fn restore(&mut self, state: CollectorState) {
+        self.category_stats = state.category_stats;
+        self.category_extensions = state.category_extensions;
+        self.top_files_heap = BoundedMinHeap::new(self.top_n);
+        for file in state.top_files {
+            self.top_files_heap.push(file);
+        }
+        self.dir_accumulator = state.dir_accumulator;
+    }
+
+    /// Hand out everything collected so far and start over empty, e.g. to
+    /// summarize one directory at a time.
+    pub fn take_state(&mut self) -> CollectorState {
+        let top_files = std::mem::replace(&mut self.top_files_heap, BoundedMinHeap::new(self.top_n));
+        CollectorState {
+            category_stats: std::mem::take(&mut self.category_stats),
+            category_extensions: std::mem::take(&mut self.category_extensions),
+            top_files: top_files.into_sorted_vec(),
+            dir_accumulator: std::mem::take(&mut self.dir_accumulator),
+        }
+    }
+
+    /// Add a snapshot taken from a collector built with the same options,
+    /// like [`merge`](Self::merge) does for a whole collector.
+    pub fn merge_state(&mut self, state: &CollectorState) {
+        for (category, stats) in &state.category_stats {
+            let entry = self.category_stats.entry(category.clone()).or_default();
+            entry.bytes += stats.bytes;
+            entry.disk_bytes += stats.disk_bytes;
+            entry.file_count += stats.file_count;
+            entry.content_files += stats.content_files;
+            entry.content_bytes += stats.content_bytes;
+        }
+        for (category, ext_map) in &state.category_extensions {
+            let target_ext_map = self.category_extensions.entry(category.clone()).or_default();
+            for (ext, count) in ext_map {
+                *target_ext_map.entry(ext.clone()).or_insert(0) += count;
+            }
+        }
+        for file in &state.top_files {
+            self.top_files_heap.push(file.clone());
+        }
+        for (path, size) in &state.dir_accumulator {
+            let entry = self.dir_accumulator.entry(path.clone()).or_default();
+            entry.bytes += size.bytes;
+            entry.disk_bytes += size.disk_bytes;
+        }
     }
 
-    /// Finalize collection and produce results.
-    ///
-    /// This converts the accumulated data into the final output format:
-    /// - Creates buckets from category statistics
-    /// - Extracts top files from the bounded heap
-    /// - Selects top directories from the accumulator using a bounded heap
     /// Merge another collector into this one (for parallel aggregation)
-    pub fn merge(&mut self, mut other: SinglePassCollector) {
+    pub fn merge(&mut self, other: SinglePassCollector) {
         // Merge category stats
-        for (category, (bytes, count)) in other.category_stats {
-            let entry = self.category_stats.entry(category).or_insert((0, 0));
-            entry.0 += bytes;
-            entry.1 += count;
+        for (category, stats) in other.category_stats {
+            let entry = self.category_stats.entry(category).or_default();
+            entry.bytes += stats.bytes;
+            entry.disk_bytes += stats.disk_bytes;
+            entry.file_count += stats.file_count;
+            entry.content_files += stats.content_files;
+            entry.content_bytes += stats.content_bytes;
         }
 
         // Merge category extensions
         for (category, ext_map) in other.category_extensions {
-            let target_ext_map = self.category_extensions.entry(category).or_insert_with(HashMap::new);
+            let target_ext_map = self.category_extensions.entry(category).or_default();
             for (ext, count) in ext_map {
                 *target_ext_map.entry(ext).or_insert(0) += count;
             }
@@ -165,16 +304,30 @@ impl SinglePassCollector {
 
         // Merge directory accumulator - direct HashMap merge
         for (path, size) in other.dir_accumulator {
-            *self.dir_accumulator.entry(path).or_insert(0) += size;
+            let dir = self.dir_accumulator.entry(path).or_default();
+            dir.bytes += size.bytes;
+            dir.disk_bytes += size.disk_bytes;
         }
     }
 
+    /// Finalize collection and produce results.
+    ///
+    /// This converts the accumulated data into the final output format:
+    /// - Creates buckets from category statistics
+    /// - Extracts top files from the bounded heap
+    /// - Selects top directories from the accumulator using a bounded heap
+    ///
+    /// `total_bytes` is the scan total in the collector's size mode and is
+    /// used to compute bucket percentages.
     pub fn finalize(self, total_bytes: u64) -> CollectionResults {
+        let size_mode = self.size_mode;
+
         // Create buckets from category statistics
         let mut buckets: Vec<Bucket> = self
             .category_stats
             .into_iter()
-            .map(|(key, (bytes, count))| {
+            .map(|(key, stats)| {
+                let bytes = size_mode.select(stats.bytes, stats.disk_bytes);
                 let percent = if total_bytes > 0 {
                     (bytes as f64 / total_bytes as f64) * 100.0
                 } else {
@@ -192,16 +345,19 @@ impl SinglePassCollector {
                 Bucket {
                     label: self.categorizer.get_label(&key),
                     key,
-                    bytes,
+                    bytes: stats.bytes,
+                    disk_bytes: stats.disk_bytes,
                     percent,
-                    file_count: count,
+                    file_count: stats.file_count,
                     color: None,
                     representative_extension,
+                    content_files: stats.content_files,
+                    content_bytes: stats.content_bytes,
                 }
             })
             .collect();
 
-        buckets.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        buckets.sort_by_key(|b| std::cmp::Reverse(size_mode.select(b.bytes, b.disk_bytes)));
 
         // Extract top files
         let top_files = if self.should_collect_tops {
@@ -210,7 +366,8 @@ impl SinglePassCollector {
                 .into_iter()
                 .map(|f| FileEntry {
                     path: f.path.display().to_string(),
-                    bytes: f.size,
+                    bytes: f.bytes,
+                    disk_bytes: f.disk_bytes,
                 })
                 .collect()
         } else {
@@ -219,9 +376,19 @@ impl SinglePassCollector {
 
         // Extract top directories using bounded heap
         let top_dirs = if self.should_collect_tops {
+            let dir_sizes = match (self.dir_totals, self.root.as_deref()) {
+                (DirTotals::Cumulative, Some(root)) => roll_up_dirs(self.dir_accumulator, root),
+                _ => self.dir_accumulator,
+            };
+
             let mut dir_heap = BoundedMinHeap::new(self.top_n);
-            for (path, size) in self.dir_accumulator {
-                dir_heap.push(DirWithSize { path, size });
+            for (path, size) in dir_sizes {
+                dir_heap.push(DirWithSize {
+                    path,
+                    size: size_mode.select(size.bytes, size.disk_bytes),
+                    bytes: size.bytes,
+                    disk_bytes: size.disk_bytes,
+                });
             }
 
             dir_heap
@@ -229,7 +396,8 @@ impl SinglePassCollector {
                 .into_iter()
                 .map(|d| DirEntry {
                     path: d.path.display().to_string(),
-                    bytes: d.size,
+                    bytes: d.bytes,
+                    disk_bytes: d.disk_bytes,
                 })
                 .collect()
         } else {
@@ -244,6 +412,49 @@ impl SinglePassCollector {
     }
 }
 
+/// Turn per-directory (self) sizes into cumulative sizes.
+///
+/// Directories are processed deepest-first, so each one is complete before it
+/// is added to its parent. Every directory is visited once, and ancestors with
+/// no files of their own are created as they are reached. Nothing above `root`
+/// is touched.
+fn roll_up_dirs(mut dirs: HashMap<PathBuf, DirSize>, root: &Path) -> HashMap<PathBuf, DirSize> {
+    let root_depth = root.components().count();
+
+    // levels[d] holds directories whose depth below root is d
+    let mut levels: Vec<Vec<PathBuf>> = Vec::new();
+    for path in dirs.keys() {
+        if !path.starts_with(root) {
+            continue;
+        }
+        let depth = path.components().count() - root_depth;
+        if levels.len() <= depth {
+            levels.resize_with(depth + 1, Vec::new);
+        }
+        levels[depth].push(path.clone());
+    }
+
+    for depth in (1..levels.len()).rev() {
+        for path in std::mem::take(&mut levels[depth]) {
+            let size = dirs[&path];
+            let Some(parent) = path.parent() else { continue };
+
+            match dirs.get_mut(parent) {
+                Some(total) => {
+                    total.bytes += size.bytes;
+                    total.disk_bytes += size.disk_bytes;
+                }
+                None => {
+                    dirs.insert(parent.to_path_buf(), size);
+                    levels[depth - 1].push(parent.to_path_buf());
+                }
+            }
+        }
+    }
+
+    dirs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,6 +464,7 @@ mod tests {
         FileMetadata {
             path: PathBuf::from(path),
             size,
+            disk_size: size,
             extension: ext.map(String::from),
             modified: None,
         }
@@ -263,9 +475,9 @@ mod tests {
         let categorizer = Box::new(TypeCategorizer::new());
         let mut collector = SinglePassCollector::new(categorizer, 3, true);
 
-        collector.process_file(create_test_metadata("/test/file1.rs", 100, Some("rs")));
-        collector.process_file(create_test_metadata("/test/file2.py", 200, Some("py")));
-        collector.process_file(create_test_metadata("/test/file3.rs", 150, Some("rs")));
+        collector.process_file(&create_test_metadata("/test/file1.rs", 100, Some("rs")));
+        collector.process_file(&create_test_metadata("/test/file2.py", 200, Some("py")));
+        collector.process_file(&create_test_metadata("/test/file3.rs", 150, Some("rs")));
 
         let results = collector.finalize(450);
 
@@ -288,11 +500,11 @@ mod tests {
         let mut collector = SinglePassCollector::new(categorizer, 2, true);
 
         // Add 5 files, but only top 2 should be kept
-        collector.process_file(create_test_metadata("/test/file1.txt", 100, Some("txt")));
-        collector.process_file(create_test_metadata("/test/file2.txt", 500, Some("txt")));
-        collector.process_file(create_test_metadata("/test/file3.txt", 200, Some("txt")));
-        collector.process_file(create_test_metadata("/test/file4.txt", 50, Some("txt")));
-        collector.process_file(create_test_metadata("/test/file5.txt", 300, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file1.txt", 100, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file2.txt", 500, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file3.txt", 200, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file4.txt", 50, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file5.txt", 300, Some("txt")));
 
         let results = collector.finalize(1150);
 
@@ -307,8 +519,8 @@ mod tests {
         let categorizer = Box::new(TypeCategorizer::new());
         let mut collector = SinglePassCollector::new(categorizer, 10, false);
 
-        collector.process_file(create_test_metadata("/test/file1.txt", 100, Some("txt")));
-        collector.process_file(create_test_metadata("/test/file2.txt", 200, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file1.txt", 100, Some("txt")));
+        collector.process_file(&create_test_metadata("/test/file2.txt", 200, Some("txt")));
 
         let results = collector.finalize(300);
 
@@ -320,4 +532,113 @@ mod tests {
         assert_eq!(results.top_files.len(), 0);
         assert_eq!(results.top_dirs.len(), 0);
     }
+
+    #[test]
+    fn test_single_pass_collector_disk_size_mode() {
+        let categorizer = Box::new(TypeCategorizer::new());
+        let mut collector = SinglePassCollector::new(categorizer, 2, true).with_size_mode(SizeMode::Disk);
+
+        // A sparse image: large apparent size, little allocated
+        let mut sparse = create_test_metadata("/vm/disk.img", 10_000, Some("img"));
+        sparse.disk_size = 100;
+        // A small file rounded up to a 4K block
+        let mut small = create_test_metadata("/vm/notes.txt", 10, Some("txt"));
+        small.disk_size = 4096;
+
+        collector.process_file(&sparse);
+        collector.process_file(&small);
+
+        let results = collector.finalize(4196);
+
+        // Ranked by allocated size, but both values are kept
+        assert_eq!(results.top_files[0].path, "/vm/notes.txt");
+        assert_eq!(results.top_files[0].bytes, 10);
+        assert_eq!(results.top_files[0].disk_bytes, 4096);
+        assert_eq!(results.buckets[0].key, "Documents");
+        assert_eq!(results.buckets[1].bytes, 10_000);
+        assert_eq!(results.buckets[1].disk_bytes, 100);
+        assert_eq!(results.top_dirs[0].disk_bytes, 4196);
+    }
+
+    #[test]
+    fn test_cumulative_dir_totals() {
+        let categorizer = Box::new(TypeCategorizer::new());
+        let mut collector = SinglePassCollector::new(categorizer, 10, true)
+            .with_dir_totals(DirTotals::Cumulative, PathBuf::from("/scan"));
+
+        collector.process_file(&create_test_metadata("/scan/top.txt", 1, Some("txt")));
+        collector.process_file(&create_test_metadata("/scan/node_modules/a/b/c/index.js", 100, Some("js")));
+        collector.process_file(&create_test_metadata("/scan/node_modules/a/d/index.js", 50, Some("js")));
+        collector.process_file(&create_test_metadata("/scan/src/main.rs", 10, Some("rs")));
+
+        let results = collector.finalize(161);
+        let dir = |path: &str| results.top_dirs.iter().find(|d| d.path == path).map(|d| d.bytes);
+
+        assert_eq!(dir("/scan"), Some(161));
+        assert_eq!(dir("/scan/node_modules"), Some(150));
+        assert_eq!(dir("/scan/node_modules/a"), Some(150));
+        assert_eq!(dir("/scan/node_modules/a/b/c"), Some(100));
+        assert_eq!(dir("/scan/src"), Some(10));
+        // Nothing above the scan root
+        assert_eq!(dir("/"), None);
+    }
+
+    #[test]
+    fn test_cumulative_dir_totals_after_merge() {
+        let new_collector = || {
+            SinglePassCollector::new(Box::new(TypeCategorizer::new()), 10, true)
+                .with_dir_totals(DirTotals::Cumulative, PathBuf::from("/scan"))
+        };
+
+        let mut first = new_collector();
+        first.process_file(&create_test_metadata("/scan/deps/x/lib.rs", 40, Some("rs")));
+        let mut second = new_collector();
+        second.process_file(&create_test_metadata("/scan/deps/y/lib.rs", 60, Some("rs")));
+        second.process_file(&create_test_metadata("/scan/deps/x/mod.rs", 5, Some("rs")));
+
+        first.merge(second);
+        let results = first.finalize(105);
+
+        let dir = |path: &str| results.top_dirs.iter().find(|d| d.path == path).map(|d| d.bytes);
+        assert_eq!(dir("/scan"), Some(105));
+        assert_eq!(dir("/scan/deps"), Some(105));
+        assert_eq!(dir("/scan/deps/x"), Some(45));
+        assert_eq!(dir("/scan/deps/y"), Some(60));
+    }
+
+    #[test]
+    fn test_self_only_dir_totals() {
+        let categorizer = Box::new(TypeCategorizer::new());
+        let mut collector = SinglePassCollector::new(categorizer, 10, true)
+            .with_dir_totals(DirTotals::SelfOnly, PathBuf::from("/scan"));
+
+        collector.process_file(&create_test_metadata("/scan/a/b/file.txt", 100, Some("txt")));
+
+        let results = collector.finalize(100);
+        assert_eq!(results.top_dirs.len(), 1);
+        assert_eq!(results.top_dirs[0].path, "/scan/a/b");
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let new_collector = || SinglePassCollector::new(Box::new(TypeCategorizer::new()), 2, true);
+
+        let mut original = new_collector();
+        original.process_file(&create_test_metadata("/scan/a.rs", 300, Some("rs")));
+        original.process_file(&create_test_metadata("/scan/b.png", 200, Some("png")));
+        original.process_file(&create_test_metadata("/scan/c.png", 100, Some("png")));
+
+        let state = original.state();
+        let bytes = bincode::serialize(&state).unwrap();
+        let mut restored = new_collector();
+        restored.restore(bincode::deserialize(&bytes).unwrap());
+        restored.process_file(&create_test_metadata("/scan/d.rs", 250, Some("rs")));
+
+        let results = restored.finalize(850);
+        let code = results.buckets.iter().find(|b| b.key == "Code").unwrap();
+        assert_eq!(code.bytes, 550);
+        assert_eq!(code.file_count, 2);
+        let top: Vec<_> = results.top_files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(top, vec!["/scan/a.rs", "/scan/d.rs"]);
+    }
 }
@@ -1,17 +1,300 @@
-use crate::types::ScanResults;
+use crate::types::{DirTotals, ScanResults, SizeMode};
 use colored::*;
 use humansize::{format_size, BINARY};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
 
+/// Changed paths shown per section of the text output.
+const SHOWN_CHANGES: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ScanComparison {
     pub added_bytes: u64,
     pub removed_bytes: u64,
     pub added_files: u64,
     pub removed_files: u64,
     pub category_changes: HashMap<String, i64>,
+    /// Change of the total size, `after - before`
+    pub net_bytes: i64,
+    /// `net_bytes` in percent of the size before; `None` if that was 0
+    pub net_percent: Option<f64>,
+    /// Categories whose size changed, largest change first
+    pub categories: Vec<CategoryChange>,
+    /// Files that appeared, disappeared, changed size or moved, largest change first
+    pub files: Vec<PathChange>,
+    /// Directories that appeared, disappeared or changed size, largest change first
+    pub directories: Vec<PathChange>,
+    /// Whether both scans had a full file index. Otherwise only the paths in
+    /// their top lists and duplicate groups are compared
+    pub complete: bool,
+}
+
+/// How one category changed between two scans.
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryChange {
+    pub key: String,
+    pub label: String,
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+    pub delta: i64,
+    /// `delta` in percent of `before_bytes`; `None` for new categories
+    pub percent: Option<f64>,
+}
+
+/// `--fail-if-growth`: how much a tree may grow between two scans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrowthLimit {
+    Bytes(u64),
+    Percent(f64),
+}
+
+impl GrowthLimit {
+    /// Whether the scans of `comparison` grew by more than this.
+    pub fn exceeded_by(&self, comparison: &ScanComparison) -> bool {
+        match *self {
+            GrowthLimit::Bytes(limit) => comparison.net_bytes > 0 && comparison.net_bytes as u64 > limit,
+            // Any growth from nothing is too much
+            GrowthLimit::Percent(limit) => match comparison.net_percent {
+                Some(percent) => percent > limit,
+                None => comparison.net_bytes > 0,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for GrowthLimit {
+    type Err = String;
+
+    /// Bytes, a size like `10G`, or a percentage like `5%`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid --fail-if-growth: {}. Must be a size like 10G or a percentage like 5%", s);
+        match s.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if percent.is_finite() && percent >= 0.0 => Ok(GrowthLimit::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => crate::policy::parse_size(s).map(GrowthLimit::Bytes).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl std::fmt::Display for GrowthLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrowthLimit::Bytes(bytes) => write!(f, "{}", format_size(*bytes, BINARY)),
+            GrowthLimit::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Grown,
+    Shrunk,
+    Moved,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "new",
+            ChangeKind::Removed => "deleted",
+            ChangeKind::Grown => "grown",
+            ChangeKind::Shrunk => "shrunk",
+            ChangeKind::Moved => "moved",
+        }
+    }
+}
+
+/// A file or directory that differs between two scans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathChange {
+    pub kind: ChangeKind,
+    pub path: String,
+    /// Where a moved file was before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved_from: Option<String>,
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+    pub delta: i64,
+}
+
+impl PathChange {
+    fn new(kind: ChangeKind, path: &str, before_bytes: u64, after_bytes: u64) -> Self {
+        Self {
+            kind,
+            path: path.to_string(),
+            moved_from: None,
+            before_bytes,
+            after_bytes,
+            delta: after_bytes as i64 - before_bytes as i64,
+        }
+    }
+
+    /// `None` if the size didn't change.
+    fn resized(path: &str, before_bytes: u64, after_bytes: u64) -> Option<Self> {
+        let kind = match after_bytes.cmp(&before_bytes) {
+            std::cmp::Ordering::Greater => ChangeKind::Grown,
+            std::cmp::Ordering::Less => ChangeKind::Shrunk,
+            std::cmp::Ordering::Equal => return None,
+        };
+        Some(Self::new(kind, path, before_bytes, after_bytes))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    pub bytes: u64,
+    /// `None` for duplicate-group files outside the top lists, which only
+    /// record their apparent size
+    pub disk_bytes: Option<u64>,
+    /// Content hash, for files in a duplicate group
+    pub hash: Option<String>,
+}
+
+/// Sizes of the individual files and directories of one scan.
+///
+/// Built from a `--sqlite` index it holds every path. Built from JSON
+/// results it only holds the top lists and duplicate groups, and a path
+/// missing from it may still exist with a size below the top lists'.
+#[derive(Debug, Clone, Default)]
+pub struct FileIndex {
+    size_mode: SizeMode,
+    dir_totals: DirTotals,
+    files: HashMap<String, IndexedFile>,
+    /// Size of everything below each directory, in `size_mode`
+    dirs: HashMap<String, u64>,
+    /// Hashes of the duplicate groups, all of whose files are in `files`
+    hashes: HashSet<String>,
+    /// Whether every file of the scan is in `files`
+    all_files: bool,
+    /// Whether every directory of the scan is in `dirs`
+    all_dirs: bool,
+}
+
+impl FileIndex {
+    /// An empty index that will hold every file and directory of a scan.
+    /// Directory sizes are cumulative.
+    pub fn new(size_mode: SizeMode) -> Self {
+        Self {
+            size_mode,
+            all_files: true,
+            all_dirs: true,
+            ..Self::default()
+        }
+    }
+
+    /// The paths `results` lists: its top files and directories and the
+    /// files of its duplicate groups, with their hashes.
+    pub fn from_results(results: &ScanResults) -> Self {
+        let size_mode = results.size_mode;
+        let mut index = Self {
+            size_mode,
+            dir_totals: results.dir_totals,
+            all_files: results.top_files.len() as u64 >= results.totals.file_count,
+            all_dirs: results.top_dirs.len() as u64 >= results.totals.dir_count,
+            ..Self::default()
+        };
+        for group in results.duplicates.iter().flatten() {
+            index.hashes.insert(group.hash.clone());
+            for path in &group.paths {
+                index.files.insert(
+                    path.clone(),
+                    IndexedFile {
+                        bytes: group.size,
+                        disk_bytes: None,
+                        hash: Some(group.hash.clone()),
+                    },
+                );
+            }
+        }
+        for file in &results.top_files {
+            let entry = index.files.entry(file.path.clone()).or_insert(IndexedFile {
+                bytes: file.bytes,
+                disk_bytes: None,
+                hash: None,
+            });
+            entry.disk_bytes = Some(file.disk_bytes);
+        }
+        for dir in &results.top_dirs {
+            index.insert_dir(dir.path.clone(), dir.bytes, dir.disk_bytes);
+        }
+        index
+    }
+
+    pub fn insert_file(&mut self, path: String, bytes: u64, disk_bytes: u64) {
+        self.files.insert(
+            path,
+            IndexedFile {
+                bytes,
+                disk_bytes: Some(disk_bytes),
+                hash: None,
+            },
+        );
+    }
+
+    /// Add a directory with the size of everything below it.
+    pub fn insert_dir(&mut self, path: String, bytes: u64, disk_bytes: u64) {
+        self.dirs.insert(path, self.size_mode.select(bytes, disk_bytes));
+    }
+
+    /// Whether this index holds every file and directory of its scan.
+    pub fn is_complete(&self) -> bool {
+        self.all_files && self.all_dirs
+    }
+
+    /// `None` if `file`'s size in this index's mode is unknown.
+    fn size(&self, file: &IndexedFile) -> Option<u64> {
+        match self.size_mode {
+            SizeMode::Apparent => Some(file.bytes),
+            SizeMode::Disk => file.disk_bytes,
+        }
+    }
+
+    /// Whether `file`'s path missing from this index proves that its content
+    /// is no longer there: the index lists every file, or every file with
+    /// that content. A file that only dropped out of a top list proves nothing.
+    fn rules_out(&self, file: &IndexedFile) -> bool {
+        self.all_files || file.hash.as_ref().is_some_and(|hash| self.hashes.contains(hash))
+    }
+}
+
+/// Load a scan to compare: a JSON results file, or a scan in a `--sqlite`
+/// index given as `FILE` (its latest finished scan) or `FILE#ID`.
+pub fn load_scan(spec: &Path) -> io::Result<(ScanResults, FileIndex)> {
+    let (path, scan_id) = split_scan_id(spec);
+    if !is_sqlite(&path)? {
+        let results = load_scan_results(&path)?;
+        let index = FileIndex::from_results(&results);
+        return Ok((results, index));
+    }
+
+    crate::sqlite::load_scan(&path, scan_id)
+        .map_err(io::Error::other)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no finished scan in the SQLite index"))
+}
+
+fn split_scan_id(spec: &Path) -> (PathBuf, Option<i64>) {
+    if !spec.exists() {
+        if let Some((path, id)) = spec.to_string_lossy().rsplit_once('#') {
+            if let Ok(id) = id.parse() {
+                return (PathBuf::from(path), Some(id));
+            }
+        }
+    }
+    (spec.to_path_buf(), None)
+}
+
+fn is_sqlite(path: &Path) -> io::Result<bool> {
+    let mut header = [0u8; 16];
+    let read = File::open(path)?.read(&mut header)?;
+    Ok(read == header.len() && &header == b"SQLite format 3\0")
 }
 
 pub fn load_scan_results(path: &Path) -> std::io::Result<ScanResults> {
@@ -21,7 +304,18 @@ pub fn load_scan_results(path: &Path) -> std::io::Result<ScanResults> {
     Ok(results)
 }
 
+/// Compare the totals, categories and listed paths of two scans.
 pub fn compare_scans(before: &ScanResults, after: &ScanResults) -> ScanComparison {
+    compare_indexed(before, &FileIndex::from_results(before), after, &FileIndex::from_results(after))
+}
+
+/// Compare two scans with the file indexes they were loaded with.
+pub fn compare_indexed(
+    before: &ScanResults,
+    before_index: &FileIndex,
+    after: &ScanResults,
+    after_index: &FileIndex,
+) -> ScanComparison {
     // Compare totals
     let added_bytes = after.totals.total_bytes.saturating_sub(before.totals.total_bytes);
     let removed_bytes = before.totals.total_bytes.saturating_sub(after.totals.total_bytes);
@@ -58,13 +352,142 @@ pub fn compare_scans(before: &ScanResults, after: &ScanResults) -> ScanCompariso
         category_changes.insert(key, -(before_bytes as i64));
     }
 
+    let mut categories: Vec<CategoryChange> = category_changes
+        .iter()
+        .map(|(key, &delta)| {
+            let before_bytes = before.buckets.iter().find(|b| &b.key == key).map_or(0, |b| b.bytes);
+            let label = after
+                .buckets
+                .iter()
+                .chain(&before.buckets)
+                .find(|b| &b.key == key)
+                .map_or_else(|| key.clone(), |b| b.label.clone());
+            CategoryChange {
+                key: key.clone(),
+                label,
+                before_bytes,
+                after_bytes: (before_bytes as i64 + delta) as u64,
+                delta,
+                percent: percent_of(delta, before_bytes),
+            }
+        })
+        .collect();
+    categories.sort_by(|a, b| b.delta.unsigned_abs().cmp(&a.delta.unsigned_abs()).then_with(|| a.key.cmp(&b.key)));
+
+    let net_bytes = after.totals.total_bytes as i64 - before.totals.total_bytes as i64;
     ScanComparison {
         added_bytes,
         removed_bytes,
         added_files,
         removed_files,
         category_changes,
+        net_bytes,
+        net_percent: percent_of(net_bytes, before.totals.total_bytes),
+        categories,
+        files: diff_files(before_index, after_index),
+        directories: diff_dirs(before_index, after_index),
+        complete: before_index.is_complete() && after_index.is_complete(),
+    }
+}
+
+fn percent_of(delta: i64, before: u64) -> Option<f64> {
+    (before > 0).then(|| delta as f64 / before as f64 * 100.0)
+}
+
+fn diff_files(before: &FileIndex, after: &FileIndex) -> Vec<PathChange> {
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    for (path, old) in &before.files {
+        match after.files.get(path) {
+            Some(new) => {
+                if let (Some(old), Some(new)) = (before.size(old), after.size(new)) {
+                    changes.extend(PathChange::resized(path, old, new));
+                }
+            }
+            None if after.rules_out(old) => removed.push((path, old)),
+            None => {}
+        }
+    }
+    let mut added: Vec<_> = after
+        .files
+        .iter()
+        .filter(|(path, new)| !before.files.contains_key(*path) && before.rules_out(new))
+        .map(|(path, new)| (path, new, false))
+        .collect();
+
+    // A file that disappeared in one place and appeared in another with the
+    // same content moved. Without hashes the same name and size will do.
+    // Unmatched paths are only new or deleted if the other scan lists every
+    // file; otherwise they may just be outside its top lists.
+    removed.sort_by_key(|(path, _)| *path);
+    added.sort_by_key(|(path, _, _)| *path);
+    for (from, old) in removed {
+        let moved = added.iter_mut().find(|(to, new, taken)| {
+            !*taken
+                && new.bytes == old.bytes
+                && match (&old.hash, &new.hash) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => Path::new(from).file_name() == Path::new(to).file_name(),
+                }
+        });
+        match moved {
+            Some((to, new, taken)) => {
+                *taken = true;
+                if let (Some(old), Some(new)) = (before.size(old), after.size(new)) {
+                    let mut change = PathChange::new(ChangeKind::Moved, to, old, new);
+                    change.moved_from = Some(from.clone());
+                    changes.push(change);
+                }
+            }
+            None if after.all_files => {
+                if let Some(old) = before.size(old) {
+                    changes.push(PathChange::new(ChangeKind::Removed, from, old, 0));
+                }
+            }
+            None => {}
+        }
+    }
+    for (path, new, taken) in added {
+        match after.size(new) {
+            Some(new) if !taken && before.all_files => changes.push(PathChange::new(ChangeKind::Added, path, 0, new)),
+            _ => {}
+        }
     }
+
+    sort_changes(&mut changes);
+    changes
+}
+
+fn diff_dirs(before: &FileIndex, after: &FileIndex) -> Vec<PathChange> {
+    // Direct-files-only and cumulative sizes can't be compared
+    if before.dir_totals != after.dir_totals {
+        return Vec::new();
+    }
+
+    let mut changes = Vec::new();
+    for (path, &old) in &before.dirs {
+        match after.dirs.get(path) {
+            Some(&new) => changes.extend(PathChange::resized(path, old, new)),
+            None if after.all_dirs => changes.push(PathChange::new(ChangeKind::Removed, path, old, 0)),
+            None => {}
+        }
+    }
+    for (path, &new) in &after.dirs {
+        if !before.dirs.contains_key(path) && before.all_dirs {
+            changes.push(PathChange::new(ChangeKind::Added, path, 0, new));
+        }
+    }
+
+    sort_changes(&mut changes);
+    changes
+}
+
+/// Largest change first; moves by the size moved.
+fn sort_changes(changes: &mut [PathChange]) {
+    changes.sort_by(|a, b| {
+        let size = |c: &PathChange| if c.kind == ChangeKind::Moved { c.after_bytes } else { c.delta.unsigned_abs() };
+        size(b).cmp(&size(a)).then_with(|| a.path.cmp(&b.path))
+    });
 }
 
 pub fn print_comparison(
@@ -126,37 +549,72 @@ pub fn print_comparison(
     println!();
 
     // Category changes
-    if !comparison.category_changes.is_empty() {
+    if !comparison.categories.is_empty() {
         println!("  {}", style_text("CATEGORY CHANGES", "yellow", true, use_color));
         println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
 
-        let mut changes: Vec<_> = comparison.category_changes.iter().collect();
-        changes.sort_by(|a, b| b.1.abs().cmp(&a.1.abs()));
-
-        for (category, delta) in changes.iter().take(10) {
-            let before_label = before
-                .buckets
-                .iter()
-                .find(|b| &b.key == *category)
-                .map(|b| b.label.as_str())
-                .unwrap_or(category);
-
-            if **delta > 0 {
-                println!(
-                    "  {} +{}",
-                    style_text(&format!("{:<20}", before_label), "white", false, use_color),
-                    style_text(&format_size(**delta as u64, BINARY), "green", false, use_color)
-                );
+        for change in comparison.categories.iter().take(10) {
+            let percent = match change.percent {
+                Some(percent) => format!("{:+.1}%", percent),
+                None => "new".to_string(),
+            };
+            let (size, color) = if change.delta > 0 {
+                (format!("+{}", format_size(change.delta as u64, BINARY)), "green")
             } else {
-                println!(
-                    "  {} -{}",
-                    style_text(&format!("{:<20}", before_label), "white", false, use_color),
-                    style_text(&format_size(delta.unsigned_abs(), BINARY), "red", false, use_color)
-                );
-            }
+                (format!("-{}", format_size(change.delta.unsigned_abs(), BINARY)), "red")
+            };
+            println!(
+                "  {} {} {}",
+                style_text(&format!("{:<20}", change.label), "white", false, use_color),
+                style_text(&format!("{:>12}", size), color, false, use_color),
+                style_text(&format!("({})", percent), "bright_black", false, use_color)
+            );
         }
+        println!();
+    }
+
+    print_path_changes("FILE CHANGES", &comparison.files, use_color);
+    print_path_changes("DIRECTORY CHANGES", &comparison.directories, use_color);
+    if !comparison.complete && (!comparison.files.is_empty() || !comparison.directories.is_empty()) {
+        println!(
+            "  {}",
+            style_text(
+                "Only paths in the top lists and duplicate groups were compared, and paths that left them are not shown as deleted; use --sqlite indexes for every path",
+                "bright_black",
+                false,
+                use_color
+            )
+        );
+        println!();
     }
+}
+
+fn print_path_changes(title: &str, changes: &[PathChange], use_color: bool) {
+    if changes.is_empty() {
+        return;
+    }
+    println!("  {}", style_text(title, "yellow", true, use_color));
+    println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
 
+    for change in changes.iter().take(SHOWN_CHANGES) {
+        let (size, color) = match change.kind {
+            ChangeKind::Moved => (format_size(change.after_bytes, BINARY), "blue"),
+            _ if change.delta >= 0 => (format!("+{}", format_size(change.delta as u64, BINARY)), "green"),
+            _ => (format!("-{}", format_size(change.delta.unsigned_abs(), BINARY)), "red"),
+        };
+        println!(
+            "  {} {} {}",
+            style_text(&format!("{:<8}", change.kind.as_str()), color, false, use_color),
+            style_text(&format!("{:>12}", size), color, false, use_color),
+            change.path
+        );
+        if let Some(ref from) = change.moved_from {
+            println!("  {:<8} {:>12} {}", "", "from", style_text(from, "bright_black", false, use_color));
+        }
+    }
+    if changes.len() > SHOWN_CHANGES {
+        println!("  ... and {} more", changes.len() - SHOWN_CHANGES);
+    }
     println!();
 }
 
@@ -183,3 +641,156 @@ fn style_text(text: &str, color: &str, bold: bool, use_color: bool) -> String {
         colored.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScanOptions;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn scan(root: &Path) -> ScanResults {
+        crate::scan(root, ScanOptions::new().find_duplicates(true)).unwrap()
+    }
+
+    fn change<'a>(changes: &'a [PathChange], root: &Path, path: &str) -> &'a PathChange {
+        let path = root.join(path).display().to_string();
+        changes.iter().find(|c| c.path == path).unwrap()
+    }
+
+    #[test]
+    fn test_path_changes_from_top_lists() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("big.iso"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("a/clip.mp4"), vec![1u8; 3000]).unwrap();
+        fs::write(root.join("dup1.bin"), vec![2u8; 2000]).unwrap();
+        fs::write(root.join("dup2.bin"), vec![2u8; 2000]).unwrap();
+        fs::write(root.join("small.txt"), vec![3u8; 10]).unwrap();
+        let before = scan(root);
+
+        fs::write(root.join("big.iso"), vec![0u8; 8000]).unwrap();
+        fs::write(root.join("new.bin"), vec![4u8; 4000]).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::create_dir_all(root.join("c")).unwrap();
+        fs::rename(root.join("a/clip.mp4"), root.join("b/clip.mp4")).unwrap();
+        fs::rename(root.join("dup1.bin"), root.join("c/renamed.bin")).unwrap();
+        fs::remove_dir(root.join("a")).unwrap();
+        fs::remove_file(root.join("small.txt")).unwrap();
+        let after = scan(root);
+
+        // Every path fits in the top lists
+        let comparison = compare_scans(&before, &after);
+        assert!(comparison.complete);
+        let files = &comparison.files;
+        assert_eq!(files.len(), 5);
+        assert_eq!(change(files, root, "small.txt").kind, ChangeKind::Removed);
+
+        let grown = change(files, root, "big.iso");
+        assert_eq!((grown.kind, grown.delta), (ChangeKind::Grown, 3000));
+        assert_eq!(change(files, root, "new.bin").kind, ChangeKind::Added);
+
+        // Same name and size, then same content hash
+        let moved = change(files, root, "b/clip.mp4");
+        assert_eq!(moved.kind, ChangeKind::Moved);
+        assert_eq!(moved.moved_from, Some(root.join("a/clip.mp4").display().to_string()));
+        let moved = change(files, root, "c/renamed.bin");
+        assert_eq!(moved.kind, ChangeKind::Moved);
+        assert_eq!(moved.moved_from, Some(root.join("dup1.bin").display().to_string()));
+
+        // Largest change first
+        assert_eq!(files[0].path, root.join("new.bin").display().to_string());
+
+        let removed = change(&comparison.directories, root, "a");
+        assert_eq!((removed.kind, removed.delta), (ChangeKind::Removed, -3000));
+    }
+
+    #[test]
+    fn test_paths_leaving_top_lists_are_not_deleted() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.bin"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("b.bin"), vec![1u8; 4000]).unwrap();
+        fs::write(root.join("c.bin"), vec![2u8; 3000]).unwrap();
+        fs::write(root.join("dup1.dat"), vec![3u8; 100]).unwrap();
+        fs::write(root.join("dup2.dat"), vec![3u8; 100]).unwrap();
+        let scan = || crate::scan(root, ScanOptions::new().top(2).find_duplicates(true)).unwrap();
+        let before = scan();
+
+        // b.bin shrinks out of the top list and c.bin grows into it
+        fs::write(root.join("b.bin"), vec![1u8; 10]).unwrap();
+        fs::write(root.join("c.bin"), vec![2u8; 4500]).unwrap();
+        fs::rename(root.join("dup1.dat"), root.join("dup3.dat")).unwrap();
+        let after = scan();
+
+        let comparison = compare_scans(&before, &after);
+        assert!(!comparison.complete);
+        let files = &comparison.files;
+        assert!(files.iter().all(|c| !matches!(c.kind, ChangeKind::Removed | ChangeKind::Added)));
+        assert!(files.iter().all(|c| !c.path.ends_with("b.bin") && !c.path.ends_with("c.bin")));
+
+        // Content hashes still prove a move
+        let moved = change(files, root, "dup3.dat");
+        assert_eq!(moved.kind, ChangeKind::Moved);
+        assert_eq!(moved.moved_from, Some(root.join("dup1.dat").display().to_string()));
+
+        // Directory sizes are kept in the index's size mode
+        let mut index = FileIndex::new(SizeMode::Disk);
+        index.insert_dir("/d".to_string(), 10, 4096);
+        assert_eq!(index.dirs["/d"], 4096);
+    }
+
+    #[test]
+    fn test_duplicates_have_no_disk_size() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(root.join("a.bin"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("dup1.dat"), vec![3u8; 100]).unwrap();
+        fs::write(root.join("dup2.dat"), vec![3u8; 100]).unwrap();
+        let options = || ScanOptions::new().top(1).find_duplicates(true).size_mode(SizeMode::Disk);
+        let before = crate::scan(root, options()).unwrap();
+
+        // A duplicate enters the top list with its disk size, which the
+        // first scan never recorded
+        fs::remove_file(root.join("a.bin")).unwrap();
+        let after = crate::scan(root, options()).unwrap();
+        assert!(after.top_files[0].path.contains("dup"));
+
+        let comparison = compare_scans(&before, &after);
+        assert!(comparison.files.iter().all(|c| !c.path.contains("dup")), "{:?}", comparison.files);
+        let index = FileIndex::from_results(&before);
+        assert_eq!(index.files.values().filter(|f| f.disk_bytes.is_none()).count(), 2);
+    }
+
+    #[test]
+    fn test_category_percent_and_growth_limit() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(root.join("notes.txt"), vec![0u8; 1000]).unwrap();
+        let before = scan(root);
+        fs::write(root.join("notes.txt"), vec![0u8; 1500]).unwrap();
+        fs::write(root.join("main.rs"), vec![0u8; 100]).unwrap();
+        let after = scan(root);
+
+        let comparison = compare_scans(&before, &after);
+        assert_eq!(comparison.net_bytes, 600);
+        assert_eq!(comparison.net_percent, Some(60.0));
+        let categories: Vec<_> = comparison.categories.iter().map(|c| (c.label.as_str(), c.delta, c.percent)).collect();
+        assert_eq!(categories, [("Documents", 500, Some(50.0)), ("Code", 100, None)]);
+
+        let limit = |s: &str| s.parse::<GrowthLimit>().unwrap();
+        assert_eq!(limit("10%"), GrowthLimit::Percent(10.0));
+        assert_eq!(limit("1K"), GrowthLimit::Bytes(1024));
+        assert!(limit("50%").exceeded_by(&comparison));
+        assert!(!limit("60%").exceeded_by(&comparison));
+        assert!(limit("599").exceeded_by(&comparison));
+        assert!(!limit("1K").exceeded_by(&comparison));
+        assert!("-5%".parse::<GrowthLimit>().is_err());
+        assert!("lots".parse::<GrowthLimit>().is_err());
+
+        let json = serde_json::to_value(&comparison).unwrap();
+        assert_eq!(json["categories"][0]["percent"], 50.0);
+        assert_eq!(json["categories"][1]["percent"], serde_json::Value::Null);
+    }
+}
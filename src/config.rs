@@ -2,10 +2,14 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::categorize::{CategoryError, CategoryRule};
+use crate::cleanup::CleanupRule;
+use crate::policy::{deserialize_size, PolicyRule};
 use crate::types::Bucket;
+use crate::watch::AlertRule;
 
 /// Main configuration structure for spacemap
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct SpacemapConfig {
     /// Custom category definitions with extensions
     #[serde(default)]
@@ -26,17 +30,66 @@ pub struct SpacemapConfig {
     /// Display settings
     #[serde(default)]
     pub display: DisplayConfig,
+
+    /// Extra or replaced rules for `--suggest-cleanup`
+    #[serde(default)]
+    pub cleanup: Vec<CleanupRule>,
+
+    /// Storage policies checked with `--check-policy`
+    #[serde(default)]
+    pub policy: Vec<PolicyRule>,
+
+    /// Size alerts for `--watch`
+    #[serde(default)]
+    pub alert: Vec<AlertRule>,
 }
 
-/// Custom category with name, extensions, and optional color
+/// Custom category with name, extensions, and optional color.
+///
+/// A category with only `extensions` adds them to the extension map. Setting
+/// any of `paths`, `filenames` or the size and age limits turns it into a
+/// rule instead: a file belongs to it when it meets every condition that is
+/// set (one of the paths, one of the file names, one of the extensions, and
+/// the limits). Rules are checked in config order before the extension map;
+/// see [`crate::categorize::TypeCategorizer`].
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CustomCategory {
     pub name: String,
+    #[serde(default)]
     pub extensions: Vec<String>,
+    /// Gitignore-style globs (or `re:` regexes) matched against the file's
+    /// absolute path, as if `/` were the scan root for `--exclude`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Globs matched against the file name only, like `*.log.*`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filenames: Vec<String>,
+    /// Apparent size limits, in bytes or like `"500M"`
+    #[serde(default, deserialize_with = "deserialize_size", skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_size", skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Days since the file was last modified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_age_days: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
 }
 
+impl CustomCategory {
+    /// Whether this category has conditions beyond its extensions.
+    pub fn is_rule(&self) -> bool {
+        !self.paths.is_empty()
+            || !self.filenames.is_empty()
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.min_age_days.is_some()
+            || self.max_age_days.is_some()
+    }
+}
+
 /// Remap extensions to a different category
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ExtensionRemap {
@@ -83,7 +136,17 @@ impl SpacemapConfig {
         let contents = std::fs::read_to_string(&path)
             .map_err(|e| ConfigError::Io(path.clone(), e))?;
 
-        toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.clone(), e))
+        let config: Self = toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        config.validate().map_err(|e| ConfigError::Category(path.clone(), e))?;
+        Ok(config)
+    }
+
+    /// Check that the patterns of every category rule compile.
+    pub fn validate(&self) -> Result<(), CategoryError> {
+        for category in &self.categories {
+            CategoryRule::new(category)?;
+        }
+        Ok(())
     }
 
     /// Get default config path: ~/.config/spacemap/config.toml
@@ -94,18 +157,6 @@ impl SpacemapConfig {
     }
 }
 
-impl Default for SpacemapConfig {
-    fn default() -> Self {
-        Self {
-            categories: Vec::new(),
-            remaps: Vec::new(),
-            category_colors: HashMap::new(),
-            extension_colors: HashMap::new(),
-            display: DisplayConfig::default(),
-        }
-    }
-}
-
 /// Color resolution with priority: extension > category > percentage
 pub struct ColorResolver {
     config: SpacemapConfig,
@@ -168,6 +219,7 @@ pub enum ConfigError {
     NoConfigDir,
     Io(PathBuf, std::io::Error),
     Parse(PathBuf, toml::de::Error),
+    Category(PathBuf, CategoryError),
 }
 
 impl std::fmt::Display for ConfigError {
@@ -180,6 +232,9 @@ impl std::fmt::Display for ConfigError {
             ConfigError::Parse(path, e) => {
                 write!(f, "Failed to parse config at {}: {}", path.display(), e)
             }
+            ConfigError::Category(path, e) => {
+                write!(f, "Invalid config at {}: {}", path.display(), e)
+            }
         }
     }
 }
@@ -0,0 +1,169 @@
+use crate::cache::CachedFile;
+use crate::exclude::ExcludeMatcher;
+use crate::hardlinks::{inode_key, HardLinkTracker};
+use crate::scanner::{allocated_size, ScanStats};
+use crate::types::{FileMetadata, Warning};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Reads one directory level for the stack-based scanners
+/// ([`IncrementalScanner`](crate::IncrementalScanner) and
+/// [`ResumableScanner`](crate::ResumableScanner)): regular files with their
+/// metadata, and the names of subdirectories still to visit.
+///
+/// Excluded entries are dropped here, so excluded subtrees are never entered.
+pub struct DirReader {
+    follow_symlinks: bool,
+    exclude: Arc<ExcludeMatcher>,
+    /// `stat`s and `read_dir`s made so far
+    calls: AtomicU64,
+}
+
+impl DirReader {
+    pub fn new(follow_symlinks: bool, exclude: Arc<ExcludeMatcher>) -> Self {
+        Self {
+            follow_symlinks,
+            exclude,
+            calls: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of `stat`s and `read_dir`s made through this reader so far.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// The entries of `dir`.
+    pub fn read_dir(&self, dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        fs::read_dir(dir)?.collect()
+    }
+
+    /// `stat` or `lstat`, depending on whether symlinks are followed.
+    pub fn metadata(&self, path: &Path) -> io::Result<fs::Metadata> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if self.follow_symlinks {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        }
+    }
+
+    /// Split the entries of one directory into files and subdirectory names.
+    /// Entries that can't be read are reported in `warnings` and skipped.
+    pub fn read(&self, entries: Vec<fs::DirEntry>, warnings: &mut Vec<Warning>) -> (Vec<CachedFile>, Vec<OsString>) {
+        let mut files = Vec::new();
+        let mut subdirs = Vec::new();
+
+        for entry in entries {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    warnings.push(warning(&path, &e));
+                    continue;
+                }
+            };
+
+            // Unfollowed symlinks are neither files nor directories, as in `Scanner`
+            if file_type.is_symlink() && !self.follow_symlinks {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                if !self.exclude.is_excluded(&path, true) {
+                    subdirs.push(entry.file_name());
+                }
+                continue;
+            }
+
+            let metadata = match self.metadata(&path) {
+                Ok(m) => m,
+                Err(e) => {
+                    warnings.push(warning(&path, &e));
+                    continue;
+                }
+            };
+
+            if self.exclude.is_excluded(&path, metadata.is_dir()) {
+                continue;
+            }
+            if metadata.is_dir() {
+                subdirs.push(entry.file_name());
+            } else if metadata.is_file() {
+                files.push(file_record(entry.file_name(), &metadata));
+            }
+        }
+
+        (files, subdirs)
+    }
+}
+
+pub fn file_record(name: OsString, metadata: &fs::Metadata) -> CachedFile {
+    CachedFile {
+        name,
+        size: metadata.len(),
+        disk_size: allocated_size(metadata),
+        modified: metadata.modified().ok(),
+        inode_key: inode_key(metadata),
+    }
+}
+
+/// Count a file from `dir` in `stats` and build its [`FileMetadata`].
+///
+/// Returns `None` for additional links to an inode that was already counted.
+pub fn count_file(
+    dir: &Path,
+    device: Option<u64>,
+    file: &CachedFile,
+    need_modified: bool,
+    hard_links: &HardLinkTracker,
+    stats: &mut ScanStats,
+) -> Option<FileMetadata> {
+    if !hard_links.first_seen_key(file.inode_key) {
+        stats.hardlink_bytes += file.size;
+        return None;
+    }
+
+    let path = dir.join(&file.name);
+    stats.total_bytes += file.size;
+    stats.disk_bytes += file.disk_size;
+    stats.file_count += 1;
+    stats.devices.add_file_on(device, &path, file.size, file.disk_size);
+
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase());
+
+    Some(FileMetadata {
+        path,
+        size: file.size,
+        disk_size: file.disk_size,
+        extension,
+        modified: if need_modified { file.modified } else { None },
+    })
+}
+
+/// `(dev, ino)` of a directory, for cycle detection when following symlinks.
+#[cfg(unix)]
+pub fn dir_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn dir_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+pub fn warning(path: &Path, error: &io::Error) -> Warning {
+    Warning {
+        path: path.display().to_string(),
+        error: error.to_string(),
+    }
+}
@@ -12,10 +12,17 @@ pub struct DuplicateGroup {
     pub wasted_space: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DuplicateFinder {
     size_groups: HashMap<u64, Vec<PathBuf>>,
 }
 
+impl Default for DuplicateFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DuplicateFinder {
     pub fn new() -> Self {
         Self {
@@ -69,7 +76,7 @@ impl DuplicateFinder {
             }
         }
 
-        duplicates.sort_by(|a, b| b.wasted_space.cmp(&a.wasted_space));
+        duplicates.sort_by_key(|d| std::cmp::Reverse(d.wasted_space));
         duplicates
     }
 
@@ -0,0 +1,1218 @@
+use crate::categorize::{AgeCategorizer, Categorizer, SizeCategorizer, TypeCategorizer};
+use crate::cache::DirCache;
+use crate::checkpoint::ScanCheckpoint;
+use crate::collector::SinglePassCollector;
+use crate::cleanup::{CleanupAnalyzer, CleanupRule};
+use crate::config::SpacemapConfig;
+use crate::policy::{PolicyChecker, PolicyError};
+use crate::duplicates::DuplicateFinder;
+use crate::exclude::{ExcludeMatcher, PatternError};
+use crate::incremental_scanner::IncrementalScanner;
+use crate::mounts::MountTable;
+use crate::parallel_scanner::ParallelScanner;
+use crate::progress::{ProgressState, ScanProgress};
+use crate::resumable_scanner::ResumableScanner;
+use crate::scanner::Scanner;
+use crate::types::{DirTotals, DuplicateGroup, FileMetadata, ScanResults, SizeMode, Totals};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Categorization mode used to build result buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanMode {
+    #[default]
+    Type,
+    Size,
+    Age,
+}
+
+impl ScanMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanMode::Type => "type",
+            ScanMode::Size => "size",
+            ScanMode::Age => "age",
+        }
+    }
+}
+
+impl FromStr for ScanMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "type" => Ok(ScanMode::Type),
+            "size" => Ok(ScanMode::Size),
+            "age" => Ok(ScanMode::Age),
+            _ => Err(format!("Invalid --by mode: {}. Must be type, size, or age", s)),
+        }
+    }
+}
+
+/// Options for a single scan, built with chained setters:
+///
+/// ```no_run
+/// use spacemap::{ScanMode, ScanOptions};
+///
+/// let options = ScanOptions::new()
+///     .mode(ScanMode::Age)
+///     .exclude(vec!["node_modules".to_string()])
+///     .find_duplicates(true);
+/// ```
+pub struct ScanOptions {
+    pub(crate) mode: ScanMode,
+    pub(crate) size_mode: SizeMode,
+    dir_totals: DirTotals,
+    categorizer: Option<Box<dyn Categorizer>>,
+    config: Option<SpacemapConfig>,
+    size_buckets: Option<Vec<u64>>,
+    age_buckets: Option<Vec<u64>>,
+    max_depth: Option<usize>,
+    exclude: Vec<String>,
+    follow_symlinks: bool,
+    one_file_system: bool,
+    parallel: bool,
+    threads: usize,
+    top_n: usize,
+    collect_tops: bool,
+    find_duplicates: bool,
+    sniff: bool,
+    suggest_cleanup: bool,
+    check_policy: bool,
+    progress: bool,
+    checkpoint_path: Option<PathBuf>,
+    checkpoint_interval: u64,
+    resume_path: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    on_file: Option<FileObserver>,
+    progress_state: Option<Arc<ProgressState>>,
+}
+
+/// Callback invoked for every counted file and the key of the category it
+/// was filed under; see [`ScanOptions::on_file`].
+pub type FileObserver = Arc<dyn Fn(&FileMetadata, &str) + Send + Sync>;
+
+impl ScanOptions {
+    pub fn new() -> Self {
+        Self {
+            mode: ScanMode::Type,
+            size_mode: SizeMode::Apparent,
+            dir_totals: DirTotals::Cumulative,
+            categorizer: None,
+            config: None,
+            size_buckets: None,
+            age_buckets: None,
+            max_depth: None,
+            exclude: Vec::new(),
+            follow_symlinks: false,
+            one_file_system: false,
+            parallel: false,
+            threads: 0,
+            top_n: 10,
+            collect_tops: true,
+            find_duplicates: false,
+            sniff: false,
+            suggest_cleanup: false,
+            check_policy: false,
+            progress: false,
+            checkpoint_path: None,
+            checkpoint_interval: 300,
+            resume_path: None,
+            cache_dir: None,
+            on_file: None,
+            progress_state: None,
+        }
+    }
+
+    /// Categorization mode (default: type).
+    pub fn mode(mut self, mode: ScanMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether percentages and rankings use apparent or allocated size
+    /// (default: apparent). Both values are always reported.
+    pub fn size_mode(mut self, size_mode: SizeMode) -> Self {
+        self.size_mode = size_mode;
+        self
+    }
+
+    /// Whether `top_dirs` counts only files directly inside each directory or
+    /// everything below it (default: cumulative).
+    pub fn dir_totals(mut self, dir_totals: DirTotals) -> Self {
+        self.dir_totals = dir_totals;
+        self
+    }
+
+    /// Use a custom categorizer instead of the one selected by `mode`.
+    /// The mode is still recorded in the results.
+    pub fn categorizer(mut self, categorizer: Box<dyn Categorizer>) -> Self {
+        self.categorizer = Some(categorizer);
+        self
+    }
+
+    /// Configuration used for custom categories in type mode.
+    pub fn config(mut self, config: Option<SpacemapConfig>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Custom size bucket boundaries in bytes.
+    pub fn size_buckets(mut self, buckets: Option<Vec<u64>>) -> Self {
+        self.size_buckets = buckets;
+        self
+    }
+
+    /// Custom age bucket boundaries in days.
+    pub fn age_buckets(mut self, buckets: Option<Vec<u64>>) -> Self {
+        self.age_buckets = buckets;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Don't descend into directories on a different filesystem than `path`
+    /// (like `du -x`). Skipped mount points are listed in
+    /// `ScanResults::skipped_mounts`.
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+
+    /// Walk the tree with jwalk/rayon instead of a single thread.
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Thread count for parallel scanning (0 = auto-detect).
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Number of top files/directories to keep (default: 10).
+    pub fn top(mut self, top_n: usize) -> Self {
+        self.top_n = top_n;
+        self
+    }
+
+    /// Whether to track top files/directories at all (default: true).
+    pub fn collect_tops(mut self, collect: bool) -> Self {
+        self.collect_tops = collect;
+        self
+    }
+
+    pub fn find_duplicates(mut self, find: bool) -> Self {
+        self.find_duplicates = find;
+        self
+    }
+
+    /// In type mode, read the first bytes of files whose extension isn't
+    /// known and categorize them by content (see [`crate::sniff`]).
+    pub fn sniff(mut self, sniff: bool) -> Self {
+        self.sniff = sniff;
+        self
+    }
+
+    /// Look for directories that can be regenerated, like `node_modules` or a
+    /// Cargo `target/`, using the built-in rules and the config's
+    /// `[[cleanup]]` rules. They end up in `ScanResults::cleanup`.
+    pub fn suggest_cleanup(mut self, suggest: bool) -> Self {
+        self.suggest_cleanup = suggest;
+        self
+    }
+
+    /// Check every file against the config's `[[policy]]` rules. The outcome
+    /// ends up in `ScanResults::policy`.
+    pub fn check_policy(mut self, check: bool) -> Self {
+        self.check_policy = check;
+        self
+    }
+
+    /// Show a progress spinner on stderr while scanning.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Periodically write a checkpoint to `path`. The file is removed once
+    /// the scan completes.
+    pub fn checkpoint(mut self, path: Option<PathBuf>, interval_secs: u64) -> Self {
+        self.checkpoint_path = path;
+        self.checkpoint_interval = interval_secs;
+        self
+    }
+
+    /// Continue the scan saved in the checkpoint at `path` instead of
+    /// starting over. Directories finished before the checkpoint are not read
+    /// again. Further checkpoints go to the `checkpoint` path if set, otherwise
+    /// back to `path`.
+    ///
+    /// The checkpoint must be for the same path and the same result-shaping
+    /// options, or the scan fails with [`ScanError::InvalidCheckpoint`].
+    pub fn resume(mut self, path: Option<PathBuf>) -> Self {
+        self.resume_path = path;
+        self
+    }
+
+    /// Keep a per-directory cache in `dir` and reuse the listings of
+    /// directories whose metadata hasn't changed since the last cached scan
+    /// (sequential mode only). Hit/miss counts end up in `ScanResults::cache`.
+    pub fn cache(mut self, dir: Option<PathBuf>) -> Self {
+        self.cache_dir = dir;
+        self
+    }
+
+    /// Call `observer` with every file as it is counted, along with its
+    /// category, e.g. to build a tree of the scan or report it live. With `parallel` it is called from
+    /// several threads at once. Files always carry their modification time
+    /// when an observer is set.
+    pub fn on_file(mut self, observer: Option<FileObserver>) -> Self {
+        self.on_file = observer;
+        self
+    }
+
+    /// Publish file and byte counts to `state` while scanning, for callers
+    /// that report on a scan from another thread. Sequential, cached and
+    /// resumable scans update it every 1000 files.
+    pub fn progress_state(mut self, state: Option<Arc<ProgressState>>) -> Self {
+        self.progress_state = state;
+        self
+    }
+
+    /// Options that change what a directory listing contains; each combination
+    /// gets its own cache file.
+    fn cache_key(&self) -> String {
+        // Listings depend on the first two; directory summaries on how files
+        // are categorized and ranked
+        const SHAPING: [&str; 10] = [
+            "--follow-symlinks",
+            "--exclude",
+            "--by",
+            "--size-mode",
+            "--config",
+            "--size-buckets",
+            "--age-buckets",
+            "--top",
+            "top lists",
+            "--sniff",
+        ];
+        let options: Vec<String> = self
+            .checkpoint_options()
+            .into_iter()
+            .filter(|(name, _)| SHAPING.contains(&name.as_str()))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        options.join(";")
+    }
+
+    /// Options recorded in a checkpoint, named after their command-line flags.
+    /// Resuming with any of them changed would mix incompatible results.
+    fn checkpoint_options(&self) -> Vec<(String, String)> {
+        let list = |values: &Option<Vec<u64>>| match values {
+            Some(values) => values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","),
+            None => "default".to_string(),
+        };
+        let config = match self.config {
+            Some(ref config) => {
+                let categories = format!("{:?}{:?}", config.categories, config.remaps);
+                blake3::hash(categories.as_bytes()).to_hex()[..16].to_string()
+            }
+            None => "none".to_string(),
+        };
+        let cleanup = if self.suggest_cleanup {
+            let rules = format!("{:?}", CleanupRule::from_config(self.config.as_ref()));
+            blake3::hash(rules.as_bytes()).to_hex()[..16].to_string()
+        } else {
+            "false".to_string()
+        };
+
+        vec![
+            ("--by".to_string(), self.mode.as_str().to_string()),
+            ("--size-mode".to_string(), self.size_mode.as_str().to_string()),
+            ("--dir-totals".to_string(), self.dir_totals.as_str().to_string()),
+            ("categorizer".to_string(), if self.categorizer.is_some() { "custom" } else { "builtin" }.to_string()),
+            ("--config".to_string(), config),
+            ("--size-buckets".to_string(), list(&self.size_buckets)),
+            ("--age-buckets".to_string(), list(&self.age_buckets)),
+            ("--max-depth".to_string(), self.max_depth.map_or("none".to_string(), |d| d.to_string())),
+            ("--exclude".to_string(), self.exclude.join(",")),
+            ("--follow-symlinks".to_string(), self.follow_symlinks.to_string()),
+            ("--one-file-system".to_string(), self.one_file_system.to_string()),
+            ("--top".to_string(), self.top_n.to_string()),
+            ("top lists".to_string(), self.collect_tops.to_string()),
+            ("--find-duplicates".to_string(), self.find_duplicates.to_string()),
+            ("--sniff".to_string(), self.sniff.to_string()),
+            ("--suggest-cleanup".to_string(), cleanup),
+        ]
+    }
+
+    /// Fingerprint of the options that change which files a scan counts and
+    /// how it sizes and groups them. Scans with different keys don't belong
+    /// in one history series.
+    pub fn history_key(&self) -> String {
+        const SHAPING: [&str; 10] = [
+            "--by",
+            "--size-mode",
+            "--dir-totals",
+            "--size-buckets",
+            "--age-buckets",
+            "--max-depth",
+            "--exclude",
+            "--follow-symlinks",
+            "--one-file-system",
+            "--sniff",
+        ];
+        let options: Vec<String> = self
+            .checkpoint_options()
+            .into_iter()
+            .filter(|(name, _)| SHAPING.contains(&name.as_str()))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        blake3::hash(options.join("\0").as_bytes()).to_hex()[..16].to_string()
+    }
+
+    /// Whether a custom categorizer replaces the one selected by `mode`.
+    pub fn has_categorizer(&self) -> bool {
+        self.categorizer.is_some()
+    }
+
+    /// The categorizer a scan of `root` files files with: the custom one if
+    /// set, otherwise the built-in one for `mode`.
+    pub fn build_categorizer(&self, root: &Path) -> Box<dyn Categorizer> {
+        if let Some(ref categorizer) = self.categorizer {
+            return categorizer.clone_box();
+        }
+
+        self.categorizer_for(self.mode, root)
+    }
+
+    /// How many top files and directories are kept.
+    pub(crate) fn top_n(&self) -> usize {
+        self.top_n
+    }
+
+    /// The compiled `exclude` patterns, anchored at `root`.
+    pub(crate) fn exclude_matcher(&self, root: &Path) -> Result<ExcludeMatcher, ScanError> {
+        ExcludeMatcher::new(root, &self.exclude).map_err(ScanError::InvalidPattern)
+    }
+
+    /// A sequential scanner for a part of the tree `depth` levels below the
+    /// scan root, e.g. to read a changed directory again. `max_depth` still
+    /// counts from the scan root.
+    pub(crate) fn subtree_scanner(&self, exclude: Arc<ExcludeMatcher>, depth: usize) -> Option<Scanner> {
+        let max_depth = match self.max_depth {
+            Some(max_depth) if depth > max_depth => return None,
+            Some(max_depth) => Some(max_depth - depth),
+            None => None,
+        };
+        Some(Scanner::new(self.follow_symlinks, max_depth, exclude, true, self.one_file_system))
+    }
+
+    /// The built-in categorizer for `mode` in a scan of `root`, with this
+    /// scan's config and buckets.
+    pub(crate) fn categorizer_for(&self, mode: ScanMode, root: &Path) -> Box<dyn Categorizer> {
+        match mode {
+            ScanMode::Type => Box::new(
+                TypeCategorizer::with_config(self.config.as_ref())
+                    .with_root(root)
+                    .with_sniff(self.sniff),
+            ),
+            ScanMode::Size => Box::new(SizeCategorizer::new(self.size_buckets.clone())),
+            ScanMode::Age => Box::new(AgeCategorizer::new(self.age_buckets.clone())),
+        }
+    }
+
+    fn build_collector(&self, categorizer: Box<dyn Categorizer>, root: &Path) -> SinglePassCollector {
+        SinglePassCollector::new(categorizer, self.top_n, self.collect_tops)
+            .with_size_mode(self.size_mode)
+            .with_dir_totals(self.dir_totals, root.to_path_buf())
+    }
+
+    fn validate(&self) -> Result<(), ScanError> {
+        if self.cache_dir.is_some() && self.parallel {
+            return Err(ScanError::InvalidOptions(
+                "--cached is not supported with --parallel mode".to_string(),
+            ));
+        }
+        if self.cache_dir.is_some() && (self.checkpoint_path.is_some() || self.resume_path.is_some()) {
+            return Err(ScanError::InvalidOptions(
+                "--cached cannot be combined with --checkpoint or --resume".to_string(),
+            ));
+        }
+        if self.check_policy && (self.checkpoint_path.is_some() || self.resume_path.is_some()) {
+            return Err(ScanError::InvalidOptions(
+                "--check-policy cannot be combined with --checkpoint or --resume".to_string(),
+            ));
+        }
+        // Observers only see the files counted after the checkpoint
+        if self.on_file.is_some() && self.resume_path.is_some() {
+            return Err(ScanError::InvalidOptions(
+                "--resume cannot be combined with --sqlite or an on_file observer".to_string(),
+            ));
+        }
+        if self.check_policy && self.config.as_ref().is_none_or(|c| c.policy.is_empty()) {
+            return Err(ScanError::InvalidOptions(
+                "--check-policy needs at least one [[policy]] rule in the config".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for ScanOptions {
+    fn clone(&self) -> Self {
+        Self {
+            mode: self.mode,
+            size_mode: self.size_mode,
+            dir_totals: self.dir_totals,
+            categorizer: self.categorizer.as_ref().map(|c| c.clone_box()),
+            config: self.config.clone(),
+            size_buckets: self.size_buckets.clone(),
+            age_buckets: self.age_buckets.clone(),
+            max_depth: self.max_depth,
+            exclude: self.exclude.clone(),
+            follow_symlinks: self.follow_symlinks,
+            one_file_system: self.one_file_system,
+            parallel: self.parallel,
+            threads: self.threads,
+            top_n: self.top_n,
+            collect_tops: self.collect_tops,
+            find_duplicates: self.find_duplicates,
+            sniff: self.sniff,
+            suggest_cleanup: self.suggest_cleanup,
+            check_policy: self.check_policy,
+            progress: self.progress,
+            checkpoint_path: self.checkpoint_path.clone(),
+            checkpoint_interval: self.checkpoint_interval,
+            resume_path: self.resume_path.clone(),
+            cache_dir: self.cache_dir.clone(),
+            on_file: self.on_file.clone(),
+            progress_state: self.progress_state.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScanError {
+    PathNotFound(PathBuf),
+    InvalidOptions(String),
+    InvalidPattern(PatternError),
+    Checkpoint(PathBuf, std::io::Error),
+    /// A checkpoint that can't be resumed: unreadable, or taken for another
+    /// path or with different options
+    InvalidCheckpoint(PathBuf, String),
+    Cache(PathBuf, std::io::Error),
+    InvalidPolicy(PolicyError),
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::PathNotFound(path) => write!(f, "Path does not exist: {}", path.display()),
+            ScanError::InvalidOptions(msg) => write!(f, "{}", msg),
+            ScanError::InvalidPattern(e) => write!(f, "{}", e),
+            ScanError::Checkpoint(path, e) => {
+                write!(f, "Failed to write checkpoint {}: {}", path.display(), e)
+            }
+            ScanError::InvalidCheckpoint(path, msg) => {
+                write!(f, "Cannot resume from {}: {}", path.display(), msg)
+            }
+            ScanError::Cache(path, e) => write!(f, "Failed to update cache {}: {}", path.display(), e),
+            ScanError::InvalidPolicy(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/// Scan `path` and return aggregated results.
+///
+/// Unreadable entries do not fail the scan; they are reported in
+/// `ScanResults::warnings`.
+pub fn scan(path: impl AsRef<Path>, options: ScanOptions) -> Result<ScanResults, ScanError> {
+    let path = path.as_ref().to_path_buf();
+    options.validate()?;
+
+    if !path.exists() {
+        return Err(ScanError::PathNotFound(path));
+    }
+
+    let categorizer = options.build_categorizer(&path);
+
+    // One compiled matcher, shared by whichever scanner runs
+    let exclude = Arc::new(options.exclude_matcher(&path)?);
+
+    let policy = if options.check_policy {
+        let rules = options.config.as_ref().map(|c| c.policy.as_slice()).unwrap_or_default();
+        let checker = PolicyChecker::new(&path, rules, options.categorizer_for(ScanMode::Type, &path), options.size_mode)
+            .map_err(ScanError::InvalidPolicy)?;
+        Some(checker)
+    } else {
+        None
+    };
+
+    // Only need modified time if using age categorization or category rules
+    // with an age, for cleanup ages or policy age limits, or for an observer
+    let need_modified = options.mode == ScanMode::Age
+        || categorizer.needs_modified()
+        || options.suggest_cleanup
+        || policy.as_ref().is_some_and(|p| p.needs_modified())
+        || options.on_file.is_some();
+
+    // A checkpoint to continue from, or a fresh one if checkpointing is requested
+    let mut checkpoint = match options.resume_path {
+        Some(ref resume_path) => {
+            let invalid = |msg: String| ScanError::InvalidCheckpoint(resume_path.clone(), msg);
+            let ckpt = ScanCheckpoint::load(resume_path).map_err(|e| invalid(e.to_string()))?;
+            ckpt.check_compatible(&path, &options.checkpoint_options()).map_err(invalid)?;
+            Some(ckpt)
+        }
+        None if options.checkpoint_path.is_some() => {
+            Some(ScanCheckpoint::new(path.clone(), options.checkpoint_options()))
+        }
+        None => None,
+    };
+    // New checkpoints replace the one being resumed unless told otherwise
+    let checkpoint_path = options.checkpoint_path.clone().or_else(|| options.resume_path.clone());
+
+    // Duplicate finder (only if requested), with the size groups from the checkpoint
+    let dup_finder = if options.find_duplicates {
+        let restored = checkpoint
+            .as_mut()
+            .and_then(|ckpt| ckpt.state.as_mut())
+            .and_then(|state| state.duplicates.take());
+        Some(Arc::new(Mutex::new(restored.unwrap_or_else(DuplicateFinder::new))))
+    } else {
+        None
+    };
+
+    // Cleanup analyzer (only if requested), with the directories from the checkpoint
+    let cleanup = if options.suggest_cleanup {
+        let restored = checkpoint
+            .as_mut()
+            .and_then(|ckpt| ckpt.state.as_mut())
+            .and_then(|state| state.cleanup.take());
+        let analyzer = CleanupAnalyzer::new(&path, CleanupRule::from_config(options.config.as_ref()));
+        Some(analyzer.with_candidates(restored.unwrap_or_default()))
+    } else {
+        None
+    };
+
+    // Every counted file goes to the duplicate finder, the cleanup analyzer,
+    // the policy checker and the caller's observer
+    let observe = |meta: &FileMetadata, category: &str| {
+        if let Some(ref df) = dup_finder {
+            df.lock().add_file(meta.path.clone(), meta.size);
+        }
+        if let Some(ref cleanup) = cleanup {
+            cleanup.add_file(meta);
+        }
+        if let Some(ref policy) = policy {
+            policy.add_file(meta);
+        }
+        if let Some(ref on_file) = options.on_file {
+            on_file(meta, category);
+        }
+    };
+
+    let progress = ScanProgress::new(options.progress).with_state(options.progress_state.clone());
+
+    let mut cache_stats = None;
+
+    let (stats, results) = if let (Some(checkpoint), Some(checkpoint_path)) = (checkpoint.as_mut(), &checkpoint_path) {
+        // Sequential scans read one directory at a time; parallel ones use all threads
+        let threads = if options.parallel { options.threads } else { 1 };
+        let scanner = ResumableScanner::new(
+            threads,
+            options.follow_symlinks,
+            options.max_depth,
+            exclude.clone(),
+            need_modified,
+            options.one_file_system,
+        )
+        .with_checkpoint_interval(Duration::from_secs(options.checkpoint_interval));
+
+        if checkpoint.state.is_none() {
+            checkpoint
+                .save(checkpoint_path)
+                .map_err(|e| ScanError::Checkpoint(checkpoint_path.clone(), e))?;
+        }
+
+        let (stats, collector) = scanner.scan(
+            &path,
+            checkpoint,
+            || options.build_collector(categorizer.clone_box(), &path),
+            &progress,
+            observe,
+            |ckpt| {
+                if let (Some(state), Some(df)) = (ckpt.state.as_mut(), &dup_finder) {
+                    state.duplicates = Some(df.lock().clone());
+                }
+                if let (Some(state), Some(cleanup)) = (ckpt.state.as_mut(), &cleanup) {
+                    state.cleanup = Some(cleanup.candidates());
+                }
+                ckpt.save(checkpoint_path)
+            },
+        );
+
+        // The scan is complete; nothing left to resume
+        let _ = std::fs::remove_file(checkpoint_path);
+
+        let results = collector.finalize(options.size_mode.select(stats.total_bytes, stats.disk_bytes));
+        (stats, results)
+    } else if options.parallel {
+        // Parallel filesystem walking with thread-local collectors
+        let parallel_scanner = ParallelScanner::new(
+            options.threads,
+            options.follow_symlinks,
+            options.max_depth,
+            exclude.clone(),
+            need_modified,
+            options.one_file_system,
+        );
+
+
+        // Each thread processes into its own collector, then merge at end
+        let (stats, collector) = parallel_scanner.scan(
+            &path,
+            || options.build_collector(categorizer.clone_box(), &path),
+            &progress,
+            observe,
+        );
+
+        let results = collector.finalize(options.size_mode.select(stats.total_bytes, stats.disk_bytes));
+        (stats, results)
+    } else if let Some(ref cache_dir) = options.cache_dir {
+        let mut cache = DirCache::open(cache_dir, &path, &options.cache_key())
+            .map_err(|e| ScanError::Cache(cache_dir.clone(), e))?;
+        // Cached summaries stand in for files only when nothing needs the
+        // files themselves and their categories don't depend on the time
+        let summaries = !need_modified && dup_finder.is_none() && policy.is_none() && options.categorizer.is_none();
+        let scanner = IncrementalScanner::new(
+            options.follow_symlinks,
+            options.max_depth,
+            exclude.clone(),
+            need_modified,
+            options.one_file_system,
+        )
+        .with_summaries(summaries);
+        let scratch = options.build_collector(categorizer.clone_box(), &path);
+        let mut collector = options.build_collector(categorizer, &path);
+
+        let stats = scanner.scan(&path, &mut cache, &mut collector, scratch, observe, &progress);
+
+        cache_stats = Some(cache.stats());
+        let cache_file = cache.path().to_path_buf();
+        cache.save().map_err(|e| ScanError::Cache(cache_file, e))?;
+
+        let results = collector.finalize(options.size_mode.select(stats.total_bytes, stats.disk_bytes));
+        (stats, results)
+    } else {
+        let scanner = Scanner::new(
+            options.follow_symlinks,
+            options.max_depth,
+            exclude.clone(),
+            need_modified,
+            options.one_file_system,
+        );
+        let mut collector = options.build_collector(categorizer, &path);
+
+        let stats = scanner.scan(&path, |meta| {
+            let category = collector.process_file(&meta);
+            observe(&meta, &category);
+        }, &progress);
+
+        let results = collector.finalize(options.size_mode.select(stats.total_bytes, stats.disk_bytes));
+        (stats, results)
+    };
+
+    // Find duplicates if requested
+    let duplicates = if let Some(df) = dup_finder {
+        let finder = Arc::try_unwrap(df)
+            .unwrap_or_else(|_| panic!("Failed to unwrap duplicate finder"))
+            .into_inner();
+
+        let dup_groups = finder.find_duplicates();
+        if dup_groups.is_empty() {
+            None
+        } else {
+            Some(dup_groups.into_iter().map(|dg| DuplicateGroup {
+                size: dg.size,
+                hash: dg.hash,
+                paths: dg.paths,
+                wasted_space: dg.wasted_space,
+            }).collect())
+        }
+    } else {
+        None
+    };
+
+    let mount_table = MountTable::load();
+
+    Ok(ScanResults {
+        scanned_path: path.display().to_string(),
+        mode: options.mode.as_str().to_string(),
+        size_mode: options.size_mode,
+        dir_totals: options.dir_totals,
+        totals: Totals {
+            total_bytes: stats.total_bytes,
+            disk_bytes: stats.disk_bytes,
+            hardlink_bytes: stats.hardlink_bytes,
+            file_count: stats.file_count,
+            dir_count: stats.dir_count,
+            skipped_paths: stats.warnings.len() as u64,
+        },
+        disk_usage: mount_table.disk_usage(&path),
+        mounts: mount_table.mounts(&stats.devices, options.size_mode),
+        buckets: results.buckets,
+        top_files: results.top_files,
+        top_dirs: results.top_dirs,
+        warnings: stats.warnings,
+        skipped_mounts: stats.skipped_mounts,
+        duplicates,
+        cache: cache_stats,
+        cleanup: cleanup.map(|analyzer| analyzer.suggestions(options.size_mode)),
+        policy: policy.map(PolicyChecker::finish),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn create_tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), vec![b'a'; 300]).unwrap();
+        fs::write(dir.path().join("notes.txt"), vec![b'b'; 200]).unwrap();
+        fs::write(dir.path().join("photo.png"), vec![b'c'; 100]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_scan_sequential_and_parallel_agree() {
+        let dir = create_tree();
+
+        let sequential = scan(dir.path(), ScanOptions::new()).unwrap();
+        let parallel = scan(dir.path(), ScanOptions::new().parallel(true).threads(2)).unwrap();
+
+        assert_eq!(sequential.totals.total_bytes, 600);
+        assert_eq!(sequential.totals.file_count, 3);
+        assert_eq!(parallel.totals.total_bytes, sequential.totals.total_bytes);
+        assert_eq!(parallel.totals.file_count, sequential.totals.file_count);
+        assert_eq!(sequential.mode, "type");
+        assert_eq!(sequential.top_files[0].bytes, 300);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_reports_allocated_size() {
+        let dir = TempDir::new().unwrap();
+        // Sparse file: 8 MiB apparent, nothing written
+        let file = fs::File::create(dir.path().join("sparse.img")).unwrap();
+        file.set_len(8 * 1024 * 1024).unwrap();
+
+        let results = scan(dir.path(), ScanOptions::new().size_mode(SizeMode::Disk)).unwrap();
+
+        assert_eq!(results.size_mode, SizeMode::Disk);
+        assert_eq!(results.totals.total_bytes, 8 * 1024 * 1024);
+        assert!(results.totals.disk_bytes < results.totals.total_bytes);
+        assert_eq!(results.top_files[0].disk_bytes, results.totals.disk_bytes);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_counts_hard_links_once() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("a")).unwrap();
+        fs::create_dir(dir.path().join("b")).unwrap();
+        fs::write(dir.path().join("a/data.bin"), vec![0u8; 1000]).unwrap();
+        for i in 0..3 {
+            fs::hard_link(dir.path().join("a/data.bin"), dir.path().join(format!("b/link{}.bin", i))).unwrap();
+        }
+
+        for options in [ScanOptions::new(), ScanOptions::new().parallel(true).threads(4)] {
+            let results = scan(dir.path(), options).unwrap();
+            assert_eq!(results.totals.total_bytes, 1000);
+            assert_eq!(results.totals.file_count, 1);
+            assert_eq!(results.totals.hardlink_bytes, 3000);
+        }
+    }
+
+    #[test]
+    fn test_scan_exclude_patterns_agree() {
+        let dir = create_tree();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::write(dir.path().join("target/debug/app"), vec![0u8; 1000]).unwrap();
+        fs::write(dir.path().join("targets.txt"), vec![0u8; 10]).unwrap();
+        fs::write(dir.path().join("src/build.log"), vec![0u8; 50]).unwrap();
+
+        let patterns = vec!["target".to_string(), "*.log".to_string(), "re:^/photo".to_string()];
+        let sequential = scan(dir.path(), ScanOptions::new().exclude(patterns.clone())).unwrap();
+        let parallel = scan(dir.path(), ScanOptions::new().exclude(patterns).parallel(true)).unwrap();
+
+        // main.rs + notes.txt + targets.txt
+        assert_eq!(sequential.totals.total_bytes, 510);
+        assert_eq!(sequential.totals.file_count, 3);
+        assert_eq!(parallel.totals.total_bytes, sequential.totals.total_bytes);
+        assert_eq!(parallel.totals.file_count, sequential.totals.file_count);
+    }
+
+    #[test]
+    fn test_parallel_scan_prunes_excluded_directories() {
+        let dir = create_tree();
+        fs::create_dir_all(dir.path().join("skip/inner/deeper")).unwrap();
+        fs::write(dir.path().join("skip/inner/deeper/blob"), vec![0u8; 1000]).unwrap();
+
+        // This regex matches only the directory itself, not its children, so the
+        // children are only left out if the directory is never descended into
+        let options = ScanOptions::new().exclude(vec!["re:^/skip$".to_string()]);
+        let sequential = scan(dir.path(), options.clone()).unwrap();
+        let parallel = scan(dir.path(), options.parallel(true)).unwrap();
+
+        assert_eq!(sequential.totals.total_bytes, 600);
+        assert_eq!(parallel.totals.total_bytes, 600);
+        assert_eq!(parallel.totals.file_count, sequential.totals.file_count);
+        assert_eq!(parallel.totals.dir_count, sequential.totals.dir_count);
+    }
+
+    #[test]
+    fn test_one_file_system_on_single_filesystem() {
+        let dir = create_tree();
+        let options = ScanOptions::new().one_file_system(true);
+        let sequential = scan(dir.path(), options.clone()).unwrap();
+        let parallel = scan(dir.path(), options.parallel(true)).unwrap();
+
+        for results in [&sequential, &parallel] {
+            assert_eq!(results.totals.total_bytes, 600);
+            assert!(results.skipped_mounts.is_empty());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_one_file_system_skips_mount_points() {
+        use crate::scanner::root_device;
+
+        // /dev/shm is normally a tmpfs mounted on top of devtmpfs
+        let root = Path::new("/dev");
+        let shm = root.join("shm");
+        match (root_device(root), root_device(&shm)) {
+            (Some(a), Some(b)) if a != b => {}
+            _ => return,
+        }
+
+        let options = ScanOptions::new().one_file_system(true).max_depth(Some(1));
+        let sequential = scan(root, options.clone()).unwrap();
+        let parallel = scan(root, options.parallel(true)).unwrap();
+
+        for results in [&sequential, &parallel] {
+            assert!(results.skipped_mounts.iter().any(|m| Path::new(&m.path) == shm));
+            assert!(results.warnings.iter().all(|w| !Path::new(&w.path).starts_with(&shm)));
+        }
+    }
+
+    #[test]
+    fn test_scan_attributes_bytes_to_mounts() {
+        let dir = create_tree();
+        for options in [ScanOptions::new(), ScanOptions::new().parallel(true)] {
+            let results = scan(dir.path(), options).unwrap();
+            assert_eq!(results.mounts.len(), 1);
+            assert_eq!(results.mounts[0].bytes, 600);
+            assert_eq!(results.mounts[0].disk_bytes, results.totals.disk_bytes);
+            assert_eq!(results.mounts[0].file_count, 3);
+        }
+    }
+
+    #[test]
+    fn test_cached_scan_matches_uncached() {
+        let dir = create_tree();
+        let cache_dir = TempDir::new().unwrap();
+        let cached = || ScanOptions::new().cache(Some(cache_dir.path().to_path_buf()));
+
+        let plain = scan(dir.path(), ScanOptions::new()).unwrap();
+        let first = scan(dir.path(), cached()).unwrap();
+        let second = scan(dir.path(), cached()).unwrap();
+
+        assert!(plain.cache.is_none());
+        assert_eq!(first.cache.unwrap().hits, 0);
+        assert_eq!(second.cache.unwrap().misses, 0);
+        for results in [&first, &second] {
+            assert_eq!(results.totals.total_bytes, plain.totals.total_bytes);
+            assert_eq!(results.totals.file_count, plain.totals.file_count);
+            assert_eq!(results.totals.dir_count, plain.totals.dir_count);
+            assert_eq!(results.buckets.len(), plain.buckets.len());
+            for (bucket, expected) in results.buckets.iter().zip(&plain.buckets) {
+                assert_eq!((&bucket.key, bucket.bytes, bucket.file_count), (&expected.key, expected.bytes, expected.file_count));
+            }
+            let paths = |r: &ScanResults| r.top_dirs.iter().map(|d| (d.path.clone(), d.bytes)).collect::<Vec<_>>();
+            assert_eq!(paths(results), paths(&plain));
+            assert_eq!(results.top_files.len(), plain.top_files.len());
+            assert_eq!(results.mounts[0].file_count, plain.mounts[0].file_count);
+        }
+    }
+
+    #[test]
+    fn test_parallel_scan_reports_progress() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..5000 {
+            fs::write(dir.path().join(format!("{}.txt", i)), b"x").unwrap();
+        }
+
+        // Poll the shared state from inside the scan, like the server does from another thread
+        let state = Arc::new(ProgressState::new());
+        let seen = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let observer: FileObserver = {
+            let (state, seen) = (state.clone(), seen.clone());
+            Arc::new(move |_, _| {
+                seen.fetch_max(state.files(), std::sync::atomic::Ordering::Relaxed);
+            })
+        };
+        let options = ScanOptions::new()
+            .parallel(true)
+            .threads(2)
+            .progress_state(Some(state.clone()))
+            .on_file(Some(observer));
+        scan(dir.path(), options).unwrap();
+
+        assert!(seen.load(std::sync::atomic::Ordering::Relaxed) >= 1000);
+        assert_eq!(state.files(), 5000);
+        assert_eq!(state.bytes(), 5000);
+        assert!(state.is_finished());
+    }
+
+    #[test]
+    fn test_sniff_categorizes_by_content() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("photo"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(dir.path().join("tool"), b"\x7fELF\x02\x01\x01\0\0\0").unwrap();
+        fs::write(dir.path().join("notes"), b"just some text").unwrap();
+        fs::write(dir.path().join("logo.png"), b"not really a png").unwrap();
+
+        let bucket = |results: &ScanResults, key: &str| results.buckets.iter().find(|b| b.key == key).cloned();
+
+        let plain = scan(dir.path(), ScanOptions::new()).unwrap();
+        assert_eq!(bucket(&plain, "Other").unwrap().file_count, 3);
+        assert_eq!(bucket(&plain, "Images").unwrap().content_files, 0);
+
+        let sniffed = scan(dir.path(), ScanOptions::new().sniff(true)).unwrap();
+        let images = bucket(&sniffed, "Images").unwrap();
+        assert_eq!((images.file_count, images.content_files, images.content_bytes), (2, 1, 16));
+        assert_eq!(bucket(&sniffed, "Binaries").unwrap().content_files, 1);
+        let other = bucket(&sniffed, "Other").unwrap();
+        assert_eq!((other.file_count, other.content_files), (1, 0));
+
+        // Detected formats go through the same remaps as extensions
+        let config: SpacemapConfig = toml::from_str("[[remaps]]\nextensions = [\"bin\"]\ncategory = \"Tools\"").unwrap();
+        let remapped = scan(dir.path(), ScanOptions::new().sniff(true).config(Some(config))).unwrap();
+        assert_eq!(bucket(&remapped, "Tools").unwrap().content_files, 1);
+        assert!(bucket(&remapped, "Binaries").is_none());
+
+        // Category policies see the same categories as the buckets
+        let config: SpacemapConfig = toml::from_str("[[policy]]\nname = \"images\"\ncategory = \"Images\"\nmax_bytes = 20").unwrap();
+        let options = ScanOptions::new().sniff(true).check_policy(true).config(Some(config));
+        let policy = scan(dir.path(), options).unwrap().policy.unwrap();
+        assert_eq!(policy.checks[0].violations[0].value, Some(32));
+    }
+
+    #[test]
+    fn test_category_age_rules_see_modification_times() {
+        let dir = create_tree();
+        let old = fs::File::options().write(true).open(dir.path().join("notes.txt")).unwrap();
+        old.set_modified(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_580_000_000)).unwrap();
+
+        let config: SpacemapConfig = toml::from_str("[[categories]]\nname = \"Stale\"\nmin_age_days = 90").unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        for options in [
+            ScanOptions::new(),
+            ScanOptions::new().parallel(true),
+            ScanOptions::new().cache(Some(cache_dir.path().to_path_buf())),
+        ] {
+            let results = scan(dir.path(), options.config(Some(config.clone()))).unwrap();
+            let stale = results.buckets.iter().find(|b| b.key == "Stale").unwrap();
+            assert_eq!((stale.file_count, stale.bytes), (1, 200));
+        }
+    }
+
+    #[test]
+    fn test_category_paths_match_relative_roots() {
+        let dir = create_tree();
+        let data = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(data.join("cache")).unwrap();
+        fs::write(data.join("cache/blob.png"), vec![0u8; 50]).unwrap();
+
+        let toml = format!("[[categories]]\nname = \"Cache\"\npaths = [\"{}/cache\"]", data.display());
+        let config: SpacemapConfig = toml::from_str(&toml).unwrap();
+
+        // A root relative to the working directory, with a `..` to normalise
+        let cwd = std::env::current_dir().unwrap();
+        let mut relative: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+        relative.push(data.strip_prefix("/").unwrap());
+        let root = relative.join("src/..");
+
+        for options in [ScanOptions::new(), ScanOptions::new().parallel(true)] {
+            let results = scan(&root, options.config(Some(config.clone()))).unwrap();
+            let cache = results.buckets.iter().find(|b| b.key == "Cache").unwrap();
+            assert_eq!((cache.file_count, cache.bytes), (1, 50));
+        }
+    }
+
+    #[test]
+    fn test_suggest_cleanup() {
+        let dir = create_tree();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::write(dir.path().join("target/debug/app"), vec![0u8; 1000]).unwrap();
+
+        let results = scan(dir.path(), ScanOptions::new()).unwrap();
+        assert!(results.cleanup.is_none());
+
+        for options in [ScanOptions::new(), ScanOptions::new().parallel(true)] {
+            let results = scan(dir.path(), options.suggest_cleanup(true)).unwrap();
+            let suggestions = results.cleanup.unwrap();
+            assert_eq!(suggestions.len(), 1);
+            assert_eq!(suggestions[0].path, dir.path().join("target").display().to_string());
+            assert_eq!(suggestions[0].bytes, 1000);
+            assert!(suggestions[0].last_modified.is_some());
+        }
+    }
+
+    #[test]
+    fn test_scan_invalid_exclude_pattern() {
+        let dir = create_tree();
+        let err = scan(dir.path(), ScanOptions::new().exclude(vec!["re:(".to_string()])).unwrap_err();
+        assert!(matches!(err, ScanError::InvalidPattern(_)));
+    }
+
+    #[test]
+    fn test_scan_missing_path() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+
+        let err = scan(&missing, ScanOptions::new()).unwrap_err();
+        assert!(matches!(err, ScanError::PathNotFound(_)));
+    }
+
+    /// Save the first checkpoint a scan of `root` with `options` takes,
+    /// while there are still directories left to read.
+    fn interrupted_checkpoint(root: &Path, options: &ScanOptions, file: &Path) {
+        let categorizer = options.build_categorizer(root);
+        let scanner = ResumableScanner::new(1, false, None, Arc::new(ExcludeMatcher::empty(root)), false, false)
+            .with_checkpoint_interval(Duration::ZERO);
+        let mut checkpoint = ScanCheckpoint::new(root.to_path_buf(), options.checkpoint_options());
+        let mut saved = false;
+
+        scanner.scan(
+            root,
+            &mut checkpoint,
+            || options.build_collector(categorizer.clone_box(), root),
+            &ScanProgress::new(false),
+            |_, _| {},
+            |ckpt| {
+                if !saved {
+                    saved = true;
+                    ckpt.save(file)?;
+                }
+                Ok(())
+            },
+        );
+        assert!(saved);
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_scan() {
+        // Enough directories for more than one batch, so a checkpoint is taken mid-scan
+        let dir = create_tree();
+        for i in 0..1100 {
+            let sub = dir.path().join(format!("dirs/{:04}", i));
+            fs::create_dir_all(&sub).unwrap();
+            fs::write(sub.join(format!("f{}.txt", i)), vec![0u8; i + 1]).unwrap();
+        }
+        let ckpt_dir = TempDir::new().unwrap();
+        let ckpt_file = ckpt_dir.path().join("scan.ckpt");
+
+        let full = scan(dir.path(), ScanOptions::new()).unwrap();
+
+        for options in [ScanOptions::new(), ScanOptions::new().parallel(true).threads(4)] {
+            interrupted_checkpoint(dir.path(), &options, &ckpt_file);
+            let resumed = ScanCheckpoint::load(&ckpt_file).unwrap();
+            assert!(!resumed.pending.is_empty());
+            assert!(resumed.stats.file_count > 0);
+
+            let results = scan(dir.path(), options.resume(Some(ckpt_file.clone()))).unwrap();
+
+            assert_eq!(results.totals.total_bytes, full.totals.total_bytes);
+            assert_eq!(results.totals.file_count, full.totals.file_count);
+            assert_eq!(results.totals.dir_count, full.totals.dir_count);
+            let bytes = |r: &ScanResults| r.buckets.iter().map(|b| (b.key.clone(), b.bytes)).collect::<Vec<_>>();
+            assert_eq!(bytes(&results), bytes(&full));
+            let paths = |r: &ScanResults| r.top_dirs.iter().map(|d| (d.path.clone(), d.bytes)).collect::<Vec<_>>();
+            assert_eq!(paths(&results), paths(&full));
+            // A finished scan leaves nothing to resume
+            assert!(!ckpt_file.exists());
+        }
+    }
+
+    #[test]
+    fn test_resume_refuses_different_options() {
+        let dir = create_tree();
+        let ckpt_dir = TempDir::new().unwrap();
+        let ckpt_file = ckpt_dir.path().join("scan.ckpt");
+        ScanCheckpoint::new(dir.path().to_path_buf(), ScanOptions::new().checkpoint_options())
+            .save(&ckpt_file)
+            .unwrap();
+
+        for options in [
+            ScanOptions::new().mode(ScanMode::Size),
+            ScanOptions::new().exclude(vec!["target".to_string()]),
+            ScanOptions::new().max_depth(Some(2)),
+            ScanOptions::new().suggest_cleanup(true),
+        ] {
+            let err = scan(dir.path(), options.resume(Some(ckpt_file.clone()))).unwrap_err();
+            assert!(matches!(err, ScanError::InvalidCheckpoint(..)), "{}", err);
+        }
+
+        // Scanning another path with it is refused too
+        let other = TempDir::new().unwrap();
+        let err = scan(other.path(), ScanOptions::new().resume(Some(ckpt_file.clone()))).unwrap_err();
+        assert!(matches!(err, ScanError::InvalidCheckpoint(..)));
+
+        // Observers would miss the files counted before the checkpoint
+        let observer: FileObserver = Arc::new(|_, _| {});
+        let options = ScanOptions::new().on_file(Some(observer)).resume(Some(ckpt_file.clone()));
+        let err = scan(dir.path(), options).unwrap_err();
+        assert!(matches!(err, ScanError::InvalidOptions(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_scan_mode_parse() {
+        assert_eq!("size".parse::<ScanMode>().unwrap(), ScanMode::Size);
+        assert!("color".parse::<ScanMode>().is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use globset::{Candidate, GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexSet};
+use std::path::{Path, PathBuf};
+
+/// Prefix that marks an exclude pattern as a regular expression.
+const REGEX_PREFIX: &str = "re:";
+
+/// Compiled `--exclude` patterns, anchored to the scan root.
+///
+/// Patterns follow gitignore conventions:
+/// - `target` matches a file or directory named `target` at any depth
+/// - `/build` or `docs/build` (containing a slash) are relative to the scan root
+/// - `cache/` (trailing slash) matches directories only
+/// - `*` and `?` stay within one path component, `**` crosses components
+///
+/// Patterns starting with `re:` are regular expressions, matched against the
+/// path relative to the scan root with a leading `/` (so `re:^/proc` matches
+/// `<root>/proc` and everything below it).
+///
+/// Everything below an excluded directory is excluded too, so callers that
+/// can't prune a subtree still get the same answer for its children.
+pub struct ExcludeMatcher {
+    root: PathBuf,
+    globs: GlobSet,
+    dir_globs: GlobSet,
+    regexes: RegexSet,
+}
+
+impl ExcludeMatcher {
+    pub fn new(root: &Path, patterns: &[String]) -> Result<Self, PatternError> {
+        let mut globs = GlobSetBuilder::new();
+        let mut dir_globs = GlobSetBuilder::new();
+        let mut regexes = Vec::new();
+
+        for pattern in patterns {
+            if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+                // Compile individually first so errors name the offending pattern
+                Regex::new(regex).map_err(|e| PatternError::new(pattern, &e.to_string()))?;
+                regexes.push(regex.to_string());
+                continue;
+            }
+
+            let (body, dir_only) = match pattern.strip_suffix('/') {
+                Some(body) => (body, true),
+                None => (pattern.as_str(), false),
+            };
+
+            if body.trim_start_matches('/').is_empty() {
+                return Err(PatternError::new(pattern, "empty pattern"));
+            }
+
+            // A slash at the start or in the middle anchors the pattern to the root
+            let glob = if let Some(anchored) = body.strip_prefix('/') {
+                anchored.to_string()
+            } else if body.contains('/') {
+                body.to_string()
+            } else {
+                format!("**/{}", body)
+            };
+
+            let target = if dir_only { &mut dir_globs } else { &mut globs };
+            target.add(compile_glob(pattern, &glob)?);
+            // Children of a match are excluded regardless of their own type
+            globs.add(compile_glob(pattern, &format!("{}/**", glob))?);
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            globs: globs.build().map_err(|e| PatternError::new("", &e.to_string()))?,
+            dir_globs: dir_globs.build().map_err(|e| PatternError::new("", &e.to_string()))?,
+            regexes: RegexSet::new(&regexes).map_err(|e| PatternError::new("", &e.to_string()))?,
+        })
+    }
+
+    /// A matcher that excludes nothing.
+    pub fn empty(root: &Path) -> Self {
+        Self::new(root, &[]).expect("empty pattern list always compiles")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty() && self.dir_globs.is_empty() && self.regexes.is_empty()
+    }
+
+    /// Whether `path` (somewhere below the scan root) should be skipped.
+    /// The scan root itself is never excluded.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        let candidate = Candidate::new(relative);
+        if self.globs.is_match_candidate(&candidate) {
+            return true;
+        }
+        if is_dir && self.dir_globs.is_match_candidate(&candidate) {
+            return true;
+        }
+
+        if !self.regexes.is_empty() {
+            let anchored = format!("/{}", relative.to_string_lossy().replace('\\', "/"));
+            if self.regexes.is_match(&anchored) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+fn compile_glob(pattern: &str, glob: &str) -> Result<globset::Glob, PatternError> {
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+        .map_err(|e| PatternError::new(pattern, &e.kind().to_string()))
+}
+
+/// An exclude pattern that failed to compile.
+#[derive(Debug)]
+pub struct PatternError {
+    pub pattern: String,
+    pub message: String,
+}
+
+impl PatternError {
+    fn new(pattern: &str, message: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.pattern.is_empty() {
+            write!(f, "Invalid exclude pattern: {}", self.message)
+        } else {
+            write!(f, "Invalid exclude pattern '{}': {}", self.pattern, self.message)
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(patterns: &[&str]) -> ExcludeMatcher {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        ExcludeMatcher::new(Path::new("/scan"), &patterns).unwrap()
+    }
+
+    fn excluded(m: &ExcludeMatcher, path: &str, is_dir: bool) -> bool {
+        m.is_excluded(&Path::new("/scan").join(path), is_dir)
+    }
+
+    #[test]
+    fn test_bare_name_matches_component_not_substring() {
+        let m = matcher(&["target"]);
+        assert!(excluded(&m, "target", true));
+        assert!(excluded(&m, "crates/foo/target", true));
+        assert!(excluded(&m, "target/debug/app", false));
+        assert!(!excluded(&m, "targets.txt", false));
+        assert!(!excluded(&m, "my-target", true));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let m = matcher(&["*.log"]);
+        assert!(excluded(&m, "app.log", false));
+        assert!(excluded(&m, "var/log/app.log", false));
+        assert!(!excluded(&m, "app.log.1", false));
+
+        let m = matcher(&["docs/*.pdf"]);
+        assert!(excluded(&m, "docs/manual.pdf", false));
+        // Anchored to the root, and * does not cross directories
+        assert!(!excluded(&m, "src/docs/manual.pdf", false));
+        assert!(!excluded(&m, "docs/old/manual.pdf", false));
+
+        let m = matcher(&["docs/**/*.pdf"]);
+        assert!(excluded(&m, "docs/old/manual.pdf", false));
+    }
+
+    #[test]
+    fn test_leading_slash_anchors_to_root() {
+        let m = matcher(&["/build"]);
+        assert!(excluded(&m, "build", true));
+        assert!(excluded(&m, "build/out.o", false));
+        assert!(!excluded(&m, "src/build", true));
+    }
+
+    #[test]
+    fn test_trailing_slash_matches_directories_only() {
+        let m = matcher(&["cache/"]);
+        assert!(excluded(&m, "cache", true));
+        assert!(excluded(&m, "home/cache/blob", false));
+        assert!(!excluded(&m, "cache", false));
+    }
+
+    #[test]
+    fn test_regex_patterns_anchored_to_root() {
+        let m = matcher(&["re:^/proc"]);
+        assert!(excluded(&m, "proc", true));
+        assert!(excluded(&m, "proc/1/status", false));
+        assert!(!excluded(&m, "home/proc", true));
+
+        let m = matcher(&[r"re:\.(tmp|bak)$"]);
+        assert!(excluded(&m, "a/b/file.tmp", false));
+        assert!(!excluded(&m, "a/b/file.txt", false));
+    }
+
+    #[test]
+    fn test_root_never_excluded() {
+        let m = matcher(&["re:.*"]);
+        assert!(!m.is_excluded(Path::new("/scan"), true));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        let root = Path::new("/scan");
+        assert!(ExcludeMatcher::new(root, &["re:(".to_string()]).is_err());
+        assert!(ExcludeMatcher::new(root, &["a[".to_string()]).is_err());
+        assert!(ExcludeMatcher::new(root, &["/".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Prometheus exporter: rescans a path on an interval and serves the latest
+//! results on `/metrics`.
+
+use crate::engine::{ScanError, ScanOptions};
+use crate::http::{self, Request, Response};
+use crate::output::{Metrics, PrometheusRenderer};
+use parking_lot::RwLock;
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+struct State {
+    /// Rendered results of the last successful scan
+    results: Option<String>,
+    last_success: bool,
+    duration: f64,
+    /// Unix time of the last successful scan
+    timestamp: f64,
+    scans: u64,
+    failures: u64,
+}
+
+struct Shared {
+    path: PathBuf,
+    options: ScanOptions,
+    state: RwLock<State>,
+}
+
+/// Scans `path` on demand or on an interval and keeps the metrics of the
+/// last successful scan.
+#[derive(Clone)]
+pub struct Exporter {
+    shared: Arc<Shared>,
+}
+
+impl Exporter {
+    pub fn new(path: impl AsRef<Path>, options: ScanOptions) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                path: path.as_ref().to_path_buf(),
+                options,
+                state: RwLock::new(State::default()),
+            }),
+        }
+    }
+
+    /// Scan once and update the metrics. A failed scan keeps the previous
+    /// results and sets `spacemap_scan_success` to 0.
+    pub fn scan(&self) -> Result<(), ScanError> {
+        let started = Instant::now();
+        let results = crate::scan(&self.shared.path, self.shared.options.clone());
+        let duration = started.elapsed().as_secs_f64();
+
+        let mut state = self.shared.state.write();
+        state.scans += 1;
+        state.duration = duration;
+        match results {
+            Ok(results) => {
+                state.results = Some(PrometheusRenderer::new().to_text(&results));
+                state.last_success = true;
+                state.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64());
+                Ok(())
+            }
+            Err(e) => {
+                state.last_success = false;
+                state.failures += 1;
+                Err(e)
+            }
+        }
+    }
+
+    /// Rescan every `interval` in a background thread, starting after one
+    /// interval. Failures are reported on stderr.
+    pub fn spawn_rescans(&self, interval: Duration) -> JoinHandle<()> {
+        let exporter = self.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if let Err(e) = exporter.scan() {
+                eprintln!("Error scanning {}: {}", exporter.shared.path.display(), e);
+            }
+        })
+    }
+
+    /// The current metrics, or `None` before the first successful scan.
+    pub fn metrics(&self) -> Option<String> {
+        let state = self.shared.state.read();
+        let mut text = state.results.clone()?;
+
+        let mut metrics = Metrics::default();
+        let path = [("path", self.shared.path.to_string_lossy())];
+        metrics.gauge("spacemap_scan_success", "Whether the last scan succeeded");
+        metrics.sample(&path, u8::from(state.last_success));
+        metrics.gauge("spacemap_scan_duration_seconds", "Duration of the last scan");
+        metrics.sample(&path, state.duration);
+        metrics.gauge("spacemap_last_success_timestamp_seconds", "Unix time of the last successful scan");
+        metrics.sample(&path, state.timestamp);
+        metrics.counter("spacemap_scans_total", "Scans run since the exporter started");
+        metrics.sample(&path, state.scans);
+        metrics.counter("spacemap_scan_failures_total", "Failed scans since the exporter started");
+        metrics.sample(&path, state.failures);
+        text.push_str(&metrics.into_text());
+        Some(text)
+    }
+
+    /// Answer HTTP requests on `listener` until it fails.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let exporter = self.clone();
+        http::serve(listener, move |request| exporter.respond(request))
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/metrics") => match self.metrics() {
+                Some(metrics) => Response {
+                    status: 200,
+                    content_type: CONTENT_TYPE,
+                    body: metrics.into_bytes(),
+                },
+                None => Response::text(503, "First scan has not finished yet\n"),
+            },
+            ("GET", "/") => Response {
+                status: 200,
+                content_type: "text/html",
+                body: b"<html><body><h1>spacemap exporter</h1><p><a href=\"/metrics\">Metrics</a></p></body></html>\n".to_vec(),
+            },
+            ("GET", _) => Response::text(404, "Not found\n"),
+            _ => Response::text(405, "Only GET is supported\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use tempfile::TempDir;
+
+    fn get(addr: std::net::SocketAddr, target: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_metrics() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.rs"), vec![0u8; 300]).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), vec![0u8; 20]).unwrap();
+
+        let exporter = Exporter::new(dir.path(), ScanOptions::new().collect_tops(true));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = exporter.clone();
+        std::thread::spawn(move || server.serve(listener));
+
+        assert!(get(addr, "/metrics").starts_with("HTTP/1.1 503"));
+
+        exporter.scan().unwrap();
+        let response = get(addr, "/metrics");
+        let path = dir.path().to_string_lossy();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(&format!("spacemap_bytes{{path=\"{}\"}} 320\n", path)));
+        assert!(response.contains(&format!("spacemap_category_files{{path=\"{}\",category=\"Code\"}} 1\n", path)));
+        assert!(response.contains(&format!("spacemap_scan_success{{path=\"{}\"}} 1\n", path)));
+        assert!(response.contains("# TYPE spacemap_scans_total counter\n"));
+        assert!(response.contains(&format!("spacemap_scans_total{{path=\"{}\"}} 1\n", path)));
+        assert!(response.contains(&format!("spacemap_scan_failures_total{{path=\"{}\"}} 0\n", path)));
+
+        assert!(get(addr, "/other").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,117 @@
+use parking_lot::Mutex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// Number of independently locked shards. Hard-linked files are rare in most
+/// trees, so a small fixed number keeps contention low without per-thread setup.
+const SHARD_COUNT: usize = 64;
+
+/// Tracks `(device, inode)` pairs of files with more than one link so each
+/// inode is counted only once.
+///
+/// Only files with `nlink > 1` ever reach the tracker, and each lookup locks a
+/// single shard, so parallel scanners don't serialize on one global lock.
+pub struct HardLinkTracker {
+    shards: Vec<Mutex<HashSet<(u64, u64)>>>,
+}
+
+impl HardLinkTracker {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT).map(|_| Mutex::new(HashSet::new())).collect(),
+        }
+    }
+
+    /// Returns true the first time an inode is seen, false for every later link.
+    ///
+    /// Files that are not hard-linked are always reported as first seen.
+    pub fn first_seen(&self, metadata: &std::fs::Metadata) -> bool {
+        self.first_seen_key(inode_key(metadata))
+    }
+
+    /// Like [`first_seen`](Self::first_seen), for a key saved by [`inode_key`].
+    pub fn first_seen_key(&self, key: Option<(u64, u64)>) -> bool {
+        match key {
+            Some(key) => self.shard(key).lock().insert(key),
+            None => true,
+        }
+    }
+
+    /// Every key recorded so far, for checkpoints.
+    pub fn keys(&self) -> Vec<(u64, u64)> {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.lock().iter().copied().collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// A tracker that has already seen `keys`.
+    pub fn from_keys(keys: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        let tracker = Self::new();
+        for key in keys {
+            tracker.shard(key).lock().insert(key);
+        }
+        tracker
+    }
+
+    fn shard(&self, key: (u64, u64)) -> &Mutex<HashSet<(u64, u64)>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARD_COUNT]
+    }
+}
+
+impl Default for HardLinkTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `(dev, ino)` for files with more than one hard link.
+#[cfg(unix)]
+pub fn inode_key(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+pub fn inode_key(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_hard_links_counted_once() {
+        let dir = TempDir::new().unwrap();
+        let original = dir.path().join("original");
+        let link = dir.path().join("link");
+        fs::write(&original, b"data").unwrap();
+        fs::hard_link(&original, &link).unwrap();
+
+        let tracker = HardLinkTracker::new();
+        assert!(tracker.first_seen(&fs::metadata(&original).unwrap()));
+        assert!(!tracker.first_seen(&fs::metadata(&link).unwrap()));
+    }
+
+    #[test]
+    fn test_single_link_files_always_counted() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, b"data").unwrap();
+
+        let tracker = HardLinkTracker::new();
+        let metadata = fs::metadata(&file).unwrap();
+        assert!(tracker.first_seen(&metadata));
+        assert!(tracker.first_seen(&metadata));
+    }
+}
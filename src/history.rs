@@ -0,0 +1,522 @@
+//! Scan history: a summary of every scan, kept per scanned path, and the
+//! trends `spacemap history` draws from it.
+//!
+//! Each path gets a JSON-lines file under `history/` in the cache directory,
+//! so a scan only appends one line and a damaged line loses only that scan.
+//! Entries record a fingerprint of the scan options, and only scans with the
+//! same options as the latest one make up the series that is shown.
+
+use crate::types::{DirEntry, DiskUsage, ScanResults};
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use colored::*;
+use humansize::{format_size, BINARY};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Categories and directories shown by `print_history`.
+const SHOWN_ROWS: usize = 10;
+
+/// Entries kept per path; older ones are dropped once there are a tenth more.
+const MAX_ENTRIES: usize = 1000;
+
+/// Summary of one scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the scan finished, RFC 3339
+    pub timestamp: String,
+    pub path: String,
+    pub mode: String,
+    /// [`ScanOptions::history_key`](crate::ScanOptions::history_key) of the scan
+    #[serde(default)]
+    pub options: String,
+    pub total_bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub categories: Vec<CategorySize>,
+    pub top_dirs: Vec<DirEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_usage: Option<DiskUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategorySize {
+    pub key: String,
+    pub label: String,
+    pub bytes: u64,
+}
+
+impl HistoryEntry {
+    pub fn from_results(results: &ScanResults, options: String) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            path: results.scanned_path.clone(),
+            mode: results.mode.clone(),
+            options,
+            total_bytes: results.totals.total_bytes,
+            disk_bytes: results.totals.disk_bytes,
+            file_count: results.totals.file_count,
+            dir_count: results.totals.dir_count,
+            categories: results
+                .buckets
+                .iter()
+                .map(|b| CategorySize {
+                    key: b.key.clone(),
+                    label: b.label.clone(),
+                    bytes: b.bytes,
+                })
+                .collect(),
+            top_dirs: results.top_dirs.clone(),
+            disk_usage: results.disk_usage.clone(),
+        }
+    }
+
+    fn time(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.timestamp).ok()
+    }
+}
+
+/// The history of one scanned path.
+pub struct History {
+    file: PathBuf,
+}
+
+impl History {
+    /// The history of `path` in `cache_dir`. Paths are resolved first, so
+    /// `.` and its absolute path share one history.
+    pub fn open(cache_dir: &Path, path: &Path) -> Self {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let key = blake3::hash(path.as_os_str().as_encoded_bytes()).to_hex();
+        Self {
+            file: cache_dir.join("history").join(format!("{}.jsonl", key)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> io::Result<()> {
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // One write per entry, so concurrent scans don't interleave lines
+        OpenOptions::new().create(true).append(true).open(&self.file)?.write_all(line.as_bytes())?;
+
+        let lines = self.lines()?;
+        if lines.len() > MAX_ENTRIES + MAX_ENTRIES / 10 {
+            let mut kept = lines[lines.len() - MAX_ENTRIES..].join("\n");
+            kept.push('\n');
+            // Replace the file in one step so readers never see a partial history
+            let tmp = self.file.with_extension("jsonl.tmp");
+            fs::write(&tmp, kept)?;
+            fs::rename(&tmp, &self.file)?;
+        }
+        Ok(())
+    }
+
+    /// The last `n` entries scanned with the same options as the latest one,
+    /// oldest first. Lines that can't be read are skipped; a missing history
+    /// is empty.
+    pub fn load(&self, n: usize) -> io::Result<Vec<HistoryEntry>> {
+        let mut entries: Vec<HistoryEntry> = self
+            .lines()?
+            .iter()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        if let Some(options) = entries.last().map(|e| e.options.clone()) {
+            entries.retain(|e| e.options == options);
+        }
+        let skip = entries.len().saturating_sub(n);
+        entries.drain(..skip);
+        Ok(entries)
+    }
+
+    fn lines(&self) -> io::Result<Vec<String>> {
+        match fs::File::open(&self.file) {
+            Ok(file) => BufReader::new(file).lines().collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Growth of the total size over a history, and when it fills the disk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trend {
+    /// Least-squares slope of the total size over time
+    pub bytes_per_day: f64,
+    /// Slope of the space allocated on disk, which is what fills the
+    /// filesystem
+    pub disk_bytes_per_day: f64,
+    /// When the filesystem of the latest scan runs out of space at the disk
+    /// rate; `None` if the tree isn't growing or the free space is unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days_until_full: Option<f64>,
+}
+
+impl Trend {
+    /// `None` with fewer than two scans at different times.
+    pub fn of(entries: &[HistoryEntry]) -> Option<Self> {
+        let bytes_per_day = slope(entries, |e| e.total_bytes)?;
+        let disk_bytes_per_day = slope(entries, |e| e.disk_bytes)?;
+
+        let last = entries.last()?;
+        let days_until_full = match last.disk_usage {
+            Some(ref usage) if disk_bytes_per_day > 0.0 => Some(usage.available_space as f64 / disk_bytes_per_day),
+            _ => None,
+        };
+        let full_at = days_until_full.and_then(|days| {
+            let time = last.time()? + Duration::try_seconds((days * 86_400.0).min(1e11) as i64)?;
+            Some(time.format("%Y-%m-%d").to_string())
+        });
+        Some(Self {
+            bytes_per_day,
+            disk_bytes_per_day,
+            full_at,
+            days_until_full,
+        })
+    }
+}
+
+/// Least-squares slope of `value` per day over `entries`; `None` with fewer
+/// than two scans at different times.
+fn slope(entries: &[HistoryEntry], value: impl Fn(&HistoryEntry) -> u64) -> Option<f64> {
+    let first = entries.first()?.time()?;
+    let points: Vec<(f64, f64)> = entries
+        .iter()
+        .filter_map(|e| {
+            let days = (e.time()? - first).num_seconds() as f64 / 86_400.0;
+            Some((days, value(e) as f64))
+        })
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let var_x: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if var_x == 0.0 {
+        return None;
+    }
+    let cov: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    Some(cov / var_x)
+}
+
+/// One bar per value, scaled between the smallest and largest; `None`
+/// (not recorded in that scan) is a space.
+pub fn sparkline(values: &[Option<u64>]) -> String {
+    let known = values.iter().flatten();
+    let (Some(&min), Some(&max)) = (known.clone().min(), known.max()) else {
+        return " ".repeat(values.len());
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            Some(_) if max == min => SPARK_LEVELS[SPARK_LEVELS.len() / 2],
+            Some(value) => {
+                let level = (value - min) as f64 / (max - min) as f64 * (SPARK_LEVELS.len() - 1) as f64;
+                SPARK_LEVELS[level.round() as usize]
+            }
+            None => ' ',
+        })
+        .collect()
+}
+
+/// One row of the history view: a label and its size in every scan.
+struct Series {
+    label: String,
+    values: Vec<Option<u64>>,
+}
+
+impl Series {
+    fn latest(&self) -> u64 {
+        self.values.last().copied().flatten().unwrap_or(0)
+    }
+
+    /// Change from the first scan that has a value.
+    fn delta(&self) -> i64 {
+        let first = self.values.iter().flatten().next().copied().unwrap_or(0);
+        self.latest() as i64 - first as i64
+    }
+}
+
+/// Categories of the latest scan's mode, largest now first. Scans in other
+/// modes have no values.
+fn category_series(entries: &[HistoryEntry]) -> Vec<Series> {
+    let Some(last) = entries.last() else {
+        return Vec::new();
+    };
+    let mut labels: Vec<(String, String)> = Vec::new();
+    for entry in entries.iter().filter(|e| e.mode == last.mode) {
+        for category in &entry.categories {
+            if !labels.iter().any(|(key, _)| key == &category.key) {
+                labels.push((category.key.clone(), category.label.clone()));
+            }
+        }
+    }
+
+    let mut series: Vec<Series> = labels
+        .into_iter()
+        .map(|(key, label)| Series {
+            label,
+            values: entries
+                .iter()
+                .map(|e| {
+                    (e.mode == last.mode)
+                        .then(|| e.categories.iter().find(|c| c.key == key).map_or(0, |c| c.bytes))
+                })
+                .collect(),
+        })
+        .collect();
+    series.sort_by_key(|s| std::cmp::Reverse(s.latest()));
+    series
+}
+
+/// The latest scan's top directories. Earlier scans only have values for
+/// the directories in their own top lists.
+fn directory_series(entries: &[HistoryEntry]) -> Vec<Series> {
+    let Some(last) = entries.last() else {
+        return Vec::new();
+    };
+    let sizes: Vec<HashMap<&str, u64>> = entries
+        .iter()
+        .map(|e| e.top_dirs.iter().map(|d| (d.path.as_str(), d.bytes)).collect())
+        .collect();
+    last.top_dirs
+        .iter()
+        .map(|dir| Series {
+            label: dir.path.clone(),
+            values: sizes.iter().map(|s| s.get(dir.path.as_str()).copied()).collect(),
+        })
+        .collect()
+}
+
+pub fn print_history(path: &Path, entries: &[HistoryEntry], use_color: bool) {
+    println!();
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+        println!("  No scans of {} recorded yet", path.display());
+        println!();
+        return;
+    };
+
+    let date = |e: &HistoryEntry| e.time().map_or_else(|| e.timestamp.clone(), |t| t.format("%Y-%m-%d %H:%M").to_string());
+    println!("  {}", style_text("Scan History", "cyan", true, use_color));
+    println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
+    println!("  Path:  {}", style_text(&last.path, "white", false, use_color));
+    println!("  Scans: {} ({} to {})", entries.len(), date(first), date(last));
+    println!();
+
+    let total = Series {
+        label: "Total".to_string(),
+        values: entries.iter().map(|e| Some(e.total_bytes)).collect(),
+    };
+    print_series("TOTAL", &[total], 20, use_color);
+    print_series("CATEGORIES", &category_series(entries), 20, use_color);
+    let dirs = directory_series(entries);
+    let width = dirs.iter().map(|s| s.label.chars().count()).max().unwrap_or(0).clamp(20, 48);
+    print_series("TOP DIRECTORIES", &dirs, width, use_color);
+
+    println!("  {}", style_text("FORECAST", "yellow", true, use_color));
+    println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
+    match Trend::of(entries) {
+        None => println!("  Needs at least two scans at different times"),
+        Some(trend) => {
+            let rate = trend.bytes_per_day.abs() as u64;
+            let shown = format_size(rate, BINARY);
+            if rate == 0 {
+                println!("  Flat, no change per day");
+            } else if trend.bytes_per_day > 0.0 {
+                println!("  Growing {} per day", style_text(&shown, "red", true, use_color));
+            } else {
+                println!("  Shrinking {} per day", style_text(&shown, "green", true, use_color));
+            }
+            match (trend.full_at, trend.days_until_full, &last.disk_usage) {
+                (Some(date), Some(days), Some(usage)) => println!(
+                    "  At this rate the filesystem ({} free) is full in {:.0} days, around {}",
+                    format_size(usage.available_space, BINARY),
+                    days,
+                    style_text(&date, "yellow", true, use_color)
+                ),
+                _ if trend.disk_bytes_per_day > 0.0 => println!("  Free space of the filesystem is unknown"),
+                _ => {}
+            }
+        }
+    }
+    println!();
+}
+
+fn print_series(title: &str, series: &[Series], width: usize, use_color: bool) {
+    if series.is_empty() {
+        return;
+    }
+    println!("  {}", style_text(title, "yellow", true, use_color));
+    println!("  {}", style_text(&"─".repeat(56), "bright_black", false, use_color));
+    for row in series.iter().take(SHOWN_ROWS) {
+        let delta = row.delta();
+        let (change, color) = match delta {
+            0 => (String::new(), "bright_black"),
+            d if d > 0 => (format!("+{}", format_size(d as u64, BINARY)), "red"),
+            d => (format!("-{}", format_size(d.unsigned_abs(), BINARY)), "green"),
+        };
+        println!(
+            "  {} {} {:>10} {}",
+            style_text(&format!("{:<width$}", truncate(&row.label, width), width = width), "white", false, use_color),
+            style_text(&sparkline(&row.values), "cyan", false, use_color),
+            format_size(row.latest(), BINARY),
+            style_text(&change, color, false, use_color)
+        );
+    }
+    println!();
+}
+
+/// Keep the end of long paths, which tells them apart.
+fn truncate(label: &str, width: usize) -> String {
+    let chars = label.chars().count();
+    if chars <= width {
+        return label.to_string();
+    }
+    let tail: String = label.chars().skip(chars - width + 1).collect();
+    format!("…{}", tail)
+}
+
+fn style_text(text: &str, color: &str, bold: bool, use_color: bool) -> String {
+    if !use_color {
+        return text.to_string();
+    }
+
+    let colored = match color {
+        "red" => text.red(),
+        "green" => text.green(),
+        "yellow" => text.yellow(),
+        "cyan" => text.cyan(),
+        "white" => text.white(),
+        "bright_black" => text.bright_black(),
+        _ => text.normal(),
+    };
+
+    if bold {
+        colored.bold().to_string()
+    } else {
+        colored.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(day: u32, total_bytes: u64, available_space: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: format!("2026-10-{:02}T12:00:00+00:00", day),
+            path: "/data".to_string(),
+            mode: "type".to_string(),
+            options: "default".to_string(),
+            total_bytes,
+            disk_bytes: total_bytes,
+            file_count: 1,
+            dir_count: 1,
+            categories: vec![CategorySize {
+                key: "Videos".to_string(),
+                label: "Videos".to_string(),
+                bytes: total_bytes,
+            }],
+            top_dirs: vec![DirEntry {
+                path: "/data/videos".to_string(),
+                bytes: total_bytes,
+                disk_bytes: total_bytes,
+            }],
+            disk_usage: Some(DiskUsage {
+                total_space: 10_000,
+                available_space,
+                used_space: 10_000 - available_space,
+                used_percent: 0.0,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_last() {
+        let dir = TempDir::new().unwrap();
+        let history = History::open(dir.path(), Path::new("/data"));
+        assert!(history.load(10).unwrap().is_empty());
+
+        for day in 1..=5 {
+            history.append(&entry(day, day as u64 * 100, 5000)).unwrap();
+        }
+        // A damaged line only loses that scan
+        OpenOptions::new().append(true).open(history.path()).unwrap().write_all(b"{not json\n").unwrap();
+
+        let entries = history.load(3).unwrap();
+        let totals: Vec<u64> = entries.iter().map(|e| e.total_bytes).collect();
+        assert_eq!(totals, [300, 400, 500]);
+        assert!(History::open(dir.path(), Path::new("/other")).load(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_series_follows_latest_options_and_is_capped() {
+        let dir = TempDir::new().unwrap();
+        let history = History::open(dir.path(), Path::new("/data"));
+        for day in 1..=3 {
+            history.append(&entry(day, day as u64 * 100, 5000)).unwrap();
+        }
+        // A shallow scan doesn't belong to the same series
+        let shallow = HistoryEntry {
+            options: "max-depth".to_string(),
+            ..entry(4, 10, 5000)
+        };
+        history.append(&shallow).unwrap();
+        assert_eq!(history.load(10).unwrap().len(), 1);
+        history.append(&entry(5, 500, 5000)).unwrap();
+        let totals: Vec<u64> = history.load(10).unwrap().iter().map(|e| e.total_bytes).collect();
+        assert_eq!(totals, [100, 200, 300, 500]);
+
+        for _ in 0..MAX_ENTRIES + MAX_ENTRIES / 10 {
+            history.append(&entry(6, 600, 5000)).unwrap();
+        }
+        // Trimmed back to MAX_ENTRIES once, with the oldest scans dropped
+        let entries = history.load(usize::MAX).unwrap();
+        assert_eq!(history.lines().unwrap().len(), MAX_ENTRIES + 4);
+        assert!(entries.iter().all(|e| e.total_bytes == 600));
+    }
+
+    #[test]
+    fn test_trend_projects_full_disk() {
+        let entries: Vec<_> = (1..=4).map(|day| entry(day, day as u64 * 100, 5000 - day as u64 * 100)).collect();
+        let trend = Trend::of(&entries).unwrap();
+        assert!((trend.bytes_per_day - 100.0).abs() < 1e-6);
+        // 4600 bytes free at 100 bytes a day
+        assert_eq!(trend.days_until_full.map(f64::round), Some(46.0));
+        assert_eq!(trend.full_at.as_deref(), Some("2026-11-19"));
+
+        assert!(Trend::of(&entries[..1]).is_none());
+        let shrinking: Vec<_> = (1..=3).map(|day| entry(day, 1000 - day as u64 * 100, 5000)).collect();
+        assert_eq!(Trend::of(&shrinking).unwrap().full_at, None);
+
+        // Sparse files grow in size but not on disk, so the disk doesn't fill
+        let sparse: Vec<_> = entries.iter().cloned().map(|e| HistoryEntry { disk_bytes: 100, ..e }).collect();
+        let trend = Trend::of(&sparse).unwrap();
+        assert!((trend.bytes_per_day - 100.0).abs() < 1e-6);
+        assert_eq!(trend.disk_bytes_per_day, 0.0);
+        assert_eq!(trend.full_at, None);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[Some(0), Some(50), Some(100)]), "▁▅█");
+        assert_eq!(sparkline(&[None, Some(7), Some(7)]), " ▅▅");
+        assert_eq!(sparkline(&[None, None]), "  ");
+    }
+}
@@ -0,0 +1,403 @@
+use crate::cache::{CachedDir, CachedFile, DirCache, DirStamp, DirSummary};
+use crate::collector::SinglePassCollector;
+use crate::dir_reader::{count_file, dir_key, file_record, warning, DirReader};
+use crate::exclude::ExcludeMatcher;
+use crate::hardlinks::HardLinkTracker;
+use crate::progress::ScanProgress;
+use crate::scanner::{device_id, ScanStats};
+use crate::types::{FileMetadata, SkippedMount};
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Sequential scanner backed by a [`DirCache`].
+///
+/// Every directory is stat'ed, but only directories whose [`DirStamp`]
+/// changed since the last scan are listed and have their files stat'ed
+/// again. Unchanged directories add the [`DirSummary`] cached for them, or
+/// replay their cached files when the caller needs to see every file.
+pub struct IncrementalScanner {
+    reader: DirReader,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    need_modified: bool,
+    one_file_system: bool,
+    summaries: bool,
+}
+
+/// Where the files of one directory are counted.
+struct Sink<'a, F> {
+    collector: &'a mut SinglePassCollector,
+    /// Collects one directory at a time for its [`DirSummary`]
+    scratch: SinglePassCollector,
+    callback: F,
+    hard_links: HardLinkTracker,
+}
+
+impl IncrementalScanner {
+    pub fn new(
+        follow_symlinks: bool,
+        max_depth: Option<usize>,
+        exclude: Arc<ExcludeMatcher>,
+        need_modified: bool,
+        one_file_system: bool,
+    ) -> Self {
+        Self {
+            reader: DirReader::new(follow_symlinks, exclude),
+            follow_symlinks,
+            max_depth,
+            need_modified,
+            one_file_system,
+            summaries: true,
+        }
+    }
+
+    /// Whether unchanged directories may add their cached summary instead of
+    /// passing each file to the callback (default: true). Summaries are only
+    /// valid while the collector categorizes and ranks files the same way and
+    /// nothing else needs the individual files.
+    pub fn with_summaries(mut self, summaries: bool) -> Self {
+        self.summaries = summaries;
+        self
+    }
+
+    /// Scan `path` into `collector`. `scratch` is an empty collector built
+    /// with the same options, used to summarize directories for the cache.
+    /// `callback` sees every file counted one by one, with its category.
+    pub fn scan<F>(
+        &self,
+        path: &Path,
+        cache: &mut DirCache,
+        collector: &mut SinglePassCollector,
+        scratch: SinglePassCollector,
+        callback: F,
+        progress: &ScanProgress,
+    ) -> ScanStats
+    where
+        F: FnMut(&FileMetadata, &str),
+    {
+        let mut stats = ScanStats::default();
+        let mut sink = Sink {
+            collector,
+            scratch,
+            callback,
+            hard_links: HardLinkTracker::new(),
+        };
+
+        let root_metadata = match self.reader.metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                stats.warnings.push(warning(path, &e));
+                progress.finish();
+                return stats;
+            }
+        };
+
+        // A single file: nothing to cache
+        if !root_metadata.is_dir() {
+            if root_metadata.is_file() {
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_os_string();
+                let file = file_record(name, &root_metadata);
+                let parent = path.parent().unwrap_or(Path::new(""));
+                let device = device_id(&root_metadata);
+                self.count(parent, device, &file, &mut sink, &mut stats);
+            }
+            progress.finish();
+            return stats;
+        }
+
+        let root_device = device_id(&root_metadata);
+        // Symlinked directories can form cycles; only tracked when following links
+        let mut visited = HashSet::new();
+        let mut stack = vec![(path.to_path_buf(), root_metadata, 0usize)];
+
+        while let Some((dir, metadata, depth)) = stack.pop() {
+            if self.follow_symlinks {
+                if let Some(key) = dir_key(&metadata) {
+                    if !visited.insert(key) {
+                        continue;
+                    }
+                }
+            }
+
+            stats.dir_count += 1;
+            stats.devices.add_dir(&metadata, &dir);
+            if self.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+
+            let files_before = stats.file_count;
+            let device = device_id(&metadata);
+            let stamp = DirStamp::new(&metadata);
+            let (listing, complete) = match cache.lookup(&dir, &stamp) {
+                Some(cached) => {
+                    self.replay(&dir, device, &cached, &mut sink, &mut stats);
+                    (cached, true)
+                }
+                None => match self.read(&dir, device, stamp, &mut sink, &mut stats) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        stats.warnings.push(warning(&dir, &e));
+                        continue;
+                    }
+                },
+            };
+
+            if stats.file_count / 1000 != files_before / 1000 {
+                progress.update(stats.file_count, stats.total_bytes, dir.to_str().unwrap_or(""));
+            }
+
+            // Reversed so directories are visited in listing order
+            for name in listing.subdirs.iter().rev() {
+                let subdir = dir.join(name);
+                let sub_metadata = match self.reader.metadata(&subdir) {
+                    Ok(m) if m.is_dir() => m,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        stats.warnings.push(warning(&subdir, &e));
+                        continue;
+                    }
+                };
+
+                if self.one_file_system {
+                    if let (Some(root), Some(sub)) = (root_device, device_id(&sub_metadata)) {
+                        if root != sub {
+                            stats.skipped_mounts.push(SkippedMount {
+                                path: subdir.display().to_string(),
+                                device: sub,
+                            });
+                            continue;
+                        }
+                    }
+                }
+
+                stack.push((subdir, sub_metadata, depth + 1));
+            }
+
+            // Listings with unreadable entries are retried next time instead of hiding the error
+            if complete {
+                cache.record(dir, listing);
+            }
+        }
+
+        progress.finish();
+        stats
+    }
+
+    /// List `dir` and count its files, along with whether every entry could
+    /// be read.
+    fn read<F>(
+        &self,
+        dir: &Path,
+        device: Option<u64>,
+        stamp: DirStamp,
+        sink: &mut Sink<'_, F>,
+        stats: &mut ScanStats,
+    ) -> io::Result<(CachedDir, bool)>
+    where
+        F: FnMut(&FileMetadata, &str),
+    {
+        let entries = self.reader.read_dir(dir)?;
+        let warnings_before = stats.warnings.len();
+        let (files, subdirs) = self.reader.read(entries, &mut stats.warnings);
+
+        let mut summary = DirSummary::default();
+        for file in &files {
+            if file.inode_key.is_some() {
+                self.count(dir, device, file, sink, stats);
+                continue;
+            }
+            if let Some(meta) = count_file(dir, device, file, self.need_modified, &sink.hard_links, stats) {
+                let category = sink.scratch.process_file(&meta);
+                (sink.callback)(&meta, &category);
+                summary.bytes += file.size;
+                summary.disk_bytes += file.disk_size;
+                summary.file_count += 1;
+            }
+        }
+        summary.collector = sink.scratch.take_state();
+        sink.collector.merge_state(&summary.collector);
+
+        let listing = CachedDir {
+            stamp,
+            files,
+            subdirs,
+            summary,
+        };
+        Ok((listing, stats.warnings.len() == warnings_before))
+    }
+
+    /// Count the files of a directory that hasn't changed since `listing` was cached.
+    fn replay<F>(&self, dir: &Path, device: Option<u64>, listing: &CachedDir, sink: &mut Sink<'_, F>, stats: &mut ScanStats)
+    where
+        F: FnMut(&FileMetadata, &str),
+    {
+        if !self.summaries {
+            for file in &listing.files {
+                self.count(dir, device, file, sink, stats);
+            }
+            return;
+        }
+
+        let summary = &listing.summary;
+        stats.total_bytes += summary.bytes;
+        stats.disk_bytes += summary.disk_bytes;
+        stats.file_count += summary.file_count;
+        stats.devices.add_files_on(device, dir, summary.bytes, summary.disk_bytes, summary.file_count);
+        sink.collector.merge_state(&summary.collector);
+
+        // Hard links still go through the tracker
+        for file in listing.files.iter().filter(|file| file.inode_key.is_some()) {
+            self.count(dir, device, file, sink, stats);
+        }
+    }
+
+    /// Count a single file straight into the collector.
+    fn count<F>(&self, dir: &Path, device: Option<u64>, file: &CachedFile, sink: &mut Sink<'_, F>, stats: &mut ScanStats)
+    where
+        F: FnMut(&FileMetadata, &str),
+    {
+        if let Some(meta) = count_file(dir, device, file, self.need_modified, &sink.hard_links, stats) {
+            let category = sink.collector.process_file(&meta);
+            (sink.callback)(&meta, &category);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categorize::TypeCategorizer;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    struct Scan {
+        stats: ScanStats,
+        cache: crate::types::CacheStats,
+        /// Files passed to the callback one by one
+        seen: Vec<PathBuf>,
+        /// `stat`s and `read_dir`s made
+        calls: u64,
+        bytes_by_category: u64,
+    }
+
+    fn scan_with_cache(root: &Path, cache_dir: &Path, summaries: bool) -> Scan {
+        let scanner = IncrementalScanner::new(false, None, Arc::new(ExcludeMatcher::empty(root)), false, false)
+            .with_summaries(summaries);
+        let new_collector = || SinglePassCollector::new(Box::new(TypeCategorizer::new()), 10, true);
+        let mut collector = new_collector();
+        let mut cache = DirCache::open(cache_dir, root, "").unwrap();
+        let mut seen = Vec::new();
+        let stats = scanner.scan(
+            root,
+            &mut cache,
+            &mut collector,
+            new_collector(),
+            |meta, _| seen.push(meta.path.clone()),
+            &ScanProgress::new(false),
+        );
+        let cache_stats = cache.stats();
+        cache.save().unwrap();
+        seen.sort();
+        let results = collector.finalize(stats.total_bytes);
+        Scan {
+            calls: scanner.reader.calls(),
+            stats,
+            cache: cache_stats,
+            seen,
+            bytes_by_category: results.buckets.iter().map(|b| b.bytes).sum(),
+        }
+    }
+
+    fn create_tree() -> TempDir {
+        let tree = TempDir::new().unwrap();
+        fs::create_dir_all(tree.path().join("a/b")).unwrap();
+        fs::write(tree.path().join("a/b/file"), vec![0u8; 100]).unwrap();
+        fs::write(tree.path().join("a/b/notes.txt"), vec![0u8; 20]).unwrap();
+        fs::write(tree.path().join("top"), vec![0u8; 50]).unwrap();
+        tree
+    }
+
+    #[test]
+    fn test_unchanged_tree_is_served_from_cache() {
+        let tree = create_tree();
+        let cache_dir = TempDir::new().unwrap();
+
+        let first = scan_with_cache(tree.path(), cache_dir.path(), true);
+        assert_eq!(first.cache.hits, 0);
+        assert_eq!(first.cache.misses, 3);
+        assert_eq!(first.seen.len(), 3);
+
+        let second = scan_with_cache(tree.path(), cache_dir.path(), true);
+        assert_eq!(second.cache.hits, 3);
+        assert_eq!(second.cache.misses, 0);
+        assert_eq!(second.stats.total_bytes, first.stats.total_bytes);
+        assert_eq!(second.stats.file_count, 3);
+        assert_eq!(second.stats.dir_count, first.stats.dir_count);
+        assert_eq!(second.bytes_by_category, 170);
+        // Summaries stand in for the files
+        assert!(second.seen.is_empty());
+
+        // Without summaries, cached files are replayed one by one
+        let replayed = scan_with_cache(tree.path(), cache_dir.path(), false);
+        assert_eq!(replayed.cache.hits, 3);
+        assert_eq!(replayed.seen, first.seen);
+        assert_eq!(replayed.bytes_by_category, 170);
+    }
+
+    #[test]
+    fn test_warm_scan_only_stats_directories() {
+        let tree = create_tree();
+        let cache_dir = TempDir::new().unwrap();
+
+        // Cold: a stat per directory and file, and a read_dir per directory
+        let cold = scan_with_cache(tree.path(), cache_dir.path(), true);
+        assert_eq!(cold.calls, 3 + 3 + 3);
+
+        // Warm: one stat per directory, no listings and no file stats
+        let warm = scan_with_cache(tree.path(), cache_dir.path(), true);
+        assert_eq!(warm.calls, 3);
+        let replayed = scan_with_cache(tree.path(), cache_dir.path(), false);
+        assert_eq!(replayed.calls, 3);
+    }
+
+    #[test]
+    fn test_only_changed_directories_are_reread() {
+        let tree = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        fs::create_dir_all(tree.path().join("a/b")).unwrap();
+        fs::create_dir_all(tree.path().join("c")).unwrap();
+        fs::write(tree.path().join("a/b/file"), vec![0u8; 100]).unwrap();
+        scan_with_cache(tree.path(), cache_dir.path(), true);
+
+        // A change deep in the tree is picked up without rereading its ancestors
+        fs::write(tree.path().join("a/b/new"), vec![0u8; 25]).unwrap();
+        let scan = scan_with_cache(tree.path(), cache_dir.path(), true);
+
+        assert_eq!(scan.stats.total_bytes, 125);
+        assert_eq!(scan.stats.file_count, 2);
+        assert_eq!(scan.cache.misses, 1);
+        assert_eq!(scan.cache.hits, 3);
+        assert_eq!(scan.seen, [tree.path().join("a/b/file"), tree.path().join("a/b/new")]);
+        assert_eq!(scan.bytes_by_category, 125);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hard_links_in_cached_directories_count_once() {
+        let tree = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        fs::create_dir_all(tree.path().join("a")).unwrap();
+        fs::write(tree.path().join("a/data"), vec![0u8; 100]).unwrap();
+        fs::hard_link(tree.path().join("a/data"), tree.path().join("link")).unwrap();
+
+        let cold = scan_with_cache(tree.path(), cache_dir.path(), true);
+        let warm = scan_with_cache(tree.path(), cache_dir.path(), true);
+        assert_eq!(warm.cache.misses, 0);
+        assert_eq!((cold.stats.total_bytes, cold.stats.hardlink_bytes), (100, 100));
+        assert_eq!((warm.stats.total_bytes, warm.stats.hardlink_bytes), (100, 100));
+        assert_eq!(warm.bytes_by_category, 100);
+    }
+}
@@ -0,0 +1,66 @@
+//! spacemap - disk space analysis as a library.
+//!
+//! The `spacemap` binary is a thin wrapper around [`scan`]. Other tools can
+//! call it directly instead of shelling out and parsing JSON:
+//!
+//! ```no_run
+//! use spacemap::{ScanMode, ScanOptions};
+//!
+//! let options = ScanOptions::new().mode(ScanMode::Size).parallel(true).top(20);
+//! let results = spacemap::scan("/data", options).unwrap();
+//! println!("{} bytes in {} files", results.totals.total_bytes, results.totals.file_count);
+//! ```
+//!
+//! The lower-level building blocks ([`Scanner`], [`ParallelScanner`],
+//! [`SinglePassCollector`] and the [`Categorizer`] implementations) are
+//! exported as well for callers that need to drive a scan themselves.
+
+pub mod bounded_heap;
+pub mod cache;
+pub mod categorize;
+pub mod checkpoint;
+pub mod clean;
+pub mod cleanup;
+pub mod collector;
+pub mod compare;
+pub mod config;
+pub mod dir_reader;
+pub mod duplicates;
+pub mod engine;
+pub mod exclude;
+pub mod exporter;
+pub mod hardlinks;
+pub mod history;
+pub mod http;
+pub mod incremental_scanner;
+pub mod mounts;
+pub mod output;
+pub mod parallel_scanner;
+pub mod path_pool;
+pub mod policy;
+pub mod progress;
+pub mod resumable_scanner;
+pub mod scanner;
+pub mod server;
+pub mod sharded_collector;
+pub mod sniff;
+pub mod sqlite;
+pub mod tree;
+pub mod tui;
+pub mod types;
+pub mod watch;
+
+pub use categorize::{AgeCategorizer, Categorizer, SizeCategorizer, TypeCategorizer};
+pub use cleanup::{CleanupRule, CleanupSuggestion};
+pub use collector::{CollectionResults, SinglePassCollector};
+pub use engine::{scan, FileObserver, ScanError, ScanMode, ScanOptions};
+pub use incremental_scanner::IncrementalScanner;
+pub use parallel_scanner::ParallelScanner;
+pub use policy::{PolicyReport, PolicyRule};
+pub use resumable_scanner::ResumableScanner;
+pub use scanner::{ScanStats, Scanner};
+pub use tree::FileTree;
+pub use types::{
+    Bucket, CacheStats, DirEntry, DirTotals, DiskUsage, DuplicateGroup, FileEntry, FileMetadata, MountUsage,
+    ScanResults, SizeMode, SkippedMount, Totals, Warning,
+};
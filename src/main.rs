@@ -1,51 +1,35 @@
-mod bounded_heap;
-mod cache;
-mod categorize;
-mod checkpoint;
 mod cli;
-mod collector;
-mod compare;
-mod config;
-mod duplicates;
-mod output;
-mod parallel_scanner;
-mod path_pool;
-mod progress;
-mod scanner;
-mod sharded_collector;
-mod types;
-
-use categorize::{AgeCategorizer, SizeCategorizer, TypeCategorizer};
+
 use clap::Parser;
-use cli::Cli;
-use collector::SinglePassCollector;
-use duplicates::DuplicateFinder;
-use output::{JsonRenderer, TerminalRenderer};
-use parallel_scanner::ParallelScanner;
-use parking_lot::Mutex;
-use progress::ScanProgress;
-use scanner::Scanner;
-use std::sync::Arc;
-use sysinfo::Disks;
-use types::{DiskUsage, ScanResults, Totals};
+use humansize::{format_size, BINARY};
+use cli::{CleanArgs, Cli, Command, ExporterArgs, HistoryArgs, ServeArgs, TuiArgs};
+use spacemap::exporter::Exporter;
+use spacemap::history::{self, History, HistoryEntry, Trend};
+use spacemap::server::{Server, ServerConfig};
+use spacemap::output::{CsvRenderer, HtmlRenderer, JsonRenderer, OutputFormat, PrometheusRenderer, TerminalRenderer};
+use spacemap::tui::{self, TuiError};
+use spacemap::clean::{self, CleanError, CleanPlan, Cleaner, RemovalMethod, Trash};
+use spacemap::sqlite::SqliteIndex;
+use spacemap::watch::{self, Alerts, WatchError};
+use spacemap::compare::GrowthLimit;
+use spacemap::{cache, compare, config, FileObserver, FileTree, ScanError, ScanOptions};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 fn main() {
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Tui(ref args)) => run_tui(args),
+        Some(Command::Clean(ref args)) => run_clean(args),
+        Some(Command::Exporter(ref args)) => run_exporter(args),
+        Some(Command::Serve(ref args)) => run_serve(args),
+        Some(Command::History(ref args)) => run_history(args),
+        None => {}
+    }
+
     // Load configuration early
-    let config = match config::SpacemapConfig::load(cli.config.as_ref()) {
-        Ok(cfg) => Some(cfg),
-        Err(e) => {
-            if cli.config.is_some() {
-                // User specified a config, so fail if it can't load
-                eprintln!("Error loading config: {}", e);
-                std::process::exit(2);
-            } else {
-                // No config or default doesn't exist - use defaults silently
-                None
-            }
-        }
-    };
+    let config = load_config(cli.scan.config.as_ref());
 
     // Handle comparison mode
     if let Some(ref compare_paths) = cli.compare {
@@ -53,62 +37,80 @@ fn main() {
             eprintln!("Error: --compare requires exactly 2 file paths");
             std::process::exit(2);
         }
+        let format = match cli.format() {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(2);
+            }
+        };
+        let growth_limit = match cli.fail_if_growth.as_deref().map(str::parse::<GrowthLimit>).transpose() {
+            Ok(limit) => limit,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(2);
+            }
+        };
 
-        let before = match compare::load_scan_results(&compare_paths[0]) {
-            Ok(results) => results,
+        let (before, before_index) = match compare::load_scan(&compare_paths[0]) {
+            Ok(scan) => scan,
             Err(e) => {
                 eprintln!("Error loading {}: {}", compare_paths[0].display(), e);
                 std::process::exit(2);
             }
         };
 
-        let after = match compare::load_scan_results(&compare_paths[1]) {
-            Ok(results) => results,
+        let (after, after_index) = match compare::load_scan(&compare_paths[1]) {
+            Ok(scan) => scan,
             Err(e) => {
                 eprintln!("Error loading {}: {}", compare_paths[1].display(), e);
                 std::process::exit(2);
             }
         };
 
-        let comparison = compare::compare_scans(&before, &after);
-        let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
-        compare::print_comparison(&before, &after, &comparison, use_color);
-
-        std::process::exit(0);
-    }
-
-    // Handle resume mode
-    if let Some(ref resume_path) = cli.resume {
-        match checkpoint::ScanCheckpoint::load(resume_path) {
-            Ok(ckpt) => {
-                if !cli.should_output_json() {
-                    eprintln!("Resuming scan from checkpoint:");
-                    eprintln!("  Started: {:?}", ckpt.started_at);
-                    eprintln!("  Last checkpoint: {:?}", ckpt.last_checkpoint);
-                    eprintln!("  Files scanned: {}", ckpt.stats.file_count);
-                    eprintln!("  Bytes scanned: {}", ckpt.stats.total_bytes);
-                    eprintln!();
-                    eprintln!("Note: Resume restarts the scan with progress restored.");
-                    eprintln!();
+        let comparison = compare::compare_indexed(&before, &before_index, &after, &after_index);
+        match format {
+            OutputFormat::Terminal => {
+                let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
+                compare::print_comparison(&before, &after, &comparison, use_color);
+            }
+            OutputFormat::Json => {
+                if let Err(e) = JsonRenderer::new().render(&comparison, cli.output.as_deref()) {
+                    eprintln!("Error writing JSON output: {}", e);
+                    std::process::exit(3);
                 }
             }
-            Err(e) => {
-                eprintln!("Error loading checkpoint from {}: {}", resume_path.display(), e);
+            _ => {
+                eprintln!("Error: --compare only supports terminal and JSON output");
                 std::process::exit(2);
             }
         }
-    }
 
-    // Checkpoint not supported in parallel mode
-    if cli.parallel && (cli.checkpoint.is_some() || cli.resume.is_some()) {
-        eprintln!("Error: Checkpoint/resume not supported with --parallel mode");
-        std::process::exit(2);
+        if let Some(limit) = growth_limit {
+            if limit.exceeded_by(&comparison) {
+                eprintln!(
+                    "Error: grew by {} ({}), more than --fail-if-growth {}",
+                    format_size(comparison.net_bytes.unsigned_abs(), BINARY),
+                    comparison.net_percent.map_or_else(|| "new".to_string(), |p| format!("{:+.1}%", p)),
+                    limit
+                );
+                std::process::exit(5);
+            }
+        }
+        std::process::exit(0);
     }
 
-    if let Err(e) = cli.validate() {
+    if let Err(e) = cli.scan.validate() {
         eprintln!("Error: {}", e);
         std::process::exit(2);
     }
+    let format = match cli.format() {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
 
     let path = cli.get_path();
 
@@ -117,232 +119,409 @@ fn main() {
         std::process::exit(2);
     }
 
-    // Check cache if enabled
-    if cli.cached {
-        match cache::ScanCache::new(cli.cache_dir.clone()) {
-            Ok(cache) => {
-                if let Some(entry) = cache.get(&path) {
-                    // Cache hit!
-                    if !cli.should_output_json() {
-                        eprintln!("Using cached results (scanned at {:?})", entry.last_scan);
-                    }
-
-                    let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
-                    let renderer = output::TerminalRenderer::new(use_color, cli.verbose);
-                    renderer.render(&entry.results);
-                    std::process::exit(0);
-                }
-            }
-            Err(e) => {
-                eprintln!("Warning: Failed to load cache: {}", e);
-            }
+    // Single-pass collection: categorize files, track top files/dirs in one scan
+    let should_collect_tops = cli.verbose || cli.is_machine_output();
+
+    if !cli.is_machine_output() {
+        if let Some(ref resume_path) = cli.resume {
+            eprintln!("Resuming scan from checkpoint: {}", resume_path.display());
+        } else if let Some(ref checkpoint_path) = cli.checkpoint {
+            eprintln!("Checkpoint enabled: {}", checkpoint_path.display());
         }
     }
 
-    // Only need modified time if using age categorization
-    let need_modified = cli.by.as_str() == "age";
-    let scanner = Scanner::new(cli.follow_symlinks, cli.max_depth, cli.exclude.clone(), need_modified);
+    let options = cli
+        .scan
+        .options(config.clone())
+        .top(cli.top)
+        .collect_tops(should_collect_tops)
+        .find_duplicates(cli.find_duplicates)
+        .suggest_cleanup(cli.suggest_cleanup)
+        .check_policy(cli.check_policy)
+        // Progress indicator: only enabled if --progress flag is used (and not JSON output)
+        .progress(cli.progress && !cli.is_machine_output())
+        .checkpoint(cli.checkpoint.clone(), cli.checkpoint_interval)
+        .resume(cli.resume.clone())
+        .cache(cli.cached.then(|| cli.cache_dir.clone().unwrap_or_else(cache::default_cache_dir)));
+
+    if cli.watch {
+        run_watch(&cli, &path, options, config.as_ref());
+    }
+
+    let mut observers: Vec<FileObserver> = Vec::new();
+
+    // The HTML treemap needs the whole hierarchy, not just the top-N lists.
+    // Resumed scans don't replay the files counted before the checkpoint.
+    let tree = (format == OutputFormat::Html && cli.resume.is_none()).then(|| Arc::new(Mutex::new(FileTree::new(&path, options.build_categorizer(&path)))));
+    if let Some(ref tree) = tree {
+        let tree = Arc::clone(tree);
+        observers.push(Arc::new(move |file, category| tree.lock().unwrap().insert_categorized(file, category)));
+    }
 
-    // Create categorizer based on mode
-    let categorizer: Box<dyn categorize::Categorizer> = match cli.by.as_str() {
-        "type" => Box::new(TypeCategorizer::with_config(config.as_ref())),
-        "size" => {
-            let custom_buckets = cli.size_buckets.as_ref().and_then(|s| parse_size_buckets(s));
-            Box::new(SizeCategorizer::new(custom_buckets))
+    let index = cli.sqlite.as_ref().map(|db| match SqliteIndex::create(db, &path, options.build_categorizer(&path)) {
+        Ok(index) => index,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(3);
         }
-        "age" => {
-            let custom_buckets = cli.age_buckets.as_ref().and_then(|s| parse_age_buckets(s));
-            Box::new(AgeCategorizer::new(custom_buckets))
+    });
+    if let Some(ref index) = index {
+        observers.push(index.observer());
+    }
+
+    let options = match observers.len() {
+        0 => options,
+        1 => options.on_file(observers.pop()),
+        _ => options.on_file(Some(Arc::new(move |file, category| observers.iter().for_each(|observe| observe(file, category))))),
+    };
+
+    let history_key = options.history_key();
+    let results = match spacemap::scan(&path, options) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(scan_error_code(&e));
         }
-        _ => unreachable!(),
     };
 
-    // Single-pass collection: categorize files, track top files/dirs in one scan
-    let should_collect_tops = cli.verbose || cli.should_output_json();
+    if let Some(index) = index {
+        if let Err(e) = index.finish(&results) {
+            eprintln!("Error: {}", e);
+            std::process::exit(3);
+        }
+    }
 
-    // Duplicate finder (only if requested)
-    let dup_finder = if cli.find_duplicates {
-        Some(Arc::new(Mutex::new(DuplicateFinder::new())))
-    } else {
-        None
-    };
+    if !cli.no_history {
+        let cache_dir = cli.cache_dir.clone().unwrap_or_else(cache::default_cache_dir);
+        let history = History::open(&cache_dir, &path);
+        // The scan itself succeeded, so this is only worth a warning
+        if let Err(e) = history.append(&HistoryEntry::from_results(&results, history_key)) {
+            eprintln!("Warning: could not record scan history in {}: {}", history.path().display(), e);
+        }
+    }
 
-    // Progress indicator: only enabled if --progress flag is used (and not JSON output)
-    let show_progress = cli.progress && !cli.should_output_json();
-    let progress = ScanProgress::new(show_progress);
-
-    // Create checkpoint if requested
-    let mut checkpoint_data = if let Some(ref checkpoint_path) = cli.checkpoint {
-        let ckpt = checkpoint::ScanCheckpoint::new(path.clone());
-        // Save initial checkpoint
-        if let Err(e) = ckpt.save(checkpoint_path) {
-            eprintln!("Warning: Failed to create checkpoint: {}", e);
-            None
-        } else {
-            if !cli.should_output_json() {
-                eprintln!("Checkpoint enabled: {}", checkpoint_path.display());
-            }
-            Some((ckpt, checkpoint_path.clone()))
+    let written = match format {
+        OutputFormat::Terminal => {
+            let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
+            let renderer = TerminalRenderer::with_config(use_color, cli.verbose, config.as_ref());
+            renderer.render(&results);
+            Ok(())
         }
-    } else {
-        None
+        OutputFormat::Json => JsonRenderer::new().render(&results, cli.output.as_deref()),
+        OutputFormat::Csv => CsvRenderer::csv().render(&results, cli.output.as_deref()),
+        OutputFormat::Tsv => CsvRenderer::tsv().render(&results, cli.output.as_deref()),
+        OutputFormat::Html => {
+            let tree = tree.as_ref().map(|tree| tree.lock().unwrap());
+            HtmlRenderer::with_config(config.as_ref()).render(&results, tree.as_deref(), cli.output.as_deref())
+        }
+        OutputFormat::Prometheus => PrometheusRenderer::new().render(&results, cli.output.as_deref()),
     };
+    if let Err(e) = written {
+        eprintln!("Error writing {} output: {}", format.as_str().to_uppercase(), e);
+        std::process::exit(3);
+    }
 
-    let (stats, results) = if cli.parallel {
-        // Parallel filesystem walking with thread-local collectors
-        let parallel_scanner = ParallelScanner::new(
-            cli.threads,
-            cli.follow_symlinks,
-            cli.max_depth,
-            cli.exclude.clone(),
-            need_modified,
-        );
-
-        let dup_finder_clone = dup_finder.clone();
-
-        // Each thread processes into its own collector, then merge at end
-        let (stats, collector) = parallel_scanner.scan(
-            &path,
-            categorizer,
-            cli.top,
-            should_collect_tops,
-            &progress,
-            |file_meta| {
-                if let Some(ref df) = dup_finder_clone {
-                    df.lock().add_file(file_meta.path.clone(), file_meta.size);
-                }
-            },
-        );
+    if let (Some(report), Some(junit)) = (&results.policy, &cli.junit) {
+        if let Err(e) = std::fs::write(junit, report.to_junit(&results.scanned_path)) {
+            eprintln!("Error writing JUnit report {}: {}", junit.display(), e);
+            std::process::exit(3);
+        }
+    }
 
-        let results = collector.finalize(stats.total_bytes);
-        (stats, results)
+    let exit_code = if results.policy.as_ref().is_some_and(|p| !p.passed()) {
+        4
+    } else if results.warnings.is_empty() {
+        0
     } else {
-        // Sequential scanning (original implementation)
-        let mut collector = SinglePassCollector::new(categorizer, cli.top, should_collect_tops);
-        let dup_finder_clone = dup_finder.clone();
+        1
+    };
+    std::process::exit(exit_code);
+}
 
-        let checkpoint_params = checkpoint_data.as_mut().map(|(ckpt, path)| {
-            (ckpt, path.as_path(), cli.checkpoint_interval)
-        });
+fn run_watch(cli: &Cli, path: &Path, options: ScanOptions, config: Option<&config::SpacemapConfig>) -> ! {
+    if cli.format().ok() != Some(OutputFormat::Terminal) {
+        eprintln!("Error: --watch only supports the terminal format");
+        std::process::exit(2);
+    }
+    let alerts = match Alerts::new(config.map_or(&[], |c| c.alert.as_slice())) {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
 
-        let stats = scanner.scan(&path, |meta| {
-            collector.process_file(meta.clone());
-            if let Some(ref df) = dup_finder_clone {
-                df.lock().add_file(meta.path.clone(), meta.size);
-            }
-        }, &progress, checkpoint_params);
+    let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
+    let renderer = TerminalRenderer::with_config(use_color, cli.verbose, config);
+    let interval = std::time::Duration::from_secs(cli.watch_interval.max(1));
+    let result = watch::watch(path, options.progress(false), alerts, interval, |results, status| {
+        // Redraw from the top of a cleared screen
+        print!("\x1b[2J\x1b[H");
+        renderer.render(results);
+        println!();
+        if let Some(ref polling) = status.polling {
+            println!("{}", polling);
+        }
+        for alert in &status.alerts {
+            println!("{}", alert);
+        }
+        println!("Watching {} (updated {}); press Ctrl-C to stop", path.display(), status.updated);
+    });
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(WatchError::Scan(e)) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(scan_error_code(&e));
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(3);
+        }
+    }
+}
 
-        let results = collector.finalize(stats.total_bytes);
-        (stats, results)
+fn run_history(args: &HistoryArgs) -> ! {
+    let path = args.get_path();
+    let cache_dir = args.cache_dir.clone().unwrap_or_else(cache::default_cache_dir);
+    let history = History::open(&cache_dir, &path);
+    let entries = match history.load(args.last.max(1)) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error reading history {}: {}", history.path().display(), e);
+            std::process::exit(3);
+        }
     };
 
-    let buckets = results.buckets;
-    let top_files = results.top_files;
-    let top_dirs = results.top_dirs;
-
-    // Find duplicates if requested
-    let duplicates = if let Some(df) = dup_finder {
-        let finder = Arc::try_unwrap(df)
-            .unwrap_or_else(|_| panic!("Failed to unwrap duplicate finder"))
-            .into_inner();
-
-        let dup_groups = finder.find_duplicates();
-        if dup_groups.is_empty() {
-            None
-        } else {
-            Some(dup_groups.into_iter().map(|dg| types::DuplicateGroup {
-                size: dg.size,
-                hash: dg.hash,
-                paths: dg.paths,
-                wasted_space: dg.wasted_space,
-            }).collect())
+    if args.json {
+        let report = serde_json::json!({
+            "path": path,
+            "scans": entries,
+            "trend": Trend::of(&entries),
+        });
+        if let Err(e) = JsonRenderer::new().render(&report, None) {
+            eprintln!("Error writing JSON output: {}", e);
+            std::process::exit(3);
         }
     } else {
-        None
-    };
+        let use_color = !args.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
+        history::print_history(&path, &entries, use_color);
+    }
+    std::process::exit(0);
+}
 
-    let disk_usage = get_disk_usage(&path);
-
-    let results = ScanResults {
-        scanned_path: path.display().to_string(),
-        mode: cli.by.clone(),
-        totals: Totals {
-            total_bytes: stats.total_bytes,
-            file_count: stats.file_count,
-            dir_count: stats.dir_count,
-            skipped_paths: stats.warnings.len() as u64,
-        },
-        disk_usage,
-        buckets,
-        top_files,
-        top_dirs,
-        warnings: stats.warnings,
-        duplicates,
-    };
+fn run_tui(args: &TuiArgs) -> ! {
+    if let Err(e) = args.scan.validate() {
+        eprintln!("Error: {}", e);
+        std::process::exit(2);
+    }
 
-    // Save to cache if enabled
-    if cli.cached {
-        if let Ok(mut cache) = cache::ScanCache::new(cli.cache_dir.clone()) {
-            if let Err(e) = cache.put(path.clone(), results.clone()) {
-                eprintln!("Warning: Failed to save cache: {}", e);
-            }
+    let path = args.get_path();
+    if !path.exists() {
+        eprintln!("Error: Path does not exist: {}", path.display());
+        std::process::exit(2);
+    }
+
+    let options = args.scan.options(load_config(args.scan.config.as_ref()));
+    match tui::run(&path, options) {
+        Ok(()) => std::process::exit(0),
+        Err(TuiError::Scan(e)) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(scan_error_code(&e));
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(3);
         }
     }
+}
+
+fn run_exporter(args: &ExporterArgs) -> ! {
+    if let Err(e) = args.scan.validate() {
+        eprintln!("Error: {}", e);
+        std::process::exit(2);
+    }
+    let addr: std::net::SocketAddr = match args.listen.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Error: Invalid --listen address {}: {}", args.listen, e);
+            std::process::exit(2);
+        }
+    };
 
-    // Clean up checkpoint file after successful scan
-    if let Some((_, checkpoint_path)) = checkpoint_data {
-        let _ = std::fs::remove_file(&checkpoint_path);
+    let path = args.get_path();
+    if !path.exists() {
+        eprintln!("Error: Path does not exist: {}", path.display());
+        std::process::exit(2);
     }
 
-    if cli.should_output_json() {
-        let renderer = JsonRenderer::new();
-        if let Err(e) = renderer.render(&results, cli.output.as_deref()) {
-            eprintln!("Error writing JSON output: {}", e);
+    let listener = match std::net::TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error: Cannot listen on {}: {}", addr, e);
             std::process::exit(3);
         }
-    } else {
-        let use_color = !cli.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
-        let renderer = TerminalRenderer::with_config(use_color, cli.verbose, config.as_ref());
-        renderer.render(&results);
+    };
+
+    let options = args
+        .scan
+        .options(load_config(args.scan.config.as_ref()))
+        .top(args.top)
+        .collect_tops(true);
+    let exporter = Exporter::new(&path, options);
+
+    // The first scan has to work; later failures are reported and retried
+    eprintln!("Scanning {}...", path.display());
+    if let Err(e) = exporter.scan() {
+        eprintln!("Error: {}", e);
+        std::process::exit(scan_error_code(&e));
     }
+    exporter.spawn_rescans(std::time::Duration::from_secs(args.interval.max(1)));
 
-    let exit_code = if results.warnings.is_empty() { 0 } else { 1 };
-    std::process::exit(exit_code);
+    eprintln!("Serving metrics on http://{}/metrics", addr);
+    if let Err(e) = exporter.serve(listener) {
+        eprintln!("Error: {}", e);
+        std::process::exit(3);
+    }
+    std::process::exit(0);
 }
 
-fn parse_size_buckets(spec: &str) -> Option<Vec<u64>> {
-    let buckets: Result<Vec<u64>, _> = spec.split(',').map(|s| s.trim().parse::<u64>()).collect();
-    buckets.ok()
+fn run_serve(args: &ServeArgs) -> ! {
+    let addr: std::net::SocketAddr = match args.listen.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("Error: Invalid --listen address {}: {}", args.listen, e);
+            std::process::exit(2);
+        }
+    };
+
+    // Requested paths are canonicalized too, so the comparison is exact
+    let mut roots = Vec::new();
+    for root in &args.root {
+        match root.canonicalize() {
+            Ok(root) => roots.push(root),
+            Err(e) => {
+                eprintln!("Error: Invalid --root {}: {}", root.display(), e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let listener = match std::net::TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error: Cannot listen on {}: {}", addr, e);
+            std::process::exit(3);
+        }
+    };
+
+    let server = Server::new(ServerConfig {
+        roots,
+        workers: args.workers,
+        max_queued: args.max_queued,
+        keep: args.keep,
+        config: load_config(args.config.as_ref()),
+    });
+    eprintln!("Serving API on http://{}", addr);
+    if let Err(e) = server.serve(listener) {
+        eprintln!("Error: {}", e);
+        std::process::exit(3);
+    }
+    std::process::exit(0);
 }
 
-fn parse_age_buckets(spec: &str) -> Option<Vec<u64>> {
-    let buckets: Result<Vec<u64>, _> = spec.split(',').map(|s| s.trim().parse::<u64>()).collect();
-    buckets.ok()
+fn run_clean(args: &CleanArgs) -> ! {
+    let trash = Trash::new(args.trash_dir.clone().unwrap_or_else(clean::default_trash_dir));
+    let log_path = args.log.clone().unwrap_or_else(clean::default_log_path);
+    let method = if args.permanent {
+        RemovalMethod::Delete
+    } else {
+        RemovalMethod::Trash
+    };
+    let use_color = !args.no_color && std::io::IsTerminal::is_terminal(&std::io::stdout());
+
+    let plan = match CleanPlan::new(&args.paths, &ScanOptions::new(), &trash) {
+        Ok(plan) => plan,
+        Err(e) => exit_clean_error(e),
+    };
+    clean::print_plan(&plan, method, use_color);
+
+    if args.dry_run {
+        println!("Dry run: nothing was removed.");
+        std::process::exit(0);
+    }
+
+    if !args.yes {
+        if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            eprintln!("Error: refusing to remove without confirmation; pass --yes when stdin is not a terminal");
+            std::process::exit(2);
+        }
+        if !confirm(method) {
+            println!("Nothing was removed.");
+            std::process::exit(0);
+        }
+    }
+
+    let cleaner = Cleaner::new(trash, log_path);
+    match cleaner.execute(&plan, method) {
+        Ok(report) => {
+            clean::print_report(&report, cleaner.log_path(), use_color);
+            std::process::exit(if report.failures() == 0 { 0 } else { 1 });
+        }
+        Err(e) => exit_clean_error(e),
+    }
 }
 
-fn get_disk_usage(path: &std::path::Path) -> Option<DiskUsage> {
-    let disks = Disks::new_with_refreshed_list();
+fn confirm(method: RemovalMethod) -> bool {
+    use std::io::Write;
 
-    let canonical_path = path.canonicalize().ok()?;
+    let question = match method {
+        RemovalMethod::Trash => "Move these to the trash?",
+        RemovalMethod::Delete => "Delete these permanently?",
+    };
+    print!("{} [y/N] ", question);
+    let _ = std::io::stdout().flush();
 
-    // Find the disk that contains this path
-    let disk = disks.iter().find(|d| {
-        canonical_path.starts_with(d.mount_point())
-    })?;
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
 
-    let total_space = disk.total_space();
-    let available_space = disk.available_space();
-    let used_space = total_space.saturating_sub(available_space);
-    let used_percent = if total_space > 0 {
-        (used_space as f64 / total_space as f64) * 100.0
-    } else {
-        0.0
+fn exit_clean_error(error: CleanError) -> ! {
+    eprintln!("Error: {}", error);
+    let code = match &error {
+        CleanError::Io(..) | CleanError::Protected(..) => 2,
+        CleanError::Scan(e) => scan_error_code(e),
+        CleanError::Log(..) => 3,
     };
+    std::process::exit(code);
+}
 
-    Some(DiskUsage {
-        total_space,
-        available_space,
-        used_space,
-        used_percent,
-    })
+fn load_config(path: Option<&PathBuf>) -> Option<config::SpacemapConfig> {
+    match config::SpacemapConfig::load(path) {
+        Ok(cfg) => Some(cfg),
+        Err(e) => {
+            if path.is_some() {
+                // User specified a config, so fail if it can't load
+                eprintln!("Error loading config: {}", e);
+                std::process::exit(2);
+            } else {
+                // No config or default doesn't exist - use defaults silently
+                None
+            }
+        }
+    }
+}
+
+fn scan_error_code(error: &ScanError) -> i32 {
+    match error {
+        ScanError::PathNotFound(_)
+        | ScanError::InvalidOptions(_)
+        | ScanError::InvalidPattern(_)
+        | ScanError::InvalidCheckpoint(..)
+        | ScanError::InvalidPolicy(_) => 2,
+        ScanError::Checkpoint(..) | ScanError::Cache(..) => 3,
+    }
 }
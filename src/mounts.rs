@@ -0,0 +1,265 @@
+use crate::scanner::device_id;
+use crate::types::{DiskUsage, MountUsage, SizeMode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use sysinfo::{Disk, Disks};
+
+/// Bytes attributed to one filesystem during a scan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceUsage {
+    /// Shallowest path seen on this device. For filesystems the scan crossed
+    /// into this is the mount point; for the root filesystem it is the scan root.
+    pub first_path: PathBuf,
+    pub bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+}
+
+/// Per-filesystem totals, keyed by device ID (`st_dev`).
+///
+/// Platforms without device IDs attribute everything to device 0.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceTally {
+    devices: HashMap<u64, DeviceUsage>,
+}
+
+impl DeviceTally {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a directory so filesystems that only hold directories still show up.
+    pub fn add_dir(&mut self, metadata: &std::fs::Metadata, path: &Path) {
+        self.usage(device_id(metadata), path);
+    }
+
+    pub fn add_file(&mut self, metadata: &std::fs::Metadata, path: &Path, bytes: u64, disk_bytes: u64) {
+        self.add_file_on(device_id(metadata), path, bytes, disk_bytes);
+    }
+
+    /// Like [`add_file`](Self::add_file) when only the device is known,
+    /// e.g. for files reused from the scan cache.
+    pub fn add_file_on(&mut self, device: Option<u64>, path: &Path, bytes: u64, disk_bytes: u64) {
+        self.add_files_on(device, path, bytes, disk_bytes, 1);
+    }
+
+    /// Like [`add_file_on`](Self::add_file_on) for `file_count` files at
+    /// once, e.g. a directory summary from the scan cache.
+    pub fn add_files_on(&mut self, device: Option<u64>, path: &Path, bytes: u64, disk_bytes: u64, file_count: u64) {
+        let usage = self.usage(device, path);
+        usage.bytes += bytes;
+        usage.disk_bytes += disk_bytes;
+        usage.file_count += file_count;
+    }
+
+    fn usage(&mut self, device: Option<u64>, path: &Path) -> &mut DeviceUsage {
+        // Only the first entry per device pays for the path copy
+        self.devices.entry(device.unwrap_or(0)).or_insert_with(|| DeviceUsage {
+            first_path: path.to_path_buf(),
+            bytes: 0,
+            disk_bytes: 0,
+            file_count: 0,
+        })
+    }
+
+    pub fn merge(&mut self, other: DeviceTally) {
+        for (device, theirs) in other.devices {
+            match self.devices.entry(device) {
+                Entry::Vacant(slot) => {
+                    slot.insert(theirs);
+                }
+                Entry::Occupied(mut slot) => {
+                    let ours = slot.get_mut();
+                    ours.bytes += theirs.bytes;
+                    ours.disk_bytes += theirs.disk_bytes;
+                    ours.file_count += theirs.file_count;
+                    // Parallel threads see a device in arbitrary order; keep the shallowest path
+                    if theirs.first_path.components().count() < ours.first_path.components().count() {
+                        ours.first_path = theirs.first_path;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, &DeviceUsage)> {
+        self.devices.iter()
+    }
+}
+
+/// Snapshot of the system's mounted filesystems.
+pub struct MountTable {
+    disks: Disks,
+    /// Device ID of each disk's mount point, parallel to `disks`
+    disk_devices: Vec<Option<u64>>,
+}
+
+impl MountTable {
+    pub fn load() -> Self {
+        let disks = Disks::new_with_refreshed_list();
+        let disk_devices = disks
+            .iter()
+            .map(|disk| {
+                std::fs::metadata(disk.mount_point())
+                    .ok()
+                    .and_then(|m| device_id(&m))
+            })
+            .collect();
+
+        Self { disks, disk_devices }
+    }
+
+    /// Capacity of the filesystem containing `path`.
+    pub fn disk_usage(&self, path: &Path) -> Option<DiskUsage> {
+        let canonical_path = path.canonicalize().ok()?;
+        let device = std::fs::metadata(&canonical_path).ok().and_then(|m| device_id(&m));
+        let disk = self.containing(&canonical_path, device)?;
+
+        let total_space = disk.total_space();
+        let available_space = disk.available_space();
+        let used_space = total_space.saturating_sub(available_space);
+        let used_percent = if total_space > 0 {
+            (used_space as f64 / total_space as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        Some(DiskUsage {
+            total_space,
+            available_space,
+            used_space,
+            used_percent,
+        })
+    }
+
+    /// Describe every filesystem in `tally`, largest first.
+    pub fn mounts(&self, tally: &DeviceTally, size_mode: SizeMode) -> Vec<MountUsage> {
+        let mut mounts: Vec<MountUsage> = tally
+            .iter()
+            .map(|(&device, usage)| {
+                let disk = usage
+                    .first_path
+                    .canonicalize()
+                    .ok()
+                    .and_then(|path| self.containing(&path, device_id_known(device)));
+
+                match disk {
+                    Some(disk) => MountUsage {
+                        mount_point: disk.mount_point().display().to_string(),
+                        file_system: disk.file_system().to_string_lossy().into_owned(),
+                        bytes: usage.bytes,
+                        disk_bytes: usage.disk_bytes,
+                        file_count: usage.file_count,
+                        total_space: Some(disk.total_space()),
+                        available_space: Some(disk.available_space()),
+                    },
+                    // Pseudo filesystems (/proc, devtmpfs, ...) are often not listed as disks
+                    None => MountUsage {
+                        mount_point: usage.first_path.display().to_string(),
+                        file_system: "unknown".to_string(),
+                        bytes: usage.bytes,
+                        disk_bytes: usage.disk_bytes,
+                        file_count: usage.file_count,
+                        total_space: None,
+                        available_space: None,
+                    },
+                }
+            })
+            .collect();
+
+        mounts.sort_by(|a, b| {
+            let a_size = size_mode.select(a.bytes, a.disk_bytes);
+            let b_size = size_mode.select(b.bytes, b.disk_bytes);
+            b_size.cmp(&a_size).then_with(|| a.mount_point.cmp(&b.mount_point))
+        });
+        mounts
+    }
+
+    /// The disk with the longest mount point prefix of `path`.
+    ///
+    /// When both device IDs are known they must agree: a path on a filesystem
+    /// that isn't listed as a disk (e.g. `/dev`) would otherwise be reported
+    /// as part of `/`.
+    fn containing(&self, path: &Path, device: Option<u64>) -> Option<&Disk> {
+        let index = longest_prefix(self.disks.iter().map(|d| d.mount_point()), path)?;
+        match (device, self.disk_devices[index]) {
+            (Some(a), Some(b)) if a != b => None,
+            _ => self.disks.get(index),
+        }
+    }
+}
+
+/// Device IDs recorded as 0 by [`DeviceTally`] on platforms without `st_dev`.
+fn device_id_known(device: u64) -> Option<u64> {
+    if cfg!(unix) {
+        Some(device)
+    } else {
+        None
+    }
+}
+
+/// Index of the mount point that is the longest prefix of `path`.
+///
+/// `/` is a prefix of every absolute path, so the first match is usually the
+/// wrong one for paths on deeper mounts such as `/home` or `/mnt/data`.
+fn longest_prefix<'a>(mount_points: impl Iterator<Item = &'a Path>, path: &Path) -> Option<usize> {
+    mount_points
+        .enumerate()
+        .filter(|(_, mount_point)| path.starts_with(mount_point))
+        .max_by_key(|(_, mount_point)| mount_point.components().count())
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_longest_prefix_prefers_deepest_mount() {
+        let mounts = [Path::new("/"), Path::new("/home/user/data"), Path::new("/home")];
+        let pick = |path: &str| longest_prefix(mounts.iter().copied(), Path::new(path));
+
+        assert_eq!(pick("/home/user/data/file"), Some(1));
+        assert_eq!(pick("/home/user/notes"), Some(2));
+        assert_eq!(pick("/var/log"), Some(0));
+        // Component-wise, not string, prefixes
+        assert_eq!(pick("/homework"), Some(0));
+    }
+
+    #[test]
+    fn test_tally_merge_keeps_shallowest_path() {
+        let dir = TempDir::new().unwrap();
+        let deep = dir.path().join("a/b");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(deep.join("file"), vec![0u8; 10]).unwrap();
+        let file_meta = fs::metadata(deep.join("file")).unwrap();
+
+        let mut first = DeviceTally::new();
+        first.add_file(&file_meta, &deep.join("file"), 10, 4096);
+
+        let mut second = DeviceTally::new();
+        second.add_dir(&fs::metadata(dir.path()).unwrap(), dir.path());
+        second.add_file(&file_meta, &deep.join("file"), 10, 4096);
+
+        first.merge(second);
+        assert_eq!(first.len(), 1);
+
+        let (_, usage) = first.iter().next().unwrap();
+        assert_eq!(usage.first_path, dir.path());
+        assert_eq!(usage.bytes, 20);
+        assert_eq!(usage.disk_bytes, 8192);
+        assert_eq!(usage.file_count, 2);
+    }
+}
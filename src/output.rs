@@ -1,8 +1,13 @@
+use crate::cleanup::CleanupSuggestion;
 use crate::config::{ColorResolver, SpacemapConfig};
-use crate::types::{Bucket, DirEntry, FileEntry, ScanResults, Warning};
+use crate::policy::{self, CheckKind, Limit, PolicyReport};
+use crate::tree::{FileTree, NodeId, NodeTotals};
+use crate::types::{
+    Bucket, DirEntry, DirTotals, FileEntry, MountUsage, ScanResults, SizeMode, SkippedMount, Warning,
+};
 use colored::*;
 use humansize::{format_size, BINARY};
-use std::io;
+use std::io::{self, Write};
 
 pub struct TerminalRenderer {
     use_color: bool,
@@ -30,6 +35,21 @@ impl TerminalRenderer {
         println!();
         self.print_buckets(&results.buckets);
 
+        if results.mounts.len() > 1 {
+            println!();
+            self.print_mounts(&results.mounts, results.size_mode);
+        }
+
+        if let Some(ref suggestions) = results.cleanup {
+            println!();
+            self.print_cleanup(suggestions, results.size_mode);
+        }
+
+        if let Some(ref policy) = results.policy {
+            println!();
+            self.print_policy(policy);
+        }
+
         if self.verbose {
             if !results.top_files.is_empty() {
                 println!();
@@ -38,7 +58,7 @@ impl TerminalRenderer {
 
             if !results.top_dirs.is_empty() {
                 println!();
-                self.print_top_dirs(&results.top_dirs);
+                self.print_top_dirs(&results.top_dirs, results.dir_totals);
             }
 
             if let Some(ref duplicates) = results.duplicates {
@@ -52,6 +72,11 @@ impl TerminalRenderer {
                 println!();
                 self.print_warnings(&results.warnings);
             }
+
+            if !results.skipped_mounts.is_empty() {
+                println!();
+                self.print_skipped_mounts(&results.skipped_mounts);
+            }
         }
         println!();
     }
@@ -64,10 +89,14 @@ impl TerminalRenderer {
         );
         println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
 
+        let scan_bytes = results
+            .size_mode
+            .select(results.totals.total_bytes, results.totals.disk_bytes);
+
         // Disk info
         if let Some(disk) = &results.disk_usage {
             let scan_percent = if disk.total_space > 0 {
-                (results.totals.total_bytes as f64 / disk.total_space as f64) * 100.0
+                (scan_bytes as f64 / disk.total_space as f64) * 100.0
             } else {
                 0.0
             };
@@ -84,11 +113,44 @@ impl TerminalRenderer {
             println!(
                 "  {}  {} ({:.2}% of disk)",
                 self.style("SCAN", "green", true),
-                self.style(&format_size(results.totals.total_bytes, BINARY), "green", false),
+                self.style(&format_size(scan_bytes, BINARY), "green", false),
                 scan_percent
             );
         }
 
+        println!(
+            "  {}  {} apparent    {} on disk    (ranked by {})",
+            self.style("SIZE", "green", true),
+            self.style(&format_size(results.totals.total_bytes, BINARY), "green", false),
+            self.style(&format_size(results.totals.disk_bytes, BINARY), "green", false),
+            results.size_mode.as_str()
+        );
+
+        if results.totals.hardlink_bytes > 0 {
+            println!(
+                "  {}  {} in extra hard links (counted once)",
+                self.style("LINKS", "green", true),
+                self.style(&format_size(results.totals.hardlink_bytes, BINARY), "green", false),
+            );
+        }
+
+        if let Some(cache) = &results.cache {
+            println!(
+                "  {}  {} directories reused, {} re-read",
+                self.style("CACHE", "blue", true),
+                self.style(&cache.hits.to_string(), "blue", false),
+                self.style(&cache.misses.to_string(), "blue", false),
+            );
+        }
+
+        if !results.skipped_mounts.is_empty() {
+            println!(
+                "  {}  {} mount point(s) not crossed",
+                self.style("MOUNTS", "yellow", true),
+                self.style(&results.skipped_mounts.len().to_string(), "yellow", false),
+            );
+        }
+
         // Stats row
         println!(
             "  {}  {}    {}  {}    {}  {}",
@@ -110,20 +172,23 @@ impl TerminalRenderer {
         // Fixed column widths
         let name_w = 14;
         let size_w = 12;
+        let disk_w = 12;
         let pct_w = 8;
         let files_w = 10;
         let bar_w = 20;
 
         // Header - format as single string to preserve spacing
         let header = format!(
-            "  {:<name_w$}{:>size_w$}{:>pct_w$}{:>files_w$}  {}",
+            "  {:<name_w$}{:>size_w$}{:>disk_w$}{:>pct_w$}{:>files_w$}  {}",
             "CATEGORY",
             "SIZE",
+            "ON DISK",
             "PCT",
             "FILES",
             "DISTRIBUTION",
             name_w = name_w,
             size_w = size_w,
+            disk_w = disk_w,
             pct_w = pct_w,
             files_w = files_w,
         );
@@ -131,9 +196,10 @@ impl TerminalRenderer {
 
         // Separator
         let sep = format!(
-            "  {}{}{}{}  {}",
+            "  {}{}{}{}{}  {}",
             "─".repeat(name_w),
             "─".repeat(size_w),
+            "─".repeat(disk_w),
             "─".repeat(pct_w),
             "─".repeat(files_w),
             "─".repeat(bar_w),
@@ -143,6 +209,7 @@ impl TerminalRenderer {
         // Rows
         for bucket in buckets {
             let size_str = format_size(bucket.bytes, BINARY);
+            let disk_str = format_size(bucket.disk_bytes, BINARY);
             let pct_str = format!("{:.1}%", bucket.percent);
 
             // Determine row color - use ColorResolver if available, otherwise fallback to percentage
@@ -172,32 +239,50 @@ impl TerminalRenderer {
             if self.use_color {
                 let name_col = format!("{:<width$}", bucket.label, width = name_w);
                 let size_col = format!("{:>width$}", size_str, width = size_w);
+                let disk_col = format!("{:>width$}", disk_str, width = disk_w);
                 let pct_col = format!("{:>width$}", pct_str, width = pct_w);
                 let files_col = format!("{:>width$}", bucket.file_count, width = files_w);
 
                 println!(
-                    "  {}{}{}{}  {}",
+                    "  {}{}{}{}{}  {}",
                     self.style(&name_col, &name_color, bold),
                     self.style(&size_col, "green", true),
+                    self.style(&disk_col, "green", false),
                     self.style(&pct_col, "magenta", false),
                     self.style(&files_col, "cyan", false),
                     bar
                 );
             } else {
                 println!(
-                    "  {:<name_w$}{:>size_w$}{:>pct_w$}{:>files_w$}  {}",
+                    "  {:<name_w$}{:>size_w$}{:>disk_w$}{:>pct_w$}{:>files_w$}  {}",
                     bucket.label,
                     size_str,
+                    disk_str,
                     pct_str,
                     bucket.file_count,
                     bar,
                     name_w = name_w,
                     size_w = size_w,
+                    disk_w = disk_w,
                     pct_w = pct_w,
                     files_w = files_w,
                 );
             }
         }
+
+        let content_files: u64 = buckets.iter().map(|b| b.content_files).sum();
+        if content_files > 0 {
+            let content_bytes: u64 = buckets.iter().map(|b| b.content_bytes).sum();
+            println!();
+            println!(
+                "  {}",
+                self.style(
+                    &format!("{} files ({}) categorized by content", content_files, format_size(content_bytes, BINARY)),
+                    "white",
+                    false
+                )
+            );
+        }
     }
 
     fn make_bar(&self, bucket: &Bucket, width: usize) -> String {
@@ -251,33 +336,41 @@ impl TerminalRenderer {
 
         for (i, file) in files.iter().enumerate() {
             let size = format_size(file.bytes, BINARY);
+            let disk_size = format_size(file.disk_bytes, BINARY);
             let path = self.truncate_path(&file.path, 42);
 
             println!(
-                "  {} {:>10}  {}",
+                "  {} {:>10} {:>10}  {}",
                 self.style(&format!("{:>2}.", i + 1), "bright_black", false),
                 self.style(&size, "green", false),
+                self.style(&disk_size, "bright_black", false),
                 path
             );
         }
     }
 
-    fn print_top_dirs(&self, dirs: &[DirEntry]) {
+    fn print_top_dirs(&self, dirs: &[DirEntry], dir_totals: DirTotals) {
         if dirs.is_empty() {
             return;
         }
 
-        println!("  {}", self.style("TOP DIRECTORIES", "cyan", true));
+        let title = match dir_totals {
+            DirTotals::Cumulative => "TOP DIRECTORIES",
+            DirTotals::SelfOnly => "TOP DIRECTORIES (direct files only)",
+        };
+        println!("  {}", self.style(title, "cyan", true));
         println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
 
         for (i, dir) in dirs.iter().enumerate() {
             let size = format_size(dir.bytes, BINARY);
+            let disk_size = format_size(dir.disk_bytes, BINARY);
             let path = self.truncate_path(&dir.path, 42);
 
             println!(
-                "  {} {:>10}  {}",
+                "  {} {:>10} {:>10}  {}",
                 self.style(&format!("{:>2}.", i + 1), "bright_black", false),
                 self.style(&size, "green", false),
+                self.style(&disk_size, "bright_black", false),
                 path
             );
         }
@@ -311,6 +404,43 @@ impl TerminalRenderer {
         }
     }
 
+    fn print_mounts(&self, mounts: &[MountUsage], size_mode: SizeMode) {
+        println!("  {}", self.style("FILESYSTEMS", "white", true));
+        println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
+
+        for mount in mounts {
+            let scanned = size_mode.select(mount.bytes, mount.disk_bytes);
+            let capacity = match (mount.total_space, mount.available_space) {
+                (Some(total), Some(free)) => format!(
+                    "{} free of {}",
+                    format_size(free, BINARY),
+                    format_size(total, BINARY)
+                ),
+                _ => "capacity unknown".to_string(),
+            };
+
+            println!(
+                "    {:<24} {:<8} {:>10}   {}",
+                self.truncate_path(&mount.mount_point, 24),
+                mount.file_system,
+                self.style(&format_size(scanned, BINARY), "green", false),
+                self.style(&capacity, "bright_black", false)
+            );
+        }
+    }
+
+    fn print_skipped_mounts(&self, mounts: &[SkippedMount]) {
+        println!(
+            "  {}",
+            self.style("SKIPPED MOUNT POINTS (--one-file-system)", "yellow", true)
+        );
+        println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
+
+        for mount in mounts {
+            println!("    {}", self.style(&mount.path, "bright_black", false));
+        }
+    }
+
     fn print_duplicates(&self, duplicates: &[crate::types::DuplicateGroup]) {
         println!(
             "  {}",
@@ -351,6 +481,105 @@ impl TerminalRenderer {
         }
     }
 
+    fn print_cleanup(&self, suggestions: &[CleanupSuggestion], size_mode: SizeMode) {
+        println!("  {}", self.style("CLEANUP SUGGESTIONS", "yellow", true));
+        println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
+
+        if suggestions.is_empty() {
+            println!("  No regenerable directories found");
+            return;
+        }
+
+        let size = |s: &CleanupSuggestion| size_mode.select(s.bytes, s.disk_bytes);
+        let total: u64 = suggestions.iter().map(size).sum();
+        println!(
+            "  {} reclaimable in {} directories",
+            self.style(&format_size(total, BINARY), "green", true),
+            self.style(&suggestions.len().to_string(), "cyan", false)
+        );
+        println!();
+
+        for (i, suggestion) in suggestions.iter().take(10).enumerate() {
+            let age = match suggestion.age_days {
+                Some(days) => format!("{}d old", days),
+                None => "age unknown".to_string(),
+            };
+            println!(
+                "  {}. {:>10}  {:>11}  {}",
+                self.style(&(i + 1).to_string(), "cyan", false),
+                self.style(&format_size(size(suggestion), BINARY), "green", true),
+                age,
+                self.truncate_path(&suggestion.path, 50)
+            );
+            let mut details = suggestion.rule.clone();
+            if let Some(ref hint) = suggestion.hint {
+                details.push_str(&format!(" - {}", hint));
+            }
+            println!("     {}", self.style(&details, "bright_black", false));
+        }
+
+        if suggestions.len() > 10 {
+            println!(
+                "  {}",
+                self.style(&format!("...and {} more", suggestions.len() - 10), "bright_black", false)
+            );
+        }
+    }
+
+    fn print_policy(&self, report: &PolicyReport) {
+        println!("  {}", self.style("POLICY CHECK", "yellow", true));
+        println!("  {}", self.style(&"─".repeat(56), "bright_black", false));
+
+        let failed = report.failed_checks().count();
+        if failed == 0 {
+            println!(
+                "  {} all {} checks passed",
+                self.style("✓", "green", true),
+                report.checks.len()
+            );
+            return;
+        }
+        println!(
+            "  {} {} of {} checks failed",
+            self.style("✗", "red", true),
+            self.style(&failed.to_string(), "red", true),
+            report.checks.len()
+        );
+
+        for check in report.failed_checks() {
+            let (limit, value): (String, fn(u64) -> String) = match (check.check, &check.limit) {
+                (CheckKind::MaxAgeDays, Limit::Number(days)) => (format!("{} days", days), |v| format!("{} days old", v)),
+                (_, Limit::Number(bytes)) => (format_size(*bytes, BINARY), |v| format_size(v, BINARY)),
+                (_, limit) => (policy::limit_text(limit), |v| v.to_string()),
+            };
+            println!();
+            println!(
+                "  {} {} ({} {}): {} violation(s)",
+                self.style("✗", "red", true),
+                self.style(&check.policy, "white", true),
+                check.check.as_str(),
+                limit,
+                check.violation_count
+            );
+            for violation in check.violations.iter().take(10) {
+                match violation.value {
+                    Some(v) => println!(
+                        "     {}  {}",
+                        self.style(&format!("{:>12}", value(v)), "red", false),
+                        violation.path
+                    ),
+                    None => println!("     {}", violation.path),
+                }
+            }
+            if check.violation_count > 10 {
+                println!(
+                    "     {}",
+                    self.style(&format!("...and {} more", check.violation_count - 10), "bright_black", false)
+                );
+            }
+        }
+    }
+
     fn truncate_path(&self, path: &str, max_len: usize) -> String {
         if path.len() <= max_len {
             path.to_string()
@@ -384,6 +613,48 @@ impl TerminalRenderer {
     }
 }
 
+/// Output format selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Terminal,
+    Json,
+    Csv,
+    Tsv,
+    Html,
+    Prometheus,
+}
+
+impl OutputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Terminal => "terminal",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Html => "html",
+            OutputFormat::Prometheus => "prometheus",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "terminal" => Ok(OutputFormat::Terminal),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "html" => Ok(OutputFormat::Html),
+            "prometheus" => Ok(OutputFormat::Prometheus),
+            _ => Err(format!("Invalid --format: {}. Must be terminal, json, csv, tsv, html or prometheus", s)),
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct JsonRenderer;
 
 impl JsonRenderer {
@@ -391,12 +662,13 @@ impl JsonRenderer {
         Self
     }
 
+    /// Write `value` (usually [`ScanResults`]) as pretty-printed JSON.
     pub fn render(
         &self,
-        results: &ScanResults,
+        value: &impl serde::Serialize,
         output_file: Option<&std::path::Path>,
     ) -> io::Result<()> {
-        let json = serde_json::to_string_pretty(results)?;
+        let json = serde_json::to_string_pretty(value)?;
 
         if let Some(path) = output_file {
             std::fs::write(path, json)?;
@@ -407,3 +679,601 @@ impl JsonRenderer {
         Ok(())
     }
 }
+
+/// Template of the HTML report; `{{TITLE}}` and `{{DATA}}` are filled in.
+const HTML_TEMPLATE: &str = include_str!("report.html");
+
+/// Directory tree entries smaller than this fraction of the whole scan are
+/// merged into one "smaller items" entry per directory.
+const TREEMAP_MIN_FRACTION: u64 = 4000;
+
+/// Fallback chart colors for categories without a configured one.
+const PALETTE: [&str; 10] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7", "#9c755f", "#bab0ac",
+];
+
+/// A single offline HTML page with a treemap of the directory hierarchy,
+/// category charts, sortable top-N tables and duplicate groups.
+pub struct HtmlRenderer {
+    color_resolver: Option<ColorResolver>,
+}
+
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self::with_config(None)
+    }
+
+    /// Category colors follow the config, like the terminal output.
+    pub fn with_config(config: Option<&SpacemapConfig>) -> Self {
+        Self {
+            color_resolver: config.map(|c| ColorResolver::new(c.clone())),
+        }
+    }
+
+    /// Write the report to `output_file`, or to stdout. Without a `tree`
+    /// (see [`crate::FileTree`]) the treemap is left out.
+    pub fn render(&self, results: &ScanResults, tree: Option<&FileTree>, output_file: Option<&std::path::Path>) -> io::Result<()> {
+        let html = self.to_html(results, tree);
+        match output_file {
+            Some(path) => std::fs::write(path, html),
+            None => io::stdout().lock().write_all(html.as_bytes()),
+        }
+    }
+
+    pub fn to_html(&self, results: &ScanResults, tree: Option<&FileTree>) -> String {
+        let size_mode = results.size_mode;
+        let buckets: Vec<_> = results
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                serde_json::json!({
+                    "label": bucket.label,
+                    "size": size_mode.select(bucket.bytes, bucket.disk_bytes),
+                    "percent": bucket.percent,
+                    "file_count": bucket.file_count,
+                    "color": self.bucket_color(bucket, i),
+                })
+            })
+            .collect();
+
+        let data = serde_json::json!({
+            "path": results.scanned_path,
+            "mode": results.mode,
+            "size_mode": size_mode.as_str(),
+            "generated": chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            "totals": {
+                "bytes": results.totals.total_bytes,
+                "disk_bytes": results.totals.disk_bytes,
+                "file_count": results.totals.file_count,
+                "dir_count": results.totals.dir_count,
+            },
+            "buckets": buckets,
+            "top_files": results.top_files,
+            "top_dirs": results.top_dirs,
+            "duplicates": results.duplicates.as_deref().unwrap_or_default(),
+            "tree": tree.map(|tree| {
+                let root = tree.totals(tree.root(), None);
+                let min_size = size_mode.select(root.bytes, root.disk_bytes) / TREEMAP_MIN_FRACTION;
+                treemap_node(tree, tree.root(), min_size, size_mode)
+            }),
+        });
+
+        // Keep the data from closing its <script> element early
+        let data = data.to_string().replace("</", "<\\/");
+        let title = format!("spacemap: {}", results.scanned_path)
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        fill_template(HTML_TEMPLATE, &[("TITLE", &title), ("DATA", &data)])
+    }
+
+    fn bucket_color(&self, bucket: &Bucket, index: usize) -> String {
+        let color = bucket.color.clone().or_else(|| {
+            self.color_resolver
+                .as_ref()
+                .and_then(|r| r.resolve_bucket_color(bucket, bucket.representative_extension.as_deref()))
+        });
+        match color.as_deref() {
+            Some(c) if c.starts_with('#') => c.to_string(),
+            Some("red") => "#e15759".to_string(),
+            Some("green") => "#59a14f".to_string(),
+            Some("yellow") => "#edc948".to_string(),
+            Some("blue") => "#4e79a7".to_string(),
+            Some("cyan") => "#76b7b2".to_string(),
+            Some("magenta") => "#b07aa1".to_string(),
+            Some("white") => "#bab0ac".to_string(),
+            Some("bright_black") => "#79706e".to_string(),
+            _ => PALETTE[index % PALETTE.len()].to_string(),
+        }
+    }
+}
+
+impl Default for HtmlRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `template` with each `{{NAME}}` marker replaced by its value. Markers are
+/// found in a single pass, so a value containing one is left as it is.
+fn fill_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len() + values.iter().map(|(_, v)| v.len()).sum::<usize>());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        let marker = values
+            .iter()
+            .find(|(name, _)| rest.strip_prefix(name).is_some_and(|r| r.starts_with("}}")));
+        match marker {
+            Some((name, value)) => {
+                filled.push_str(value);
+                rest = &rest[name.len() + 2..];
+            }
+            None => filled.push_str("{{"),
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// `{n: name, s: size, f: files, c: children, t: category, o: merged}` for
+/// `id` and the entries below it of at least `min_size`.
+fn treemap_node(tree: &FileTree, id: NodeId, min_size: u64, size_mode: SizeMode) -> serde_json::Value {
+    let node = tree.node(id);
+    let size = |totals: &NodeTotals| size_mode.select(totals.bytes, totals.disk_bytes);
+    let mut value = serde_json::json!({
+        "n": node.name.to_string_lossy(),
+        "s": size(&node.totals),
+        "f": node.totals.file_count,
+    });
+
+    if node.is_dir {
+        let mut children: Vec<NodeId> = node.children.clone();
+        children.sort_by_key(|&c| std::cmp::Reverse(size(&tree.node(c).totals)));
+        let split = children.partition_point(|&c| size(&tree.node(c).totals) >= min_size.max(1));
+
+        let mut entries: Vec<_> = children[..split]
+            .iter()
+            .map(|&c| treemap_node(tree, c, min_size, size_mode))
+            .collect();
+        let rest = &children[split..];
+        if !rest.is_empty() {
+            let mut merged = NodeTotals::default();
+            for &c in rest {
+                let totals = tree.node(c).totals;
+                merged.bytes += totals.bytes;
+                merged.disk_bytes += totals.disk_bytes;
+                merged.file_count += totals.file_count;
+            }
+            entries.push(serde_json::json!({
+                "n": format!("({} smaller items)", rest.len()),
+                "s": size(&merged),
+                "f": merged.file_count,
+                "o": true,
+            }));
+        }
+        value["c"] = serde_json::Value::Array(entries);
+    } else if let Some(category) = tree.category(id) {
+        value["t"] = serde_json::Value::String(category.label.clone());
+    }
+    value
+}
+
+/// Gauges in the Prometheus text exposition format, labelled by scanned
+/// path and by category or directory.
+pub struct PrometheusRenderer;
+
+impl PrometheusRenderer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn render(&self, results: &ScanResults, output_file: Option<&std::path::Path>) -> io::Result<()> {
+        let text = self.to_text(results);
+        match output_file {
+            Some(path) => std::fs::write(path, text),
+            None => io::stdout().lock().write_all(text.as_bytes()),
+        }
+    }
+
+    pub fn to_text(&self, results: &ScanResults) -> String {
+        let mut metrics = Metrics::default();
+        let scanned = results.scanned_path.as_str();
+        let path = [("path", scanned)];
+        let totals = &results.totals;
+
+        metrics.gauge("spacemap_bytes", "Apparent size of all scanned files");
+        metrics.sample(&path, totals.total_bytes);
+        metrics.gauge("spacemap_disk_bytes", "Space allocated on disk for all scanned files");
+        metrics.sample(&path, totals.disk_bytes);
+        metrics.gauge("spacemap_files", "Number of scanned files");
+        metrics.sample(&path, totals.file_count);
+        metrics.gauge("spacemap_directories", "Number of scanned directories");
+        metrics.sample(&path, totals.dir_count);
+        metrics.gauge("spacemap_warnings", "Entries that could not be read");
+        metrics.sample(&path, results.warnings.len() as u64);
+
+        if let Some(ref usage) = results.disk_usage {
+            metrics.gauge("spacemap_filesystem_size_bytes", "Capacity of the filesystem holding the scanned path");
+            metrics.sample(&path, usage.total_space);
+            metrics.gauge("spacemap_filesystem_available_bytes", "Free space on the filesystem holding the scanned path");
+            metrics.sample(&path, usage.available_space);
+        }
+
+        metrics.gauge("spacemap_category_bytes", "Apparent size of the files in each category");
+        for bucket in &results.buckets {
+            metrics.sample(&[("path", scanned), ("category", bucket.label.as_str())], bucket.bytes);
+        }
+        metrics.gauge("spacemap_category_disk_bytes", "Space allocated on disk for the files in each category");
+        for bucket in &results.buckets {
+            metrics.sample(&[("path", scanned), ("category", bucket.label.as_str())], bucket.disk_bytes);
+        }
+        metrics.gauge("spacemap_category_files", "Number of files in each category");
+        for bucket in &results.buckets {
+            metrics.sample(&[("path", scanned), ("category", bucket.label.as_str())], bucket.file_count);
+        }
+
+        if !results.top_dirs.is_empty() {
+            metrics.gauge("spacemap_directory_bytes", "Apparent size of the largest directories");
+            for dir in &results.top_dirs {
+                metrics.sample(&[("path", scanned), ("directory", dir.path.as_str())], dir.bytes);
+            }
+            metrics.gauge("spacemap_directory_disk_bytes", "Space allocated on disk for the largest directories");
+            for dir in &results.top_dirs {
+                metrics.sample(&[("path", scanned), ("directory", dir.path.as_str())], dir.disk_bytes);
+            }
+        }
+
+        metrics.into_text()
+    }
+}
+
+impl Default for PrometheusRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for the text exposition format.
+#[derive(Default)]
+pub struct Metrics {
+    text: String,
+    current: String,
+}
+
+impl Metrics {
+    /// Start a gauge; its samples follow with [`Metrics::sample`].
+    pub fn gauge(&mut self, name: &str, help: &str) {
+        self.family(name, help, "gauge");
+    }
+
+    /// Start a counter, a value that only goes up; `name` should end in `_total`.
+    pub fn counter(&mut self, name: &str, help: &str) {
+        self.family(name, help, "counter");
+    }
+
+    fn family(&mut self, name: &str, help: &str, kind: &str) {
+        self.text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+        self.current = name.to_string();
+    }
+
+    pub fn sample<V: std::fmt::Display>(&mut self, labels: &[(&str, impl AsRef<str>)], value: V) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value.as_ref())))
+            .collect();
+        self.text.push_str(&format!("{}{{{}}} {}\n", self.current, labels.join(","), value));
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Writes buckets, top files, top directories, warnings and duplicate groups
+/// as one CSV (or TSV) table each.
+pub struct CsvRenderer {
+    delimiter: u8,
+}
+
+impl CsvRenderer {
+    pub fn csv() -> Self {
+        Self { delimiter: b',' }
+    }
+
+    pub fn tsv() -> Self {
+        Self { delimiter: b'\t' }
+    }
+
+    fn extension(&self) -> &'static str {
+        if self.delimiter == b'\t' {
+            "tsv"
+        } else {
+            "csv"
+        }
+    }
+
+    /// Write every section to its own file in `output_dir` (created if
+    /// needed), e.g. `buckets.csv`, or all of them to stdout, each preceded by
+    /// a `# <section>` line and followed by a blank line.
+    pub fn render(&self, results: &ScanResults, output_dir: Option<&std::path::Path>) -> io::Result<()> {
+        let sections = self.sections(results)?;
+
+        match output_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                for (name, table) in sections {
+                    std::fs::write(dir.join(format!("{}.{}", name, self.extension())), table)?;
+                }
+            }
+            None => {
+                let mut stdout = io::stdout().lock();
+                for (name, table) in sections {
+                    writeln!(stdout, "# {}", name)?;
+                    stdout.write_all(&table)?;
+                    writeln!(stdout)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The tables, in output order, as section name and encoded rows.
+    pub fn sections(&self, results: &ScanResults) -> io::Result<Vec<(&'static str, Vec<u8>)>> {
+        let mut sections = Vec::new();
+
+        let mut table = self.writer(&["key", "label", "bytes", "disk_bytes", "percent", "file_count"]);
+        for bucket in &results.buckets {
+            table.write_record([
+                bucket.key.clone(),
+                bucket.label.clone(),
+                bucket.bytes.to_string(),
+                bucket.disk_bytes.to_string(),
+                format!("{:.2}", bucket.percent),
+                bucket.file_count.to_string(),
+            ])?;
+        }
+        sections.push(("buckets", finish(table)?));
+
+        let mut table = self.writer(&["path", "bytes", "disk_bytes"]);
+        for file in &results.top_files {
+            table.write_record([&file.path, &file.bytes.to_string(), &file.disk_bytes.to_string()])?;
+        }
+        sections.push(("top_files", finish(table)?));
+
+        let mut table = self.writer(&["path", "bytes", "disk_bytes"]);
+        for dir in &results.top_dirs {
+            table.write_record([&dir.path, &dir.bytes.to_string(), &dir.disk_bytes.to_string()])?;
+        }
+        sections.push(("top_dirs", finish(table)?));
+
+        let mut table = self.writer(&["path", "error"]);
+        for warning in &results.warnings {
+            table.write_record([&warning.path, &warning.error])?;
+        }
+        sections.push(("warnings", finish(table)?));
+
+        // One row per file, with the group it belongs to
+        let mut table = self.writer(&["group", "size", "hash", "wasted_space", "path"]);
+        for (i, group) in results.duplicates.iter().flatten().enumerate() {
+            for path in &group.paths {
+                table.write_record([
+                    &(i + 1).to_string(),
+                    &group.size.to_string(),
+                    &group.hash,
+                    &group.wasted_space.to_string(),
+                    path,
+                ])?;
+            }
+        }
+        sections.push(("duplicates", finish(table)?));
+
+        Ok(sections)
+    }
+
+    fn writer(&self, header: &[&str]) -> csv::Writer<Vec<u8>> {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(Vec::new());
+        writer
+            .write_record(header)
+            .expect("writing to memory doesn't fail");
+        writer
+    }
+}
+
+fn finish(writer: csv::Writer<Vec<u8>>) -> io::Result<Vec<u8>> {
+    writer.into_inner().map_err(|e| e.into_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DirTotals, DuplicateGroup, Totals};
+
+    fn results() -> ScanResults {
+        ScanResults {
+            scanned_path: "/data".to_string(),
+            mode: "type".to_string(),
+            size_mode: SizeMode::Apparent,
+            dir_totals: DirTotals::Cumulative,
+            totals: Totals::default(),
+            disk_usage: None,
+            mounts: Vec::new(),
+            buckets: vec![Bucket {
+                key: "Code".to_string(),
+                label: "Code".to_string(),
+                bytes: 300,
+                disk_bytes: 4096,
+                percent: 100.0,
+                file_count: 2,
+                color: None,
+                representative_extension: None,
+                content_files: 0,
+                content_bytes: 0,
+            }],
+            top_files: vec![FileEntry {
+                path: "/data/a, \"quoted\"\nname.rs".to_string(),
+                bytes: 200,
+                disk_bytes: 4096,
+            }],
+            top_dirs: vec![DirEntry {
+                path: "/data\tsrc".to_string(),
+                bytes: 300,
+                disk_bytes: 8192,
+            }],
+            warnings: vec![Warning {
+                path: "/data/secret".to_string(),
+                error: "Permission denied".to_string(),
+            }],
+            skipped_mounts: Vec::new(),
+            duplicates: Some(vec![DuplicateGroup {
+                size: 10,
+                hash: "abc".to_string(),
+                paths: vec!["/data/x".to_string(), "/data/y".to_string()],
+                wasted_space: 10,
+            }]),
+            cache: None,
+            cleanup: None,
+            policy: None,
+        }
+    }
+
+    fn section(renderer: &CsvRenderer, name: &str) -> String {
+        let sections = renderer.sections(&results()).unwrap();
+        let (_, table) = sections.into_iter().find(|(n, _)| *n == name).unwrap();
+        String::from_utf8(table).unwrap()
+    }
+
+    #[test]
+    fn test_csv_sections_and_quoting() {
+        let csv = CsvRenderer::csv();
+        assert_eq!(
+            section(&csv, "buckets"),
+            "key,label,bytes,disk_bytes,percent,file_count\nCode,Code,300,4096,100.00,2\n"
+        );
+        assert_eq!(
+            section(&csv, "top_files"),
+            "path,bytes,disk_bytes\n\"/data/a, \"\"quoted\"\"\nname.rs\",200,4096\n"
+        );
+        assert_eq!(
+            section(&csv, "duplicates"),
+            "group,size,hash,wasted_space,path\n1,10,abc,10,/data/x\n1,10,abc,10,/data/y\n"
+        );
+        assert_eq!(section(&csv, "warnings"), "path,error\n/data/secret,Permission denied\n");
+
+        // Quoted fields read back unchanged
+        let table = section(&csv, "top_files");
+        let mut reader = csv::Reader::from_reader(table.as_bytes());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(&record[0], "/data/a, \"quoted\"\nname.rs");
+    }
+
+    #[test]
+    fn test_tsv_quotes_tabs() {
+        let tsv = CsvRenderer::tsv();
+        assert_eq!(section(&tsv, "top_dirs"), "path\tbytes\tdisk_bytes\n\"/data\tsrc\"\t300\t8192\n");
+    }
+
+    #[test]
+    fn test_writes_one_file_per_section() {
+        let dir = tempfile::TempDir::new().unwrap();
+        CsvRenderer::tsv().render(&results(), Some(dir.path())).unwrap();
+
+        let mut files: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["buckets.tsv", "duplicates.tsv", "top_dirs.tsv", "top_files.tsv", "warnings.tsv"]);
+    }
+
+    fn html_tree() -> FileTree {
+        let mut tree = FileTree::new(std::path::Path::new("/data"), Box::new(crate::categorize::TypeCategorizer::new()));
+        for (path, size) in [("/data/src/main.rs", 300_000), ("/data/src/lib.rs", 200_000), ("/data/notes.txt", 10)] {
+            let path = std::path::PathBuf::from(path);
+            tree.insert(&crate::types::FileMetadata {
+                extension: path.extension().and_then(|e| e.to_str()).map(|s| s.to_string()),
+                path,
+                size,
+                disk_size: size,
+                modified: None,
+            });
+        }
+        tree
+    }
+
+    fn report_data(html: &str) -> serde_json::Value {
+        let start = html.find("id=\"report-data\">").unwrap() + "id=\"report-data\">".len();
+        let end = start + html[start..].find("</script>").unwrap();
+        serde_json::from_str(&html[start..end]).unwrap()
+    }
+
+    #[test]
+    fn test_prometheus_gauges() {
+        let mut results = results();
+        results.totals.total_bytes = 300;
+        results.totals.file_count = 2;
+        let text = PrometheusRenderer::new().to_text(&results);
+
+        assert!(text.starts_with("# HELP spacemap_bytes Apparent size of all scanned files\n# TYPE spacemap_bytes gauge\n"));
+        assert!(text.contains("spacemap_bytes{path=\"/data\"} 300\n"));
+        assert!(text.contains("spacemap_files{path=\"/data\"} 2\n"));
+        assert!(text.contains("spacemap_category_disk_bytes{path=\"/data\",category=\"Code\"} 4096\n"));
+        assert!(text.contains("spacemap_directory_bytes{path=\"/data\",directory=\"/data\tsrc\"} 300\n"));
+
+        results.scanned_path = "C:\\data \"x\"\n".to_string();
+        let text = PrometheusRenderer::new().to_text(&results);
+        assert!(text.contains("spacemap_files{path=\"C:\\\\data \\\"x\\\"\\n\"} 2\n"));
+    }
+
+    #[test]
+    fn test_html_report_data() {
+        let tree = html_tree();
+        let html = HtmlRenderer::new().to_html(&results(), Some(&tree));
+        let data = report_data(&html);
+
+        assert_eq!(data["buckets"][0]["label"], "Code");
+        assert!(data["buckets"][0]["color"].as_str().unwrap().starts_with('#'));
+        assert_eq!(data["top_dirs"][0]["path"], "/data\tsrc");
+        assert_eq!(data["duplicates"][0]["paths"][1], "/data/y");
+
+        // Small entries are merged, large ones keep their category
+        let root = &data["tree"];
+        assert_eq!(root["s"], 500_010);
+        assert_eq!(root["c"][0]["n"], "src");
+        assert_eq!(root["c"][0]["c"][0]["n"], "main.rs");
+        assert_eq!(root["c"][0]["c"][0]["t"], "Code");
+        assert_eq!(root["c"][1]["n"], "(1 smaller items)");
+        assert_eq!(root["c"][1]["o"], true);
+    }
+
+    #[test]
+    fn test_html_is_self_contained_and_escaped() {
+        let mut results = results();
+        results.scanned_path = "/data/<b>".to_string();
+        results.top_files[0].path = "/data/</script><script>alert(1)</script>".to_string();
+        let html = HtmlRenderer::new().to_html(&results, None);
+
+        assert!(!html.contains("{{"));
+        assert!(html.contains("<title>spacemap: /data/&lt;b&gt;</title>"));
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert_eq!(report_data(&html)["top_files"][0]["path"], results.top_files[0].path);
+        assert!(report_data(&html)["tree"].is_null());
+        for reference in ["src=", "href=", "@import", "url("] {
+            assert!(!html.contains(reference), "external reference: {}", reference);
+        }
+
+        // Markers in the path stay in the title
+        results.scanned_path = "/data/{{DATA}}".to_string();
+        let html = HtmlRenderer::new().to_html(&results, None);
+        assert!(html.contains("<title>spacemap: /data/{{DATA}}</title>"));
+        assert_eq!(report_data(&html)["path"], "/data/{{DATA}}");
+    }
+}
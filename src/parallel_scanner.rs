@@ -1,30 +1,37 @@
-use crate::categorize::Categorizer;
 use crate::collector::SinglePassCollector;
+use crate::exclude::ExcludeMatcher;
+use crate::hardlinks::HardLinkTracker;
+use crate::mounts::DeviceTally;
 use crate::progress::ScanProgress;
-use crate::scanner::ScanStats;
-use crate::types::{FileMetadata, Warning};
+use crate::scanner::{allocated_size, device_id, root_device, ScanStats};
+use crate::types::{FileMetadata, SkippedMount, Warning};
 use jwalk::WalkDir;
 use parking_lot::Mutex;
 use rayon::prelude::*;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 /// Thread-local state: collector + statistics
 struct ThreadState {
     collector: SinglePassCollector,
     total_bytes: u64,
+    disk_bytes: u64,
+    hardlink_bytes: u64,
     file_count: u64,
     dir_count: u64,
+    devices: DeviceTally,
 }
 
 /// Parallel directory scanner using jwalk and rayon.
-/// Uses a shared collector protected by parking_lot::Mutex for simplicity.
+/// Each rayon fold gets its own collector; they are merged once at the end.
 pub struct ParallelScanner {
     num_threads: usize,
     follow_symlinks: bool,
     max_depth: Option<usize>,
-    exclude_patterns: Vec<String>,
+    exclude: Arc<ExcludeMatcher>,
     need_modified: bool,
+    one_file_system: bool,
 }
 
 impl ParallelScanner {
@@ -32,8 +39,9 @@ impl ParallelScanner {
         num_threads: usize,
         follow_symlinks: bool,
         max_depth: Option<usize>,
-        exclude_patterns: Vec<String>,
+        exclude: Arc<ExcludeMatcher>,
         need_modified: bool,
+        one_file_system: bool,
     ) -> Self {
         // Auto-detect thread count if 0
         let num_threads = if num_threads == 0 {
@@ -46,25 +54,30 @@ impl ParallelScanner {
             num_threads,
             follow_symlinks,
             max_depth,
-            exclude_patterns,
+            exclude,
             need_modified,
+            one_file_system,
         }
     }
 
-    pub fn scan<F>(
+    /// Walk `path` in parallel.
+    ///
+    /// `new_collector` is called once per rayon fold to create the thread-local
+    /// collector; the collectors are merged before being returned.
+    pub fn scan<C, F>(
         &self,
         path: &Path,
-        categorizer: Box<dyn Categorizer>,
-        top_n: usize,
-        should_collect_tops: bool,
+        new_collector: C,
         progress: &ScanProgress,
         callback: F,
     ) -> (ScanStats, SinglePassCollector)
     where
-        F: Fn(&FileMetadata) + Send + Sync,
+        C: Fn() -> SinglePassCollector + Send + Sync,
+        F: Fn(&FileMetadata, &str) + Send + Sync,
     {
         // Shared warnings (rare, so Mutex is fine)
         let warnings = Arc::new(Mutex::new(Vec::new()));
+        let skipped_mounts: Arc<Mutex<Vec<SkippedMount>>> = Arc::new(Mutex::new(Vec::new()));
 
         // Configure jwalk for parallel walking
         let mut walker = WalkDir::new(path)
@@ -76,43 +89,87 @@ impl ParallelScanner {
             walker = walker.max_depth(depth);
         }
 
-        // Clone for closure
-        let exclude_patterns = self.exclude_patterns.clone();
+        // Early directory pruning: drop excluded entries and other filesystems
+        // while jwalk reads each directory, so those subtrees are never
+        // descended into or stat'ed
+        let root_device = if self.one_file_system { root_device(path) } else { None };
+        if !self.exclude.is_empty() || root_device.is_some() {
+            let exclude = self.exclude.clone();
+            let skipped_mounts = skipped_mounts.clone();
+            walker = walker.process_read_dir(move |_depth, _dir, _state, children| {
+                children.retain(|child| {
+                    let Ok(entry) = child else {
+                        return true;
+                    };
+                    let is_dir = entry.file_type().is_dir();
+                    if exclude.is_excluded(&entry.path(), is_dir) {
+                        return false;
+                    }
+                    if let (Some(root), true) = (root_device, is_dir) {
+                        if let Some(device) = entry.metadata().ok().and_then(|m| device_id(&m)) {
+                            if device != root {
+                                skipped_mounts.lock().push(SkippedMount {
+                                    path: entry.path().display().to_string(),
+                                    device,
+                                });
+                                return false;
+                            }
+                        }
+                    }
+                    true
+                });
+            });
+        }
+
         let need_modified = self.need_modified;
 
+        // Shared across threads; only hard-linked files touch it, one shard at a time
+        let hard_links = HardLinkTracker::new();
+
+        // Running totals for progress; the per-fold stats are only summed at the end
+        let progress_files = AtomicU64::new(0);
+        let progress_bytes = AtomicU64::new(0);
+
         // Use rayon's fold to create thread-local state (collector + stats)
         let final_state = walker
             .into_iter()
             .par_bridge()
             .fold(
                 || ThreadState {
-                    collector: SinglePassCollector::new(categorizer.clone_box(), top_n, should_collect_tops),
+                    collector: new_collector(),
                     total_bytes: 0,
+                    disk_bytes: 0,
+                    hardlink_bytes: 0,
                     file_count: 0,
                     dir_count: 0,
+                    devices: DeviceTally::new(),
                 },
                 |mut state, entry_result| {
-                    let file_meta = match entry_result {
+                    match entry_result {
                         Ok(entry) => {
-                            // Check exclusion patterns
-                            if !exclude_patterns.is_empty() {
-                                let path_str = entry.path().to_string_lossy().to_string();
-                                if exclude_patterns.iter().any(|pattern| path_str.contains(pattern)) {
-                                    return state;
-                                }
-                            }
-
                             // Process the entry
                             if let Ok(metadata) = entry.metadata() {
                                 if metadata.is_dir() {
                                     state.dir_count += 1;
+                                    state.devices.add_dir(&metadata, &entry.path());
                                 } else if metadata.is_file() {
                                     let size = metadata.len();
+
+                                    // Count each hard-linked inode once
+                                    if !hard_links.first_seen(&metadata) {
+                                        state.hardlink_bytes += size;
+                                        return state;
+                                    }
+
+                                    let disk_size = allocated_size(&metadata);
                                     state.total_bytes += size;
+                                    state.disk_bytes += disk_size;
                                     state.file_count += 1;
 
-                                    let extension = entry
-                                        .path()
+                                    let path = entry.path();
+                                    state.devices.add_file(&metadata, &path, size, disk_size);
+
+                                    let extension = path
                                         .extension()
                                         .and_then(|e| e.to_str())
                                         .map(|s| s.to_lowercase());
@@ -125,15 +182,23 @@ impl ParallelScanner {
                                     };
 
                                     let file_meta = FileMetadata {
-                                        path: entry.path(),
+                                        path,
                                         size,
+                                        disk_size,
                                         extension,
                                         modified,
                                     };
 
                                     // Process directly into thread-local collector
-                                    state.collector.process_file(file_meta.clone());
-                                    callback(&file_meta);
+                                    let category = state.collector.process_file(&file_meta);
+                                    callback(&file_meta, &category);
+
+                                    // Update progress every 1000 files to avoid overhead
+                                    let files = progress_files.fetch_add(1, Ordering::Relaxed) + 1;
+                                    let bytes = progress_bytes.fetch_add(size, Ordering::Relaxed) + size;
+                                    if files.is_multiple_of(1000) {
+                                        progress.update(files, bytes, file_meta.path.to_str().unwrap_or(""));
+                                    }
                                 }
                             } else {
                                 warnings.lock().push(Warning {
@@ -157,31 +222,47 @@ impl ParallelScanner {
             )
             .reduce(
                 || ThreadState {
-                    collector: SinglePassCollector::new(categorizer.clone_box(), top_n, should_collect_tops),
+                    collector: new_collector(),
                     total_bytes: 0,
+                    disk_bytes: 0,
+                    hardlink_bytes: 0,
                     file_count: 0,
                     dir_count: 0,
+                    devices: DeviceTally::new(),
                 },
                 |mut acc, other| {
                     // Merge collectors
                     acc.collector.merge(other.collector);
                     // Sum statistics
                     acc.total_bytes += other.total_bytes;
+                    acc.disk_bytes += other.disk_bytes;
+                    acc.hardlink_bytes += other.hardlink_bytes;
                     acc.file_count += other.file_count;
                     acc.dir_count += other.dir_count;
+                    acc.devices.merge(other.devices);
                     acc
                 },
             );
 
+        progress.update(final_state.file_count, final_state.total_bytes, path.to_str().unwrap_or(""));
         progress.finish();
 
         let stats = ScanStats {
             total_bytes: final_state.total_bytes,
+            disk_bytes: final_state.disk_bytes,
+            hardlink_bytes: final_state.hardlink_bytes,
             file_count: final_state.file_count,
             dir_count: final_state.dir_count,
+            devices: final_state.devices,
             warnings: Arc::try_unwrap(warnings)
                 .unwrap_or_else(|_| panic!("Failed to unwrap warnings"))
                 .into_inner(),
+            skipped_mounts: {
+                // Directories are read concurrently, so order by path for stable output
+                let mut mounts = std::mem::take(&mut *skipped_mounts.lock());
+                mounts.sort_by(|a, b| a.path.cmp(&b.path));
+                mounts
+            },
         };
 
         (stats, final_state.collector)
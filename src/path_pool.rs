@@ -8,6 +8,12 @@ pub struct PathPool {
     index: HashMap<PathBuf, u32>,      // Path -> ID mapping
 }
 
+impl Default for PathPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PathPool {
     pub fn new() -> Self {
         Self {
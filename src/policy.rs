@@ -0,0 +1,512 @@
+//! Storage policies (`[[policy]]` in the config) checked with `--check-policy`.
+//!
+//! Each policy selects files by path and/or type category and sets limits on
+//! them. Files are checked as they are scanned; the outcome ends up in
+//! `ScanResults::policy` and can be written as JUnit XML for CI.
+
+use crate::categorize::Categorizer;
+use crate::exclude::ExcludeMatcher;
+use crate::types::{FileMetadata, SizeMode};
+use parking_lot::Mutex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Violations kept per check; the rest are only counted.
+const MAX_VIOLATIONS: usize = 100;
+
+/// One `[[policy]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    pub name: String,
+    /// Files the policy applies to, as a gitignore-style glob relative to the
+    /// scan root (like `--exclude`); all files if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Only files of this type category (as with `--by type`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Budget for all selected files together
+    #[serde(default, deserialize_with = "deserialize_size", skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// Limit for each selected file
+    #[serde(default, deserialize_with = "deserialize_size", skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden_extensions: Vec<String>,
+    /// Selected files must have been modified within this many days
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+}
+
+/// Sizes are either a number of bytes or a string like `"500M"` or `"1.5GiB"`
+/// (K, M, G and T are powers of 1024).
+pub(crate) fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => parse_size(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size '{}'", text))?;
+    let shift = match unit.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("invalid size unit in '{}'", text)),
+    };
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// What a check limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckKind {
+    MaxBytes,
+    MaxFileSize,
+    ForbiddenExtensions,
+    MaxAgeDays,
+}
+
+impl CheckKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckKind::MaxBytes => "max_bytes",
+            CheckKind::MaxFileSize => "max_file_size",
+            CheckKind::ForbiddenExtensions => "forbidden_extensions",
+            CheckKind::MaxAgeDays => "max_age_days",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Limit {
+    /// Bytes, or days for `max_age_days`
+    Number(u64),
+    Extensions(Vec<String>),
+}
+
+/// A path that broke a limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub path: String,
+    /// Bytes, or days since modification for `max_age_days`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<u64>,
+}
+
+/// The outcome of one limit of one policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyCheck {
+    pub policy: String,
+    pub check: CheckKind,
+    pub limit: Limit,
+    pub violation_count: u64,
+    /// The first violations found, up to 100
+    pub violations: Vec<Violation>,
+}
+
+impl PolicyCheck {
+    pub fn passed(&self) -> bool {
+        self.violation_count == 0
+    }
+
+    fn violate(&mut self, path: &Path, value: Option<u64>) {
+        self.violation_count += 1;
+        if self.violations.len() < MAX_VIOLATIONS {
+            self.violations.push(Violation {
+                path: path.display().to_string(),
+                value,
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyReport {
+    pub checks: Vec<PolicyCheck>,
+}
+
+impl PolicyReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed())
+    }
+
+    pub fn failed_checks(&self) -> impl Iterator<Item = &PolicyCheck> {
+        self.checks.iter().filter(|c| !c.passed())
+    }
+
+    /// The report as a JUnit XML document with one test case per check.
+    pub fn to_junit(&self, scanned_path: &str) -> String {
+        let failures = self.failed_checks().count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"spacemap\" tests=\"{}\" failures=\"{}\">\n",
+            self.checks.len(),
+            failures
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            escape_xml(&format!("spacemap policy: {}", scanned_path)),
+            self.checks.len(),
+            failures
+        ));
+
+        for check in &self.checks {
+            let name = escape_xml(&format!("{} ({})", check.policy, check.check.as_str()));
+            if check.passed() {
+                xml.push_str(&format!("    <testcase classname=\"spacemap.policy\" name=\"{}\"/>\n", name));
+                continue;
+            }
+
+            let mut body: Vec<String> = check
+                .violations
+                .iter()
+                .map(|v| match v.value {
+                    Some(value) => format!("{}: {}", v.path, value),
+                    None => v.path.clone(),
+                })
+                .collect();
+            let omitted = check.violation_count - check.violations.len() as u64;
+            if omitted > 0 {
+                body.push(format!("...and {} more", omitted));
+            }
+
+            xml.push_str(&format!("    <testcase classname=\"spacemap.policy\" name=\"{}\">\n", name));
+            xml.push_str(&format!(
+                "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                check.check.as_str(),
+                escape_xml(&format!("{} violation(s), limit {}", check.violation_count, limit_text(&check.limit))),
+                escape_xml(&body.join("\n"))
+            ));
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+pub fn limit_text(limit: &Limit) -> String {
+    match limit {
+        Limit::Number(n) => n.to_string(),
+        Limit::Extensions(extensions) => extensions.join(", "),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// An invalid `[[policy]]` entry.
+#[derive(Debug)]
+pub struct PolicyError {
+    pub policy: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid policy '{}': {}", self.policy, self.message)
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+struct CompiledRule {
+    rule: PolicyRule,
+    paths: Option<ExcludeMatcher>,
+    forbidden: Vec<String>,
+}
+
+struct Totals {
+    bytes: u64,
+    disk_bytes: u64,
+}
+
+/// Checks every scanned file against the policies.
+pub struct PolicyChecker {
+    rules: Vec<CompiledRule>,
+    categorizer: Option<Box<dyn Categorizer>>,
+    root: String,
+    size_mode: SizeMode,
+    now: SystemTime,
+    /// Per rule: the selected files' totals and its checks, in rule order
+    state: Mutex<Vec<(Totals, Vec<PolicyCheck>)>>,
+}
+
+impl PolicyChecker {
+    /// Checker for `rules` below `root`. `categorizer` files files into the
+    /// type categories that `category` policies select.
+    pub fn new(root: &Path, rules: &[PolicyRule], categorizer: Box<dyn Categorizer>, size_mode: SizeMode) -> Result<Self, PolicyError> {
+        let mut compiled = Vec::new();
+        let mut state = Vec::new();
+
+        for rule in rules {
+            let invalid = |message: String| PolicyError {
+                policy: rule.name.clone(),
+                message,
+            };
+
+            let paths = match rule.path {
+                Some(ref pattern) => Some(
+                    ExcludeMatcher::new(root, std::slice::from_ref(pattern))
+                        .map_err(|e| invalid(format!("path '{}': {}", e.pattern, e.message)))?,
+                ),
+                None => None,
+            };
+
+            let mut checks = Vec::new();
+            let mut check = |kind, limit| {
+                checks.push(PolicyCheck {
+                    policy: rule.name.clone(),
+                    check: kind,
+                    limit,
+                    violation_count: 0,
+                    violations: Vec::new(),
+                })
+            };
+            if let Some(max) = rule.max_bytes {
+                check(CheckKind::MaxBytes, Limit::Number(max));
+            }
+            if let Some(max) = rule.max_file_size {
+                check(CheckKind::MaxFileSize, Limit::Number(max));
+            }
+            if !rule.forbidden_extensions.is_empty() {
+                check(CheckKind::ForbiddenExtensions, Limit::Extensions(rule.forbidden_extensions.clone()));
+            }
+            if let Some(days) = rule.max_age_days {
+                check(CheckKind::MaxAgeDays, Limit::Number(days));
+            }
+            if checks.is_empty() {
+                return Err(invalid("sets no limit".to_string()));
+            }
+
+            state.push((Totals { bytes: 0, disk_bytes: 0 }, checks));
+            compiled.push(CompiledRule {
+                paths,
+                forbidden: rule
+                    .forbidden_extensions
+                    .iter()
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .collect(),
+                rule: rule.clone(),
+            });
+        }
+
+        let needs_category = rules.iter().any(|r| r.category.is_some());
+        Ok(Self {
+            rules: compiled,
+            categorizer: needs_category.then_some(categorizer),
+            root: root.display().to_string(),
+            size_mode,
+            now: SystemTime::now(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Whether any policy limits file age, so files need modification times.
+    pub fn needs_modified(&self) -> bool {
+        self.rules.iter().any(|r| r.rule.max_age_days.is_some())
+            || self.categorizer.as_ref().is_some_and(|c| c.needs_modified())
+    }
+
+    pub fn add_file(&self, file: &FileMetadata) {
+        let category = self.categorizer.as_ref().map(|c| c.categorize(file));
+
+        for (i, compiled) in self.rules.iter().enumerate() {
+            if let Some(ref paths) = compiled.paths {
+                if !paths.is_excluded(&file.path, false) {
+                    continue;
+                }
+            }
+            if let (Some(wanted), Some(category)) = (&compiled.rule.category, &category) {
+                if !wanted.eq_ignore_ascii_case(category) {
+                    continue;
+                }
+            }
+
+            let mut state = self.state.lock();
+            let (totals, checks) = &mut state[i];
+            totals.bytes += file.size;
+            totals.disk_bytes += file.disk_size;
+
+            for check in checks.iter_mut() {
+                match (check.check, &check.limit) {
+                    (CheckKind::MaxFileSize, Limit::Number(max)) => {
+                        let size = self.size_mode.select(file.size, file.disk_size);
+                        if size > *max {
+                            check.violate(&file.path, Some(size));
+                        }
+                    }
+                    (CheckKind::ForbiddenExtensions, _)
+                        if file.extension.as_ref().is_some_and(|e| compiled.forbidden.contains(e)) =>
+                    {
+                        check.violate(&file.path, None);
+                    }
+                    (CheckKind::MaxAgeDays, Limit::Number(max)) => {
+                        let days = file
+                            .modified
+                            .and_then(|m| self.now.duration_since(m).ok())
+                            .map(|d| d.as_secs() / 86400);
+                        if let Some(days) = days.filter(|d| d > max) {
+                            check.violate(&file.path, Some(days));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Check the budgets and return every check's outcome.
+    pub fn finish(self) -> PolicyReport {
+        let mut report = PolicyReport::default();
+        for (compiled, (totals, mut checks)) in self.rules.iter().zip(self.state.into_inner()) {
+            let bytes = self.size_mode.select(totals.bytes, totals.disk_bytes);
+            for check in &mut checks {
+                if let (CheckKind::MaxBytes, Limit::Number(max)) = (check.check, &check.limit) {
+                    if bytes > *max {
+                        let path = compiled.rule.path.clone().unwrap_or_else(|| self.root.clone());
+                        check.violate(Path::new(&path), Some(bytes));
+                    }
+                }
+            }
+            report.checks.extend(checks);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categorize::TypeCategorizer;
+    use crate::config::SpacemapConfig;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn file(path: &str, size: u64, days_old: u64) -> FileMetadata {
+        let path = PathBuf::from(path);
+        FileMetadata {
+            extension: path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()),
+            path,
+            size,
+            disk_size: size,
+            modified: Some(SystemTime::now() - Duration::from_secs(days_old * 86400 + 60)),
+        }
+    }
+
+    fn rules(toml: &str) -> Vec<PolicyRule> {
+        toml::from_str::<SpacemapConfig>(toml).unwrap().policy
+    }
+
+    fn categorizer() -> Box<dyn Categorizer> {
+        Box::new(TypeCategorizer::new())
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2K").unwrap(), 2048);
+        assert_eq!(parse_size("1.5 GiB").unwrap(), 1536 << 20);
+        assert_eq!(parse_size("10MB").unwrap(), 10 << 20);
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+
+    #[test]
+    fn test_checks() {
+        let rules = rules(
+            r#"
+            [[policy]]
+            name = "dist budget"
+            path = "dist"
+            max_bytes = "1K"
+
+            [[policy]]
+            name = "no binaries"
+            forbidden_extensions = [".exe", "dll"]
+            max_file_size = 600
+
+            [[policy]]
+            name = "videos"
+            category = "Videos"
+            max_bytes = 100
+            max_age_days = 30
+            "#,
+        );
+        let checker = PolicyChecker::new(Path::new("/repo"), &rules, categorizer(), SizeMode::Apparent).unwrap();
+        assert!(checker.needs_modified());
+
+        checker.add_file(&file("/repo/dist/app.js", 700, 0));
+        checker.add_file(&file("/repo/dist/app.exe", 500, 0));
+        checker.add_file(&file("/repo/src/main.rs", 100, 0));
+        checker.add_file(&file("/repo/media/intro.mp4", 50, 90));
+
+        let report = checker.finish();
+        assert!(!report.passed());
+        let outcome: Vec<(&str, CheckKind, u64)> = report
+            .checks
+            .iter()
+            .map(|c| (c.policy.as_str(), c.check, c.violation_count))
+            .collect();
+        assert_eq!(
+            outcome,
+            vec![
+                ("dist budget", CheckKind::MaxBytes, 1),
+                ("no binaries", CheckKind::MaxFileSize, 1),
+                ("no binaries", CheckKind::ForbiddenExtensions, 1),
+                ("videos", CheckKind::MaxBytes, 0),
+                ("videos", CheckKind::MaxAgeDays, 1),
+            ]
+        );
+        assert_eq!(report.checks[0].violations[0], Violation { path: "dist".to_string(), value: Some(1200) });
+        assert_eq!(report.checks[1].violations[0].path, "/repo/dist/app.js");
+        assert_eq!(report.checks[2].violations[0].path, "/repo/dist/app.exe");
+        assert_eq!(report.checks[4].violations[0].value, Some(90));
+    }
+
+    #[test]
+    fn test_rejects_invalid_rules() {
+        let no_limit = rules("[[policy]]\nname = \"empty\"\npath = \"dist\"\n");
+        let err = PolicyChecker::new(Path::new("/repo"), &no_limit, categorizer(), SizeMode::Apparent).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid policy 'empty': sets no limit");
+
+        let bad_glob = rules("[[policy]]\nname = \"glob\"\npath = \"a[\"\nmax_bytes = 1\n");
+        assert!(PolicyChecker::new(Path::new("/repo"), &bad_glob, categorizer(), SizeMode::Apparent).is_err());
+
+        assert!(toml::from_str::<SpacemapConfig>("[[policy]]\nname = \"x\"\nmax_bytes = \"lots\"\n").is_err());
+    }
+
+    #[test]
+    fn test_junit() {
+        let rules = rules("[[policy]]\nname = \"<dist>\"\nmax_file_size = 10\n\n[[policy]]\nname = \"ok\"\nmax_bytes = 1000\n");
+        let checker = PolicyChecker::new(Path::new("/repo"), &rules, categorizer(), SizeMode::Apparent).unwrap();
+        checker.add_file(&file("/repo/a & b.bin", 20, 0));
+
+        let xml = checker.finish().to_junit("/repo");
+        assert!(xml.contains("<testsuites name=\"spacemap\" tests=\"2\" failures=\"1\">"), "{}", xml);
+        assert!(xml.contains("name=\"&lt;dist&gt; (max_file_size)\""), "{}", xml);
+        assert!(xml.contains("<failure type=\"max_file_size\" message=\"1 violation(s), limit 10\">/repo/a &amp; b.bin: 20</failure>"), "{}", xml);
+        assert!(xml.contains("<testcase classname=\"spacemap.policy\" name=\"ok (max_bytes)\"/>"), "{}", xml);
+    }
+}
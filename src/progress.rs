@@ -1,8 +1,45 @@
 use indicatif::{ProgressBar, ProgressStyle};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 
 pub struct ScanProgress {
     bar: ProgressBar,
     enabled: bool,
+    state: Option<Arc<ProgressState>>,
+}
+
+/// Progress counters of a running scan, readable from other threads.
+#[derive(Debug, Default)]
+pub struct ProgressState {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    current_path: Mutex<String>,
+    finished: AtomicBool,
+}
+
+impl ProgressState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Files counted so far (updated every 1000 files)
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The last path the scanner reported
+    pub fn current_path(&self) -> String {
+        self.current_path.lock().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
 }
 
 impl ScanProgress {
@@ -11,6 +48,7 @@ impl ScanProgress {
             return Self {
                 bar: ProgressBar::hidden(),
                 enabled: false,
+                state: None,
             };
         }
 
@@ -21,10 +59,22 @@ impl ScanProgress {
                 .unwrap(),
         );
 
-        Self { bar, enabled: true }
+        Self { bar, enabled: true, state: None }
+    }
+
+    /// Also publish every update to `state`.
+    pub fn with_state(mut self, state: Option<Arc<ProgressState>>) -> Self {
+        self.state = state;
+        self
     }
 
     pub fn update(&self, files: u64, bytes: u64, current_path: &str) {
+        if let Some(ref state) = self.state {
+            state.files.store(files, Ordering::Relaxed);
+            state.bytes.store(bytes, Ordering::Relaxed);
+            *state.current_path.lock() = current_path.to_string();
+        }
+
         if self.enabled {
             // Truncate path if too long
             let display_path = if current_path.len() > 60 {
@@ -44,6 +94,9 @@ impl ScanProgress {
     }
 
     pub fn finish(&self) {
+        if let Some(ref state) = self.state {
+            state.finished.store(true, Ordering::Relaxed);
+        }
         if self.enabled {
             self.bar.finish_and_clear();
         }
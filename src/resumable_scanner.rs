@@ -0,0 +1,430 @@
+use crate::checkpoint::{PendingDir, ScanCheckpoint, ScanState};
+use crate::collector::SinglePassCollector;
+use crate::dir_reader::{count_file, dir_key, file_record, warning, DirReader};
+use crate::exclude::ExcludeMatcher;
+use crate::hardlinks::HardLinkTracker;
+use crate::progress::ScanProgress;
+use crate::scanner::{device_id, ScanStats};
+use crate::types::{FileMetadata, SkippedMount, Warning};
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Directories read per round. Checkpoints are only taken between rounds.
+const BATCH_SIZE: usize = 1024;
+
+/// `(dev, ino)` of the directories read so far, to break symlink cycles.
+#[derive(Default)]
+struct VisitedDirs(Mutex<HashSet<(u64, u64)>>);
+
+impl VisitedDirs {
+    fn from_keys(keys: Vec<(u64, u64)>) -> Self {
+        Self(Mutex::new(keys.into_iter().collect()))
+    }
+
+    /// Returns true the first time a directory is seen. Directories without
+    /// a key are always reported as new.
+    fn insert(&self, metadata: &fs::Metadata) -> bool {
+        match dir_key(metadata) {
+            Some(key) => self.0.lock().insert(key),
+            None => true,
+        }
+    }
+
+    fn keys(&self) -> Vec<(u64, u64)> {
+        self.0.lock().iter().copied().collect()
+    }
+}
+
+/// Scanner that keeps its queue of unread directories explicit, so a scan
+/// can be checkpointed and resumed.
+///
+/// Directories are taken from a stack in batches and each batch is read in
+/// parallel (on one thread for a sequential scan). Between batches every
+/// directory is either fully counted in the collector and statistics or
+/// still queued, which is exactly what a [`ScanCheckpoint`] records.
+pub struct ResumableScanner {
+    reader: DirReader,
+    num_threads: usize,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    need_modified: bool,
+    one_file_system: bool,
+    checkpoint_interval: Duration,
+}
+
+/// Per-worker results for one batch.
+struct BatchState {
+    collector: SinglePassCollector,
+    stats: ScanStats,
+    pending: Vec<PendingDir>,
+}
+
+impl BatchState {
+    fn new(collector: SinglePassCollector) -> Self {
+        Self {
+            collector,
+            stats: ScanStats::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn merge(mut self, other: BatchState) -> Self {
+        self.collector.merge(other.collector);
+        self.stats.merge(other.stats);
+        self.pending.extend(other.pending);
+        self
+    }
+}
+
+impl ResumableScanner {
+    pub fn new(
+        num_threads: usize,
+        follow_symlinks: bool,
+        max_depth: Option<usize>,
+        exclude: Arc<ExcludeMatcher>,
+        need_modified: bool,
+        one_file_system: bool,
+    ) -> Self {
+        // Auto-detect thread count if 0
+        let num_threads = if num_threads == 0 {
+            rayon::current_num_threads()
+        } else {
+            num_threads
+        };
+
+        Self {
+            reader: DirReader::new(follow_symlinks, exclude),
+            num_threads,
+            follow_symlinks,
+            max_depth,
+            need_modified,
+            one_file_system,
+            checkpoint_interval: Duration::from_secs(300),
+        }
+    }
+
+    /// How often the checkpoint is updated and saved (default: 5 minutes).
+    pub fn with_checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    /// Scan `path`, continuing from `checkpoint` if it holds state from an
+    /// earlier run.
+    ///
+    /// Every checkpoint interval the checkpoint is updated and handed to
+    /// `save`. Save errors are reported as warnings and the scan goes on.
+    /// `ScanState::duplicates` is left to `save`, since the caller owns the
+    /// duplicate finder.
+    pub fn scan<C, F, S>(
+        &self,
+        path: &Path,
+        checkpoint: &mut ScanCheckpoint,
+        new_collector: C,
+        progress: &ScanProgress,
+        callback: F,
+        mut save: S,
+    ) -> (ScanStats, SinglePassCollector)
+    where
+        C: Fn() -> SinglePassCollector + Send + Sync,
+        F: Fn(&FileMetadata, &str) + Send + Sync,
+        S: FnMut(&mut ScanCheckpoint) -> io::Result<()>,
+    {
+        let mut collector = new_collector();
+
+        let root_metadata = match self.reader.metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let stats = ScanStats {
+                    warnings: vec![warning(path, &e)],
+                    ..ScanStats::default()
+                };
+                progress.finish();
+                return (stats, collector);
+            }
+        };
+
+        // A single file: nothing to checkpoint
+        if !root_metadata.is_dir() {
+            let mut stats = ScanStats::default();
+            if root_metadata.is_file() {
+                let name = path.file_name().unwrap_or(path.as_os_str()).to_os_string();
+                let file = file_record(name, &root_metadata);
+                let parent = path.parent().unwrap_or(Path::new(""));
+                let hard_links = HardLinkTracker::new();
+                let device = device_id(&root_metadata);
+                if let Some(meta) = count_file(parent, device, &file, self.need_modified, &hard_links, &mut stats) {
+                    let category = collector.process_file(&meta);
+                    callback(&meta, &category);
+                }
+            }
+            progress.finish();
+            return (stats, collector);
+        }
+
+        let (mut stats, hard_links, visited, mut pending) = match checkpoint.state.take() {
+            Some(state) => {
+                collector.restore(state.collector);
+                (
+                    state.stats,
+                    HardLinkTracker::from_keys(state.hard_links),
+                    VisitedDirs::from_keys(state.visited_dirs),
+                    std::mem::take(&mut checkpoint.pending),
+                )
+            }
+            None => (
+                ScanStats::default(),
+                HardLinkTracker::new(),
+                VisitedDirs::default(),
+                vec![PendingDir {
+                    path: path.to_path_buf(),
+                    depth: 0,
+                }],
+            ),
+        };
+
+        let root_device = device_id(&root_metadata);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.num_threads)
+            .build()
+            .expect("failed to build scan thread pool");
+        let mut last_checkpoint = Instant::now();
+
+        while !pending.is_empty() {
+            // Take from the top of the stack so the queue stays depth-first and small
+            let batch = pending.split_off(pending.len().saturating_sub(BATCH_SIZE));
+
+            let result = pool.install(|| {
+                batch
+                    .into_par_iter()
+                    .fold(
+                        || BatchState::new(new_collector()),
+                        |mut state, dir| {
+                            self.visit(dir, root_device, &hard_links, &visited, &callback, &mut state);
+                            state
+                        },
+                    )
+                    .reduce(|| BatchState::new(new_collector()), BatchState::merge)
+            });
+
+            collector.merge(result.collector);
+            stats.merge(result.stats);
+            pending.extend(result.pending);
+            progress.update(stats.file_count, stats.total_bytes, path.to_str().unwrap_or(""));
+
+            if !pending.is_empty() && last_checkpoint.elapsed() >= self.checkpoint_interval {
+                checkpoint.update(
+                    pending.clone(),
+                    ScanState {
+                        stats: stats.clone(),
+                        collector: collector.state(),
+                        hard_links: hard_links.keys(),
+                        visited_dirs: visited.keys(),
+                        duplicates: None,
+                        cleanup: None,
+                    },
+                );
+                if let Err(e) = save(checkpoint) {
+                    stats.warnings.push(Warning {
+                        path: checkpoint.scanned_path.display().to_string(),
+                        error: format!("Failed to write checkpoint: {}", e),
+                    });
+                }
+                last_checkpoint = Instant::now();
+            }
+        }
+
+        // Batches finish in any order; keep the report stable
+        stats.skipped_mounts.sort_by(|a, b| a.path.cmp(&b.path));
+
+        progress.finish();
+        (stats, collector)
+    }
+
+    /// Count one directory's files and queue its subdirectories.
+    fn visit<F>(
+        &self,
+        dir: PendingDir,
+        root_device: Option<u64>,
+        hard_links: &HardLinkTracker,
+        visited: &VisitedDirs,
+        callback: &F,
+        state: &mut BatchState,
+    ) where
+        F: Fn(&FileMetadata, &str),
+    {
+        let metadata = match self.reader.metadata(&dir.path) {
+            Ok(m) if m.is_dir() => m,
+            Ok(_) => return,
+            Err(e) => {
+                state.stats.warnings.push(warning(&dir.path, &e));
+                return;
+            }
+        };
+
+        let device = device_id(&metadata);
+        if self.one_file_system && dir.depth > 0 {
+            if let (Some(root), Some(device)) = (root_device, device) {
+                if root != device {
+                    state.stats.skipped_mounts.push(SkippedMount {
+                        path: dir.path.display().to_string(),
+                        device,
+                    });
+                    return;
+                }
+            }
+        }
+
+        // Symlinked directories can form cycles; only tracked when following links
+        if self.follow_symlinks && !visited.insert(&metadata) {
+            return;
+        }
+
+        state.stats.dir_count += 1;
+        state.stats.devices.add_dir(&metadata, &dir.path);
+        if self.max_depth.is_some_and(|max| dir.depth >= max) {
+            return;
+        }
+
+        let entries = match self.reader.read_dir(&dir.path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                state.stats.warnings.push(warning(&dir.path, &e));
+                return;
+            }
+        };
+
+        let (files, subdirs) = self.reader.read(entries, &mut state.stats.warnings);
+
+        for file in &files {
+            if let Some(meta) = count_file(&dir.path, device, file, self.need_modified, hard_links, &mut state.stats) {
+                let category = state.collector.process_file(&meta);
+                callback(&meta, &category);
+            }
+        }
+
+        for name in subdirs {
+            state.pending.push(PendingDir {
+                path: dir.path.join(name),
+                depth: dir.depth + 1,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categorize::TypeCategorizer;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn create_tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for i in 0..5 {
+            let sub = dir.path().join(format!("d{}", i));
+            fs::create_dir_all(sub.join("inner")).unwrap();
+            fs::write(sub.join("a.txt"), vec![0u8; 10 * (i + 1)]).unwrap();
+            fs::write(sub.join("inner/b.rs"), vec![0u8; 7]).unwrap();
+        }
+        dir
+    }
+
+    fn new_collector() -> SinglePassCollector {
+        SinglePassCollector::new(Box::new(TypeCategorizer::new()), 10, true)
+    }
+
+    fn scanner(threads: usize, root: &Path) -> ResumableScanner {
+        ResumableScanner::new(threads, false, None, Arc::new(ExcludeMatcher::empty(root)), false, false)
+    }
+
+    #[test]
+    fn test_full_scan_counts_everything() {
+        let tree = create_tree();
+        for threads in [1, 4] {
+            let mut checkpoint = ScanCheckpoint::new(tree.path().to_path_buf(), Vec::new());
+            let (stats, _) = scanner(threads, tree.path()).scan(
+                tree.path(),
+                &mut checkpoint,
+                new_collector,
+                &ScanProgress::new(false),
+                |_, _| {},
+                |_| Ok(()),
+            );
+
+            assert_eq!(stats.total_bytes, 150 + 35);
+            assert_eq!(stats.file_count, 10);
+            assert_eq!(stats.dir_count, 11);
+        }
+    }
+
+    #[test]
+    fn test_resume_skips_completed_directories() {
+        let tree = create_tree();
+        let root = tree.path();
+
+        // Pretend an earlier run finished the root and d0..d2, leaving d3 and d4 queued
+        let mut earlier = ScanStats::default();
+        let mut collector = new_collector();
+        let hard_links = HardLinkTracker::new();
+        let mut seen = Vec::new();
+        for dir in ["", "d0", "d0/inner", "d1", "d1/inner", "d2", "d2/inner"] {
+            let path = root.join(dir);
+            earlier.dir_count += 1;
+            let entries: Vec<_> = fs::read_dir(&path).unwrap().map(|e| e.unwrap()).collect();
+            let (files, _) = scanner(1, root).reader.read(entries, &mut earlier.warnings);
+            for file in &files {
+                let meta = count_file(&path, None, file, false, &hard_links, &mut earlier).unwrap();
+                seen.push(meta.path.clone());
+                collector.process_file(&meta);
+            }
+        }
+
+        let mut checkpoint = ScanCheckpoint::new(root.to_path_buf(), Vec::new());
+        checkpoint.update(
+            vec![
+                PendingDir { path: root.join("d3"), depth: 1 },
+                PendingDir { path: root.join("d4"), depth: 1 },
+            ],
+            ScanState {
+                stats: earlier,
+                collector: collector.state(),
+                hard_links: Vec::new(),
+                visited_dirs: Vec::new(),
+                duplicates: None,
+                cleanup: None,
+            },
+        );
+
+        let visited = parking_lot::Mutex::new(Vec::<PathBuf>::new());
+        let (stats, collector) = scanner(2, root).scan(
+            root,
+            &mut checkpoint,
+            new_collector,
+            &ScanProgress::new(false),
+            |meta, _| visited.lock().push(meta.path.clone()),
+            |_| Ok(()),
+        );
+
+        // Only the queued subtrees were read again
+        let mut visited = visited.into_inner();
+        visited.sort();
+        assert_eq!(
+            visited,
+            vec![root.join("d3/a.txt"), root.join("d3/inner/b.rs"), root.join("d4/a.txt"), root.join("d4/inner/b.rs")]
+        );
+
+        // ...but the totals cover the whole tree
+        assert_eq!(stats.total_bytes, 150 + 35);
+        assert_eq!(stats.file_count, 10);
+        assert_eq!(stats.dir_count, 11);
+        let results = collector.finalize(stats.total_bytes);
+        assert_eq!(results.buckets.iter().map(|b| b.file_count).sum::<u64>(), 10);
+    }
+}
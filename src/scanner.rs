@@ -1,32 +1,63 @@
-use crate::checkpoint::ScanCheckpoint;
+use crate::exclude::ExcludeMatcher;
+use crate::hardlinks::HardLinkTracker;
+use crate::mounts::DeviceTally;
 use crate::progress::ScanProgress;
-use crate::types::{FileMetadata, Warning};
+use crate::types::{FileMetadata, SkippedMount, Warning};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::time::Instant;
+use std::sync::Arc;
 use walkdir::{DirEntry, WalkDir};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScanStats {
     pub total_bytes: u64,
+    pub disk_bytes: u64,
+    /// Bytes of additional hard links to inodes that were already counted
+    pub hardlink_bytes: u64,
     pub file_count: u64,
     pub dir_count: u64,
     pub warnings: Vec<Warning>,
+    pub skipped_mounts: Vec<SkippedMount>,
+    /// Bytes per filesystem
+    pub devices: DeviceTally,
+}
+
+impl ScanStats {
+    /// Add the counts of another (partial) scan, e.g. from a parallel worker.
+    pub fn merge(&mut self, other: ScanStats) {
+        self.total_bytes += other.total_bytes;
+        self.disk_bytes += other.disk_bytes;
+        self.hardlink_bytes += other.hardlink_bytes;
+        self.file_count += other.file_count;
+        self.dir_count += other.dir_count;
+        self.warnings.extend(other.warnings);
+        self.skipped_mounts.extend(other.skipped_mounts);
+        self.devices.merge(other.devices);
+    }
 }
 
 pub struct Scanner {
     follow_symlinks: bool,
     max_depth: Option<usize>,
-    exclude_patterns: Vec<String>,
+    exclude: Arc<ExcludeMatcher>,
     need_modified: bool,
+    one_file_system: bool,
 }
 
 impl Scanner {
-    pub fn new(follow_symlinks: bool, max_depth: Option<usize>, exclude_patterns: Vec<String>, need_modified: bool) -> Self {
+    pub fn new(
+        follow_symlinks: bool,
+        max_depth: Option<usize>,
+        exclude: Arc<ExcludeMatcher>,
+        need_modified: bool,
+        one_file_system: bool,
+    ) -> Self {
         Self {
             follow_symlinks,
             max_depth,
-            exclude_patterns,
+            exclude,
             need_modified,
+            one_file_system,
         }
     }
 
@@ -35,40 +66,51 @@ impl Scanner {
         path: &Path,
         mut callback: F,
         progress: &ScanProgress,
-        checkpoint: Option<(&mut ScanCheckpoint, &Path, u64)>,
     ) -> ScanStats
     where
         F: FnMut(FileMetadata),
     {
         let mut stats = ScanStats {
             total_bytes: 0,
+            disk_bytes: 0,
+            hardlink_bytes: 0,
             file_count: 0,
             dir_count: 0,
             warnings: Vec::new(),
+            skipped_mounts: Vec::new(),
+            devices: DeviceTally::new(),
         };
 
+        let root_device = if self.one_file_system { root_device(path) } else { None };
+        let mut skipped_mounts = Vec::new();
+
         let mut walker = WalkDir::new(path).follow_links(self.follow_symlinks);
 
         if let Some(depth) = self.max_depth {
             walker = walker.max_depth(depth);
         }
 
-        // Early directory pruning: filter excluded directories BEFORE descending
+        // Early directory pruning: filter excluded directories and other
+        // filesystems BEFORE descending
         let walker = walker.into_iter().filter_entry(|entry| {
-            !self.should_exclude(entry)
+            if self.should_exclude(entry) {
+                return false;
+            }
+            if let Some(device) = root_device {
+                if let Some(mount) = crossed_mount(entry, device) {
+                    skipped_mounts.push(mount);
+                    return false;
+                }
+            }
+            true
         });
 
-        let mut last_checkpoint_time = Instant::now();
-        let (mut checkpoint_ref, checkpoint_path, checkpoint_interval) = if let Some((ckpt, path, interval)) = checkpoint {
-            (Some(ckpt), Some(path), interval)
-        } else {
-            (None, None, 0)
-        };
+        let hard_links = HardLinkTracker::new();
 
         for entry_result in walker {
             match entry_result {
                 Ok(entry) => {
-                    if let Err(e) = self.process_entry(&entry, &mut stats, &mut callback) {
+                    if let Err(e) = self.process_entry(&entry, &hard_links, &mut stats, &mut callback) {
                         stats.warnings.push(Warning {
                             path: entry.path().display().to_string(),
                             error: e.to_string(),
@@ -76,24 +118,13 @@ impl Scanner {
                     }
 
                     // Update progress every 1000 files to avoid overhead
-                    if stats.file_count % 1000 == 0 {
+                    if stats.file_count.is_multiple_of(1000) {
                         progress.update(
                             stats.file_count,
                             stats.total_bytes,
                             entry.path().to_str().unwrap_or(""),
                         );
                     }
-
-                    // Checkpoint periodically
-                    if let Some(ref mut ckpt) = checkpoint_ref {
-                        if last_checkpoint_time.elapsed().as_secs() >= checkpoint_interval {
-                            ckpt.update_from_stats(&stats);
-                            if let Some(ckpt_path) = checkpoint_path {
-                                let _ = ckpt.save(ckpt_path);
-                            }
-                            last_checkpoint_time = Instant::now();
-                        }
-                    }
                 }
                 Err(e) => {
                     stats.warnings.push(Warning {
@@ -105,28 +136,18 @@ impl Scanner {
         }
 
         progress.finish();
+        stats.skipped_mounts = skipped_mounts;
         stats
     }
 
     fn should_exclude(&self, entry: &DirEntry) -> bool {
-        if self.exclude_patterns.is_empty() {
-            return false;
-        }
-
-        let path_str = entry.path().to_string_lossy();
-
-        for pattern in &self.exclude_patterns {
-            if path_str.contains(pattern) {
-                return true;
-            }
-        }
-
-        false
+        self.exclude.is_excluded(entry.path(), entry.file_type().is_dir())
     }
 
     fn process_entry<F>(
         &self,
         entry: &DirEntry,
+        hard_links: &HardLinkTracker,
         stats: &mut ScanStats,
         callback: &mut F,
     ) -> std::io::Result<()>
@@ -137,10 +158,21 @@ impl Scanner {
 
         if metadata.is_dir() {
             stats.dir_count += 1;
+            stats.devices.add_dir(&metadata, entry.path());
         } else if metadata.is_file() {
             let size = metadata.len();
+
+            // Additional links to an inode we've already counted take no extra space
+            if !hard_links.first_seen(&metadata) {
+                stats.hardlink_bytes += size;
+                return Ok(());
+            }
+
+            let disk_size = allocated_size(&metadata);
             stats.total_bytes += size;
+            stats.disk_bytes += disk_size;
             stats.file_count += 1;
+            stats.devices.add_file(&metadata, entry.path(), size, disk_size);
 
             let extension = entry.path()
                 .extension()
@@ -157,6 +189,7 @@ impl Scanner {
             callback(FileMetadata {
                 path: entry.path().to_path_buf(),
                 size,
+                disk_size,
                 extension,
                 modified,
             });
@@ -165,3 +198,50 @@ impl Scanner {
         Ok(())
     }
 }
+
+/// Directory entries below the root that live on a different device than
+/// `root_device` are mount points.
+fn crossed_mount(entry: &DirEntry, root_device: u64) -> Option<SkippedMount> {
+    if entry.depth() == 0 || !entry.file_type().is_dir() {
+        return None;
+    }
+    let device = entry.metadata().ok().and_then(|m| device_id(&m))?;
+    (device != root_device).then(|| SkippedMount {
+        path: entry.path().display().to_string(),
+        device,
+    })
+}
+
+/// Device of the scan root, used by `--one-file-system`.
+pub fn root_device(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().and_then(|m| device_id(&m))
+}
+
+/// ID of the filesystem holding a file (`st_dev`). Platforms without device
+/// IDs return `None`, which disables the one-filesystem check.
+#[cfg(unix)]
+pub fn device_id(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Space actually allocated for a file, as reported by `du`.
+///
+/// Uses `st_blocks`, which is always counted in 512-byte units regardless of
+/// the filesystem block size. Platforms without block counts fall back to the
+/// apparent length.
+#[cfg(unix)]
+pub fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub fn allocated_size(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
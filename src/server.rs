@@ -0,0 +1,548 @@
+//! `spacemap serve`: an HTTP API that runs scans as queued jobs.
+//!
+//! Jobs are scanned by a fixed number of worker threads, so at most that many
+//! scans run at once. Only paths inside the configured roots can be scanned,
+//! and finished jobs are kept in memory until newer ones push them out.
+
+use crate::compare::compare_scans;
+use crate::config::SpacemapConfig;
+use crate::engine::ScanOptions;
+use crate::http::{self, Request, Response};
+use crate::progress::ProgressState;
+use crate::types::{ScanResults, Totals};
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::TcpListener;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Largest `top` a client may ask for; each scan keeps that many entries
+/// per list.
+const MAX_TOP: usize = 1000;
+
+/// Limits and allow-list of a server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Directories that scans must stay inside
+    pub roots: Vec<PathBuf>,
+    /// Scans that run at the same time
+    pub workers: usize,
+    /// Jobs that may wait for a worker before new ones are refused
+    pub max_queued: usize,
+    /// Finished jobs kept for listing and comparison
+    pub keep: usize,
+    /// Config used by every scan (categories, cleanup rules, policies)
+    pub config: Option<SpacemapConfig>,
+}
+
+/// Body of `POST /scans`. Fields are named after the command-line flags.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanRequest {
+    pub path: PathBuf,
+    pub by: Option<String>,
+    pub size_mode: Option<String>,
+    pub dir_totals: Option<String>,
+    pub max_depth: Option<usize>,
+    pub exclude: Vec<String>,
+    pub follow_symlinks: bool,
+    pub one_file_system: bool,
+    pub sniff: bool,
+    pub size_buckets: Option<Vec<u64>>,
+    pub age_buckets: Option<Vec<u64>>,
+    pub parallel: bool,
+    pub threads: usize,
+    pub top: Option<usize>,
+    pub find_duplicates: bool,
+    pub suggest_cleanup: bool,
+    pub check_policy: bool,
+}
+
+impl ScanRequest {
+    /// Scan options for this request, reporting progress to `progress`.
+    pub fn options(&self, config: Option<SpacemapConfig>, progress: Arc<ProgressState>) -> Result<ScanOptions, String> {
+        let options = ScanOptions::new()
+            .mode(self.by.as_deref().unwrap_or("type").parse()?)
+            .size_mode(self.size_mode.as_deref().unwrap_or("apparent").parse()?)
+            .dir_totals(self.dir_totals.as_deref().unwrap_or("cumulative").parse()?)
+            .config(config)
+            .size_buckets(self.size_buckets.clone())
+            .age_buckets(self.age_buckets.clone())
+            .max_depth(self.max_depth)
+            .exclude(self.exclude.clone())
+            .follow_symlinks(self.follow_symlinks)
+            .one_file_system(self.one_file_system)
+            .sniff(self.sniff)
+            .parallel(self.parallel)
+            .threads(self.threads)
+            .top(self.top.unwrap_or(10))
+            .collect_tops(true)
+            .find_duplicates(self.find_duplicates)
+            .suggest_cleanup(self.suggest_cleanup)
+            .check_policy(self.check_policy)
+            .progress_state(Some(progress));
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+struct Job {
+    request: ScanRequest,
+    status: JobStatus,
+    submitted_at: String,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    progress: Arc<ProgressState>,
+    results: Option<Arc<ScanResults>>,
+    error: Option<String>,
+}
+
+/// What `GET /scans/{id}` reports about a job.
+#[derive(Debug, Serialize)]
+pub struct JobSummary {
+    pub id: u64,
+    pub status: JobStatus,
+    pub request: ScanRequest,
+    pub submitted_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub progress: JobProgress,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totals: Option<Totals>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobProgress {
+    pub files: u64,
+    pub bytes: u64,
+    pub current_path: String,
+}
+
+/// Why a job was not accepted.
+#[derive(Debug)]
+pub enum SubmitError {
+    Invalid(String),
+    /// The path is outside every allowed root
+    Forbidden(PathBuf),
+    NotFound(PathBuf),
+    QueueFull,
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Invalid(msg) => write!(f, "{}", msg),
+            SubmitError::Forbidden(path) => write!(f, "Path is not inside an allowed root: {}", path.display()),
+            SubmitError::NotFound(path) => write!(f, "Path does not exist: {}", path.display()),
+            SubmitError::QueueFull => write!(f, "Too many scans are waiting; try again later"),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    queue: VecDeque<u64>,
+}
+
+struct Shared {
+    config: ServerConfig,
+    jobs: Mutex<Jobs>,
+    queued: Condvar,
+}
+
+/// The job queue and its workers.
+#[derive(Clone)]
+pub struct Server {
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    /// Start `config.workers` worker threads waiting for jobs.
+    pub fn new(config: ServerConfig) -> Self {
+        let workers = config.workers.max(1);
+        let server = Self {
+            shared: Arc::new(Shared {
+                config,
+                jobs: Mutex::new(Jobs::default()),
+                queued: Condvar::new(),
+            }),
+        };
+        for _ in 0..workers {
+            let worker = server.clone();
+            std::thread::spawn(move || worker.work());
+        }
+        server
+    }
+
+    /// Queue a scan and return its job id.
+    pub fn submit(&self, mut request: ScanRequest) -> Result<u64, SubmitError> {
+        if !request.path.is_absolute() {
+            return Err(SubmitError::Invalid("path must be absolute".to_string()));
+        }
+        if request.follow_symlinks {
+            // Links could lead anywhere, past the allowed roots
+            return Err(SubmitError::Invalid("follow_symlinks is not allowed by this server".to_string()));
+        }
+        // Both are sizes of allocations and thread pools, so keep clients in check
+        if request.top.is_some_and(|top| top > MAX_TOP) {
+            return Err(SubmitError::Invalid(format!("top must be at most {}", MAX_TOP)));
+        }
+        let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if request.threads > max_threads {
+            return Err(SubmitError::Invalid(format!("threads must be at most {}", max_threads)));
+        }
+        request.path = self.resolve(&request.path)?;
+        // Catch bad options now rather than in a failed job
+        request.options(None, Arc::new(ProgressState::new())).map_err(SubmitError::Invalid)?;
+
+        let mut jobs = self.shared.jobs.lock();
+        if jobs.queue.len() >= self.shared.config.max_queued {
+            return Err(SubmitError::QueueFull);
+        }
+        jobs.next_id += 1;
+        let id = jobs.next_id;
+        jobs.jobs.insert(
+            id,
+            Job {
+                request,
+                status: JobStatus::Queued,
+                submitted_at: now(),
+                started_at: None,
+                finished_at: None,
+                progress: Arc::new(ProgressState::new()),
+                results: None,
+                error: None,
+            },
+        );
+        jobs.queue.push_back(id);
+        self.shared.queued.notify_one();
+        Ok(id)
+    }
+
+    /// The canonical form of `path` if it exists inside an allowed root.
+    fn resolve(&self, path: &Path) -> Result<PathBuf, SubmitError> {
+        let inside = |path: &Path| self.shared.config.roots.iter().any(|root| path.starts_with(root));
+        match path.canonicalize() {
+            Ok(canonical) if inside(&canonical) => Ok(canonical),
+            Ok(_) => Err(SubmitError::Forbidden(path.to_path_buf())),
+            // Only confirm that a path is missing when it would have been allowed
+            Err(_) if inside(path) && !path.components().any(|c| c == Component::ParentDir) => {
+                Err(SubmitError::NotFound(path.to_path_buf()))
+            }
+            Err(_) => Err(SubmitError::Forbidden(path.to_path_buf())),
+        }
+    }
+
+    pub fn job(&self, id: u64) -> Option<JobSummary> {
+        let jobs = self.shared.jobs.lock();
+        jobs.jobs.get(&id).map(|job| summary(id, job))
+    }
+
+    /// Every kept job, newest first.
+    pub fn jobs(&self) -> Vec<JobSummary> {
+        let jobs = self.shared.jobs.lock();
+        jobs.jobs.iter().rev().map(|(&id, job)| summary(id, job)).collect()
+    }
+
+    /// Results of a finished job; `Err` with the job status otherwise.
+    pub fn results(&self, id: u64) -> Option<Result<Arc<ScanResults>, JobStatus>> {
+        let jobs = self.shared.jobs.lock();
+        let job = jobs.jobs.get(&id)?;
+        Some(job.results.clone().ok_or(job.status))
+    }
+
+    fn work(&self) {
+        loop {
+            let (id, request, progress) = {
+                let mut jobs = self.shared.jobs.lock();
+                let id = loop {
+                    match jobs.queue.pop_front() {
+                        Some(id) => break id,
+                        None => self.shared.queued.wait(&mut jobs),
+                    }
+                };
+                let Some(job) = jobs.jobs.get_mut(&id) else {
+                    continue;
+                };
+                job.status = JobStatus::Running;
+                job.started_at = Some(now());
+                (id, job.request.clone(), job.progress.clone())
+            };
+
+            let outcome = request
+                .options(self.shared.config.config.clone(), progress)
+                .and_then(|options| crate::scan(&request.path, options).map_err(|e| e.to_string()));
+
+            let mut jobs = self.shared.jobs.lock();
+            if let Some(job) = jobs.jobs.get_mut(&id) {
+                job.finished_at = Some(now());
+                match outcome {
+                    Ok(results) => {
+                        job.status = JobStatus::Done;
+                        job.results = Some(Arc::new(results));
+                    }
+                    Err(e) => {
+                        job.status = JobStatus::Failed;
+                        job.error = Some(e);
+                    }
+                }
+            }
+            forget_old_jobs(&mut jobs, self.shared.config.keep);
+        }
+    }
+
+    /// Answer HTTP requests on `listener` until it fails.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let server = self.clone();
+        http::serve(listener, move |request| server.respond(request))
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["health"]) => {
+                let jobs = self.shared.jobs.lock();
+                let running = jobs.jobs.values().filter(|j| j.status == JobStatus::Running).count();
+                Response::json(
+                    200,
+                    &serde_json::json!({ "status": "ok", "queued": jobs.queue.len(), "running": running }),
+                )
+            }
+            ("GET", ["scans"]) => Response::json(200, &self.jobs()),
+            ("POST", ["scans"]) => {
+                let scan: ScanRequest = match serde_json::from_slice(&request.body) {
+                    Ok(scan) => scan,
+                    Err(e) => return Response::error(400, &format!("Invalid scan request: {}", e)),
+                };
+                match self.submit(scan) {
+                    Ok(id) => Response::json(202, &self.job(id)),
+                    Err(e) => {
+                        let status = match e {
+                            SubmitError::Invalid(_) => 400,
+                            SubmitError::Forbidden(_) => 403,
+                            SubmitError::NotFound(_) => 404,
+                            SubmitError::QueueFull => 503,
+                        };
+                        Response::error(status, &e.to_string())
+                    }
+                }
+            }
+            ("GET", ["scans", "compare"]) => self.compare(request),
+            ("GET", ["scans", id]) => match id.parse().ok().and_then(|id| self.job(id)) {
+                Some(job) => Response::json(200, &job),
+                None => Response::error(404, &format!("No scan {}", id)),
+            },
+            ("GET", ["scans", id, "results"]) => match id.parse().ok().and_then(|id| self.results(id)) {
+                Some(Ok(results)) => Response::json(200, results.as_ref()),
+                Some(Err(status)) => Response::error(409, &format!("Scan {} is {}", id, status_name(status))),
+                None => Response::error(404, &format!("No scan {}", id)),
+            },
+            (_, ["health"] | ["scans"] | ["scans", ..]) => Response::error(405, "Method not allowed"),
+            _ => Response::error(404, "Not found"),
+        }
+    }
+
+    /// `GET /scans/compare?before=ID&after=ID`
+    fn compare(&self, request: &Request) -> Response {
+        let mut scans = Vec::new();
+        for name in ["before", "after"] {
+            let Some(id) = request.query(name).and_then(|id| id.parse::<u64>().ok()) else {
+                return Response::error(400, &format!("Missing or invalid '{}' scan id", name));
+            };
+            match self.results(id) {
+                Some(Ok(results)) => scans.push((id, results)),
+                Some(Err(status)) => {
+                    return Response::error(409, &format!("Scan {} is {}", id, status_name(status)));
+                }
+                None => return Response::error(404, &format!("No scan {}", id)),
+            }
+        }
+
+        let (before, after) = (&scans[0], &scans[1]);
+        Response::json(
+            200,
+            &serde_json::json!({
+                "before": before.0,
+                "after": after.0,
+                "comparison": compare_scans(&before.1, &after.1),
+            }),
+        )
+    }
+}
+
+fn summary(id: u64, job: &Job) -> JobSummary {
+    JobSummary {
+        id,
+        status: job.status,
+        request: job.request.clone(),
+        submitted_at: job.submitted_at.clone(),
+        started_at: job.started_at.clone(),
+        finished_at: job.finished_at.clone(),
+        progress: JobProgress {
+            files: job.results.as_ref().map_or(job.progress.files(), |r| r.totals.file_count),
+            bytes: job.results.as_ref().map_or(job.progress.bytes(), |r| r.totals.total_bytes),
+            current_path: job.progress.current_path(),
+        },
+        totals: job.results.as_ref().map(|r| r.totals.clone()),
+        error: job.error.clone(),
+    }
+}
+
+/// Drop the oldest finished jobs beyond `keep`.
+fn forget_old_jobs(jobs: &mut Jobs, keep: usize) {
+    let finished: Vec<u64> = jobs
+        .jobs
+        .iter()
+        .filter(|(_, job)| matches!(job.status, JobStatus::Done | JobStatus::Failed))
+        .map(|(&id, _)| id)
+        .collect();
+    for id in finished.iter().take(finished.len().saturating_sub(keep)) {
+        jobs.jobs.remove(id);
+    }
+}
+
+fn status_name(status: JobStatus) -> &'static str {
+    match status {
+        JobStatus::Queued => "still queued",
+        JobStatus::Running => "still running",
+        JobStatus::Done => "done",
+        JobStatus::Failed => "failed",
+    }
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    fn server(root: &Path) -> Server {
+        Server::new(ServerConfig {
+            roots: vec![root.canonicalize().unwrap()],
+            workers: 1,
+            max_queued: 10,
+            keep: 10,
+            config: None,
+        })
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn json(response: Response) -> serde_json::Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    fn wait(server: &Server, id: u64) -> JobSummary {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let job = server.job(id).unwrap();
+            if matches!(job.status, JobStatus::Done | JobStatus::Failed) || Instant::now() > deadline {
+                return job;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_scan_jobs_and_compare() {
+        let dir = TempDir::new().unwrap();
+        let data = dir.path().join("data");
+        std::fs::create_dir(&data).unwrap();
+        std::fs::write(data.join("a.rs"), vec![0u8; 100]).unwrap();
+        let server = server(dir.path());
+
+        let body = format!(r#"{{"path": "{}", "by": "type", "top": 5}}"#, data.display());
+        let response = server.respond(&request("POST", "/scans", &body));
+        assert_eq!(response.status, 202);
+        let first = json(response)["id"].as_u64().unwrap();
+        assert_eq!(wait(&server, first).status, JobStatus::Done);
+
+        std::fs::write(data.join("b.rs"), vec![0u8; 50]).unwrap();
+        let second = json(server.respond(&request("POST", "/scans", &body)))["id"].as_u64().unwrap();
+        assert_eq!(wait(&server, second).totals.unwrap().total_bytes, 150);
+
+        let results = json(server.respond(&request("GET", &format!("/scans/{}/results", first), "")));
+        assert_eq!(results["totals"]["total_bytes"], 100);
+
+        let listed = json(server.respond(&request("GET", "/scans", "")));
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+
+        let compared = server.respond(&request("GET", &format!("/scans/compare?before={}&after={}", first, second), ""));
+        assert_eq!(json(compared)["comparison"]["added_bytes"], 50);
+
+        assert_eq!(server.respond(&request("GET", "/scans/99", "")).status, 404);
+        assert_eq!(server.respond(&request("GET", "/health", "")).status, 200);
+    }
+
+    #[test]
+    fn test_rejects_paths_outside_roots() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("allowed");
+        std::fs::create_dir(&root).unwrap();
+        let server = server(&root);
+
+        let submit = |path: &Path| {
+            server.submit(ScanRequest {
+                path: path.to_path_buf(),
+                ..Default::default()
+            })
+        };
+        assert!(matches!(submit(dir.path()), Err(SubmitError::Forbidden(_))));
+        assert!(matches!(submit(&root.join("../allowed/../")), Err(SubmitError::Forbidden(_))));
+        assert!(matches!(submit(&root.join("missing")), Err(SubmitError::NotFound(_))));
+        assert!(matches!(submit(&dir.path().join("missing")), Err(SubmitError::Forbidden(_))));
+        assert!(matches!(submit(Path::new("relative")), Err(SubmitError::Invalid(_))));
+
+        let invalid = server.respond(&request("POST", "/scans", r#"{"path": "/", "colour": "red"}"#));
+        assert_eq!(invalid.status, 400);
+        let symlinks = format!(r#"{{"path": "{}", "follow_symlinks": true}}"#, root.display());
+        assert_eq!(server.respond(&request("POST", "/scans", &symlinks)).status, 400);
+    }
+
+    #[test]
+    fn test_rejects_oversized_requests() {
+        let dir = TempDir::new().unwrap();
+        let server = server(dir.path());
+        let path = dir.path().display();
+
+        let top = format!(r#"{{"path": "{}", "top": 18446744073709551615}}"#, path);
+        assert_eq!(server.respond(&request("POST", "/scans", &top)).status, 400);
+        let threads = format!(r#"{{"path": "{}", "parallel": true, "threads": 100000}}"#, path);
+        assert_eq!(server.respond(&request("POST", "/scans", &threads)).status, 400);
+
+        let within = format!(r#"{{"path": "{}", "top": {}, "threads": 1}}"#, path, MAX_TOP);
+        assert_eq!(server.respond(&request("POST", "/scans", &within)).status, 202);
+    }
+}
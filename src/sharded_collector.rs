@@ -1,9 +1,6 @@
-use crate::bounded_heap::BoundedMinHeap;
 use crate::categorize::Categorizer;
 use crate::collector::{CollectionResults, SinglePassCollector};
-use crate::types::FileMetadata;
 use parking_lot::Mutex;
-use rayon::prelude::*;
 use std::sync::Arc;
 
 /// Sharded collector that enables lock-free parallel collection.
@@ -11,7 +8,6 @@ use std::sync::Arc;
 /// Shards are merged at finalization time using parallel reduction.
 pub struct ShardedCollector {
     shards: Vec<Arc<Mutex<SinglePassCollector>>>,
-    top_n: usize,
 }
 
 impl ShardedCollector {
@@ -30,18 +26,23 @@ impl ShardedCollector {
     ) -> Self
     where
         F: FnMut() -> Box<dyn Categorizer>,
+    {
+        Self::with_collectors(num_shards, || {
+            SinglePassCollector::new(categorizer_factory(), top_n, should_collect_tops)
+        })
+    }
+
+    /// Create a sharded collector from pre-configured collectors, e.g. with
+    /// cumulative directory totals or a non-default size mode.
+    pub fn with_collectors<F>(num_shards: usize, mut collector_factory: F) -> Self
+    where
+        F: FnMut() -> SinglePassCollector,
     {
         let shards = (0..num_shards)
-            .map(|_| {
-                Arc::new(Mutex::new(SinglePassCollector::new(
-                    categorizer_factory(),
-                    top_n,
-                    should_collect_tops,
-                )))
-            })
+            .map(|_| Arc::new(Mutex::new(collector_factory())))
             .collect();
 
-        Self { shards, top_n }
+        Self { shards }
     }
 
     /// Get a reference to a specific shard for processing.
@@ -86,15 +87,7 @@ impl ShardedCollector {
         mut collector1: SinglePassCollector,
         collector2: SinglePassCollector,
     ) -> SinglePassCollector {
-        // For now, just return collector1
-        // In a full implementation, this would:
-        // 1. Merge category_stats HashMaps
-        // 2. Merge top_files_heap BoundedMinHeaps
-        // 3. Merge dir_accumulator HashMaps
-        // 4. Merge path_pools
-
-        // This is a simplified version that delegates to SinglePassCollector::finalize
-        // A proper implementation would need to expose merge functionality
+        collector1.merge(collector2);
         collector1
     }
 }
@@ -115,4 +108,34 @@ mod tests {
 
         assert_eq!(collector.shards.len(), 4);
     }
+
+    #[test]
+    fn test_sharded_collector_merges_all_shards() {
+        use crate::types::{DirTotals, FileMetadata};
+        use std::path::PathBuf;
+
+        let collector = ShardedCollector::with_collectors(3, || {
+            SinglePassCollector::new(Box::new(TypeCategorizer::new()), 10, true)
+                .with_dir_totals(DirTotals::Cumulative, PathBuf::from("/scan"))
+        });
+
+        for (shard_id, path) in ["/scan/a/x/1.rs", "/scan/a/y/2.rs", "/scan/b/3.rs"].iter().enumerate() {
+            collector.get_shard(shard_id).lock().process_file(&FileMetadata {
+                path: PathBuf::from(path),
+                size: 10,
+                disk_size: 10,
+                extension: Some("rs".to_string()),
+                modified: None,
+            });
+        }
+
+        let results = collector.finalize(30);
+        assert_eq!(results.buckets[0].file_count, 3);
+        assert_eq!(results.top_files.len(), 3);
+
+        let dir = |path: &str| results.top_dirs.iter().find(|d| d.path == path).map(|d| d.bytes);
+        assert_eq!(dir("/scan"), Some(30));
+        assert_eq!(dir("/scan/a"), Some(20));
+        assert_eq!(dir("/scan/b"), Some(10));
+    }
 }
@@ -0,0 +1,226 @@
+//! Content-based file type detection for `--sniff`.
+//!
+//! Files whose extension doesn't say what they are have their first bytes
+//! compared against well-known signatures. A match is reported as the
+//! extension the format usually has, so it is categorized through the same
+//! extension map (and config remaps) as a file that had that extension.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file. Enough for the tar header and the
+/// first entry names of a ZIP file.
+const HEADER_LEN: usize = 4096;
+
+/// Read the start of the file at `path` and detect its format.
+pub fn sniff(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path).ok()?.take(HEADER_LEN as u64).read_to_end(&mut header).ok()?;
+    detect(&header)
+}
+
+/// The extension usually used for the format `header` starts with.
+pub fn detect(header: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| header.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+
+    // Executables and libraries
+    if starts(b"\x7fELF") || starts(b"\0asm") {
+        return Some("bin");
+    }
+    if starts(b"MZ") {
+        return Some("exe");
+    }
+    for magic in [b"\xfe\xed\xfa\xce", b"\xfe\xed\xfa\xcf", b"\xce\xfa\xed\xfe", b"\xcf\xfa\xed\xfe", b"\xca\xfe\xba\xbe"] {
+        if starts(magic) {
+            return Some("dylib");
+        }
+    }
+
+    // Archives and compressed data
+    if starts(b"PK\x03\x04") {
+        return Some(zip_kind(header));
+    }
+    if starts(b"\x1f\x8b") {
+        return Some("gz");
+    }
+    if starts(b"\x28\xb5\x2f\xfd") {
+        return Some("zst");
+    }
+    if starts(b"\xfd7zXZ\0") {
+        return Some("xz");
+    }
+    if starts(b"BZh") {
+        return Some("bz2");
+    }
+    if starts(b"7z\xbc\xaf\x27\x1c") {
+        return Some("7z");
+    }
+    if starts(b"Rar!\x1a\x07") {
+        return Some("rar");
+    }
+    if at(257, b"ustar") {
+        return Some("tar");
+    }
+
+    // Images
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        return Some("png");
+    }
+    if starts(b"\xff\xd8\xff") {
+        return Some("jpg");
+    }
+    if starts(b"GIF87a") || starts(b"GIF89a") {
+        return Some("gif");
+    }
+    if starts(b"RIFF") && at(8, b"WEBP") {
+        return Some("webp");
+    }
+
+    // ISO base media (MP4, MOV, HEIC, M4A) share the `ftyp` box
+    if at(4, b"ftyp") {
+        return Some(match header.get(8..12) {
+            Some(b"heic" | b"heix" | b"mif1" | b"msf1") => "heic",
+            Some(b"M4A " | b"M4B ") => "m4a",
+            Some(b"qt  ") => "mov",
+            _ => "mp4",
+        });
+    }
+    if starts(b"\x1a\x45\xdf\xa3") {
+        return Some("mkv");
+    }
+    if starts(b"RIFF") && at(8, b"AVI ") {
+        return Some("avi");
+    }
+
+    // Audio
+    if starts(b"RIFF") && at(8, b"WAVE") {
+        return Some("wav");
+    }
+    if starts(b"fLaC") {
+        return Some("flac");
+    }
+    if starts(b"OggS") {
+        return Some("ogg");
+    }
+    if starts(b"ID3") {
+        return Some("mp3");
+    }
+
+    // Documents, databases, fonts and disk images
+    if starts(b"%PDF-") {
+        return Some("pdf");
+    }
+    if starts(b"SQLite format 3\0") {
+        return Some("sqlite");
+    }
+    if starts(b"wOFF") {
+        return Some("woff");
+    }
+    if starts(b"wOF2") {
+        return Some("woff2");
+    }
+    if starts(b"OTTO") {
+        return Some("otf");
+    }
+    if starts(b"QFI\xfb") || starts(b"KDMV") {
+        return Some("img");
+    }
+    if starts(b"<<< Oracle VM VirtualBox Disk Image >>>") {
+        return Some("vdi");
+    }
+
+    None
+}
+
+/// ZIP files are also Office documents, OpenDocument files and Java or
+/// Android archives; tell them apart by the entry names near the start.
+fn zip_kind(header: &[u8]) -> &'static str {
+    let contains = |needle: &[u8]| header.windows(needle.len()).any(|w| w == needle);
+
+    // OpenDocument stores its type uncompressed in the first entry
+    if contains(b"mimetypeapplication/vnd.oasis.opendocument.") {
+        if contains(b"opendocument.spreadsheet") {
+            return "ods";
+        }
+        if contains(b"opendocument.presentation") {
+            return "odp";
+        }
+        return "odt";
+    }
+    // JARs start with their manifest, or the directory holding it
+    let first_entry = header
+        .get(26..28)
+        .and_then(|len| header.get(30..30 + u16::from_le_bytes([len[0], len[1]]) as usize));
+    if first_entry.is_some_and(|name| name.starts_with(b"META-INF/")) {
+        return "jar";
+    }
+    if contains(b"word/") {
+        return "docx";
+    }
+    if contains(b"xl/") {
+        return "xlsx";
+    }
+    if contains(b"ppt/") {
+        return "pptx";
+    }
+    "zip"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_prefix(prefix: &[u8], offset: usize, magic: &[u8]) -> Vec<u8> {
+        let mut header = prefix.to_vec();
+        header.resize(offset.max(header.len()), 0);
+        header.extend_from_slice(magic);
+        header.resize(512, 0);
+        header
+    }
+
+    #[test]
+    fn test_detect_signatures() {
+        assert_eq!(detect(b"\x7fELF\x02\x01\x01"), Some("bin"));
+        assert_eq!(detect(b"MZ\x90\0"), Some("exe"));
+        assert_eq!(detect(b"\xcf\xfa\xed\xfe\x07"), Some("dylib"));
+        assert_eq!(detect(b"\x1f\x8b\x08\0"), Some("gz"));
+        assert_eq!(detect(b"\x28\xb5\x2f\xfd\x04"), Some("zst"));
+        assert_eq!(detect(b"\xfd7zXZ\0\0"), Some("xz"));
+        assert_eq!(detect(&with_prefix(b"file.txt", 257, b"ustar\x0000")), Some("tar"));
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0"), Some("png"));
+        assert_eq!(detect(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("jpg"));
+        assert_eq!(detect(b"\0\0\0\x20ftypisom\0\0\x02\0"), Some("mp4"));
+        assert_eq!(detect(b"\0\0\0\x18ftypheic\0\0\0\0"), Some("heic"));
+        assert_eq!(detect(b"\x1a\x45\xdf\xa3\x93\x42\x82\x88matroska"), Some("mkv"));
+        assert_eq!(detect(b"RIFF\x24\0\0\0WAVEfmt "), Some("wav"));
+        assert_eq!(detect(b"SQLite format 3\0\x10\0"), Some("sqlite"));
+        assert_eq!(detect(b"%PDF-1.7\n"), Some("pdf"));
+        assert_eq!(detect(b"plain text that matches nothing"), None);
+        assert_eq!(detect(b""), None);
+    }
+
+    #[test]
+    fn test_detect_zip_flavours() {
+        let zip = |names: &[u8]| with_prefix(b"PK\x03\x04\x14\0\0\0", 30, names);
+        assert_eq!(detect(&zip(b"[Content_Types].xml..word/document.xml")), Some("docx"));
+        assert_eq!(detect(&zip(b"[Content_Types].xml..xl/workbook.xml")), Some("xlsx"));
+        assert_eq!(detect(&zip(b"mimetypeapplication/vnd.oasis.opendocument.spreadsheet")), Some("ods"));
+
+        // Only a JAR's first entry is its manifest
+        let entry = |name: &[u8]| {
+            let mut header = b"PK\x03\x04\x14\0\0\0".to_vec();
+            header.resize(26, 0);
+            header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            header.extend_from_slice(&[0, 0]);
+            header.extend_from_slice(name);
+            header.resize(512, 0);
+            header
+        };
+        assert_eq!(detect(&entry(b"META-INF/MANIFEST.MF")), Some("jar"));
+        assert_eq!(detect(&entry(b"META-INF/")), Some("jar"));
+        assert_eq!(detect(&entry(b"docs/META-INF/MANIFEST.MF")), Some("zip"));
+        assert_eq!(detect(&zip(b"META-INF/MANIFEST.MF")), Some("zip"));
+    }
+}
@@ -0,0 +1,647 @@
+//! File-level index of a scan in SQLite.
+//!
+//! Every file the scanner counts is sent to a writer thread, which stores it
+//! in batched transactions so the scan itself never waits on the disk. Each
+//! scan gets a row in `scans`; later scans append to the same database and
+//! can be compared in SQL by `scan_id`.
+
+use crate::bounded_heap::BoundedMinHeap;
+use crate::categorize::Categorizer;
+use crate::compare::FileIndex;
+use crate::engine::FileObserver;
+use crate::types::{Bucket, DirEntry, DirTotals, FileEntry, FileMetadata, ScanResults, SizeMode, Totals};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::UNIX_EPOCH;
+
+/// Entries in the top lists of a scan loaded back from the index.
+const TOP_N: usize = 10;
+
+/// Files written per transaction.
+const BATCH_SIZE: usize = 10_000;
+
+/// Files the scanner may get ahead of the writer before it has to wait.
+const QUEUE_SIZE: usize = 65_536;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    mode TEXT,
+    size_mode TEXT,
+    total_bytes INTEGER,
+    disk_bytes INTEGER,
+    file_count INTEGER,
+    dir_count INTEGER,
+    warning_count INTEGER
+);
+CREATE TABLE IF NOT EXISTS categories (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans (id),
+    key TEXT NOT NULL,
+    label TEXT NOT NULL,
+    UNIQUE (scan_id, key)
+);
+CREATE TABLE IF NOT EXISTS directories (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans (id),
+    parent_id INTEGER REFERENCES directories (id),
+    path TEXT NOT NULL,
+    depth INTEGER NOT NULL,
+    bytes INTEGER NOT NULL DEFAULT 0,
+    disk_bytes INTEGER NOT NULL DEFAULT 0,
+    file_count INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (scan_id, path)
+);
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans (id),
+    directory_id INTEGER NOT NULL REFERENCES directories (id),
+    category_id INTEGER NOT NULL REFERENCES categories (id),
+    name TEXT NOT NULL,
+    extension TEXT,
+    size INTEGER NOT NULL,
+    disk_size INTEGER NOT NULL,
+    modified INTEGER
+);
+CREATE VIEW IF NOT EXISTS file_paths AS
+SELECT f.id, f.scan_id,
+       CASE WHEN d.path LIKE '%/' THEN d.path || f.name ELSE d.path || '/' || f.name END AS path,
+       f.name, f.extension, c.label AS category, f.size, f.disk_size, f.modified
+FROM files f
+JOIN directories d ON d.id = f.directory_id
+JOIN categories c ON c.id = f.category_id;
+";
+
+/// Created when the first scan is finished, so its bulk inserts don't maintain
+/// them row by row. Scans appended later insert into the indexed tables;
+/// rebuilding the indexes every time would cost more than it saves once the
+/// database holds a few scans.
+const INDEXES: &str = "
+CREATE INDEX IF NOT EXISTS files_scan_size ON files (scan_id, size);
+CREATE INDEX IF NOT EXISTS files_scan_extension ON files (scan_id, extension);
+CREATE INDEX IF NOT EXISTS files_directory ON files (directory_id);
+CREATE INDEX IF NOT EXISTS directories_parent ON directories (parent_id);
+";
+
+/// A failed read or write of the index database.
+#[derive(Debug)]
+pub struct SqliteError {
+    pub path: PathBuf,
+    pub error: rusqlite::Error,
+}
+
+impl std::fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SQLite index {} failed: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for SqliteError {}
+
+/// Load a finished scan back from the index at `db_path`: scan `scan_id`,
+/// or the latest one. `None` if there is no such scan.
+///
+/// The results have the totals, categories and top lists of the scan; the
+/// index has every file and directory, for [`crate::compare`].
+pub fn load_scan(db_path: &Path, scan_id: Option<i64>) -> Result<Option<(ScanResults, FileIndex)>, SqliteError> {
+    let error = |error| SqliteError { path: db_path.to_path_buf(), error };
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(error)?;
+    load(&conn, scan_id).map_err(error)
+}
+
+fn load(conn: &Connection, scan_id: Option<i64>) -> rusqlite::Result<Option<(ScanResults, FileIndex)>> {
+    let scan = conn
+        .query_row(
+            "SELECT id, path, mode, size_mode, total_bytes, disk_bytes, file_count, dir_count FROM scans
+             WHERE finished_at IS NOT NULL AND (?1 IS NULL OR id = ?1)
+             ORDER BY id DESC LIMIT 1",
+            [scan_id],
+            |r| {
+                let size_mode: Option<String> = r.get(3)?;
+                Ok((
+                    r.get::<_, i64>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, Option<String>>(2)?,
+                    size_mode.and_then(|mode| mode.parse::<SizeMode>().ok()).unwrap_or_default(),
+                    Totals {
+                        total_bytes: r.get::<_, i64>(4)? as u64,
+                        disk_bytes: r.get::<_, i64>(5)? as u64,
+                        file_count: r.get::<_, i64>(6)? as u64,
+                        dir_count: r.get::<_, i64>(7)? as u64,
+                        ..Totals::default()
+                    },
+                ))
+            },
+        )
+        .optional()?;
+    let Some((scan_id, path, mode, size_mode, totals)) = scan else {
+        return Ok(None);
+    };
+    let total = size_mode.select(totals.total_bytes, totals.disk_bytes);
+
+    let mut buckets = conn
+        .prepare(
+            "SELECT c.key, c.label, sum(f.size), sum(f.disk_size), count(*) FROM files f
+             JOIN categories c ON c.id = f.category_id
+             WHERE f.scan_id = ?1 GROUP BY c.id",
+        )?
+        .query_map([scan_id], |r| {
+            let (bytes, disk_bytes) = (r.get::<_, i64>(2)? as u64, r.get::<_, i64>(3)? as u64);
+            let size = size_mode.select(bytes, disk_bytes);
+            Ok(Bucket {
+                key: r.get(0)?,
+                label: r.get(1)?,
+                bytes,
+                disk_bytes,
+                percent: if total > 0 { size as f64 / total as f64 * 100.0 } else { 0.0 },
+                file_count: r.get::<_, i64>(4)? as u64,
+                color: None,
+                representative_extension: None,
+                content_files: 0,
+                content_bytes: 0,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // Rows go straight into the index; only the largest are kept for the
+    // top lists
+    let mut index = FileIndex::new(size_mode);
+    let mut files = BoundedMinHeap::new(TOP_N);
+    let mut statement = conn.prepare("SELECT path, size, disk_size FROM file_paths WHERE scan_id = ?1")?;
+    let mut rows = statement.query([scan_id])?;
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(0)?;
+        let (bytes, disk_bytes) = (row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64);
+        files.push((size_mode.select(bytes, disk_bytes), path.clone(), bytes, disk_bytes));
+        index.insert_file(path, bytes, disk_bytes);
+    }
+    let mut dirs = BoundedMinHeap::new(TOP_N);
+    let mut statement = conn.prepare("SELECT path, bytes, disk_bytes FROM directories WHERE scan_id = ?1")?;
+    let mut rows = statement.query([scan_id])?;
+    while let Some(row) = rows.next()? {
+        let path: String = row.get(0)?;
+        let (bytes, disk_bytes) = (row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64);
+        dirs.push((size_mode.select(bytes, disk_bytes), path.clone(), bytes, disk_bytes));
+        index.insert_dir(path, bytes, disk_bytes);
+    }
+
+    buckets.sort_by_key(|b| std::cmp::Reverse(size_mode.select(b.bytes, b.disk_bytes)));
+    let files = files
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(_, path, bytes, disk_bytes)| FileEntry { path, bytes, disk_bytes })
+        .collect();
+    let dirs = dirs
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(_, path, bytes, disk_bytes)| DirEntry { path, bytes, disk_bytes })
+        .collect();
+
+    let results = ScanResults {
+        scanned_path: path,
+        mode: mode.unwrap_or_else(|| "type".to_string()),
+        size_mode,
+        dir_totals: DirTotals::Cumulative,
+        totals,
+        disk_usage: None,
+        mounts: Vec::new(),
+        buckets,
+        top_files: files,
+        top_dirs: dirs,
+        warnings: Vec::new(),
+        skipped_mounts: Vec::new(),
+        duplicates: None,
+        cache: None,
+        cleanup: None,
+        policy: None,
+    };
+    Ok(Some((results, index)))
+}
+
+enum Message {
+    /// A file and the key of the category the scan filed it under
+    File(FileMetadata, String),
+    Finish,
+}
+
+/// One scan being written to a SQLite database.
+///
+/// Pass [`SqliteIndex::observer`] to [`crate::ScanOptions::on_file`], run the
+/// scan, then call [`SqliteIndex::finish`] with its results.
+pub struct SqliteIndex {
+    db_path: PathBuf,
+    scan_id: i64,
+    sender: SyncSender<Message>,
+    writer: JoinHandle<rusqlite::Result<Writer>>,
+}
+
+impl SqliteIndex {
+    /// Open or create the database at `db_path` and start a new scan of
+    /// `root`. Files keep the category the scan filed them under; `categorizer`
+    /// should be the scan's, and only supplies the categories' labels.
+    pub fn create(db_path: &Path, root: &Path, categorizer: Box<dyn Categorizer>) -> Result<Self, SqliteError> {
+        let error = |error| SqliteError { path: db_path.to_path_buf(), error };
+
+        let conn = Connection::open(db_path).map_err(error)?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(error)?;
+        conn.pragma_update(None, "synchronous", "NORMAL").map_err(error)?;
+        conn.execute_batch(SCHEMA).map_err(error)?;
+        conn.execute(
+            "INSERT INTO scans (path, started_at) VALUES (?1, ?2)",
+            params![root.to_string_lossy(), chrono::Local::now().to_rfc3339()],
+        )
+        .map_err(error)?;
+        let scan_id = conn.last_insert_rowid();
+
+        let writer = Writer {
+            conn,
+            scan_id,
+            root: root.to_path_buf(),
+            categorizer,
+            categories: HashMap::new(),
+            directories: Vec::new(),
+            directory_index: HashMap::new(),
+        };
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let writer = std::thread::spawn(move || writer.run(receiver));
+
+        Ok(Self {
+            db_path: db_path.to_path_buf(),
+            scan_id,
+            sender,
+            writer,
+        })
+    }
+
+    /// The `scans.id` of this scan.
+    pub fn scan_id(&self) -> i64 {
+        self.scan_id
+    }
+
+    /// Observer that queues every file for the writer thread.
+    pub fn observer(&self) -> FileObserver {
+        let sender = self.sender.clone();
+        Arc::new(move |file: &FileMetadata, category: &str| {
+            // A failed writer reports its error from `finish`
+            let _ = sender.send(Message::File(file.clone(), category.to_string()));
+        })
+    }
+
+    /// Write the remaining files, the directory totals and the scan summary.
+    pub fn finish(self, results: &ScanResults) -> Result<i64, SqliteError> {
+        let error = |error| SqliteError { path: self.db_path.clone(), error };
+
+        let _ = self.sender.send(Message::Finish);
+        let writer = match self.writer.join() {
+            Ok(writer) => writer.map_err(error)?,
+            Err(panic) => std::panic::resume_unwind(panic),
+        };
+        writer.finish(results).map_err(error)?;
+        Ok(self.scan_id)
+    }
+}
+
+struct Directory {
+    id: i64,
+    parent: Option<usize>,
+    depth: usize,
+    bytes: u64,
+    disk_bytes: u64,
+    file_count: u64,
+}
+
+struct Writer {
+    conn: Connection,
+    scan_id: i64,
+    root: PathBuf,
+    /// Labels for category keys
+    categorizer: Box<dyn Categorizer>,
+    /// Category key -> `categories.id`
+    categories: HashMap<String, i64>,
+    directories: Vec<Directory>,
+    /// Directory path -> index into `directories`
+    directory_index: HashMap<PathBuf, usize>,
+}
+
+impl Writer {
+    fn run(mut self, receiver: Receiver<Message>) -> rusqlite::Result<Self> {
+        self.conn.execute_batch("BEGIN")?;
+        let mut pending = 0;
+        for message in receiver {
+            match message {
+                Message::File(file, category) => self.insert_file(&file, category)?,
+                Message::Finish => break,
+            }
+            pending += 1;
+            if pending == BATCH_SIZE {
+                self.conn.execute_batch("COMMIT; BEGIN")?;
+                pending = 0;
+            }
+        }
+        self.conn.execute_batch("COMMIT")?;
+        Ok(self)
+    }
+
+    fn insert_file(&mut self, file: &FileMetadata, category: String) -> rusqlite::Result<()> {
+        let parent = file.path.parent().unwrap_or(&self.root).to_path_buf();
+        let directory = self.directory(&parent)?;
+        let category = self.category(category)?;
+
+        let dir = &mut self.directories[directory];
+        dir.bytes += file.size;
+        dir.disk_bytes += file.disk_size;
+        dir.file_count += 1;
+        let directory_id = dir.id;
+
+        let modified = file
+            .modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        let name = file.path.file_name().unwrap_or(file.path.as_os_str());
+        self.conn
+            .prepare_cached(
+                "INSERT INTO files (scan_id, directory_id, category_id, name, extension, size, disk_size, modified)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?
+            .execute(params![
+                self.scan_id,
+                directory_id,
+                category,
+                name.to_string_lossy(),
+                file.extension,
+                file.size as i64,
+                file.disk_size as i64,
+                modified,
+            ])?;
+        Ok(())
+    }
+
+    /// Index into `directories` for `path`, inserting it and any missing
+    /// parents up to the scan root.
+    fn directory(&mut self, path: &Path) -> rusqlite::Result<usize> {
+        if let Some(&index) = self.directory_index.get(path) {
+            return Ok(index);
+        }
+
+        let parent = match path.parent() {
+            Some(parent) if path != self.root && path.starts_with(&self.root) => Some(self.directory(parent)?),
+            _ => None,
+        };
+        let depth = parent.map_or(0, |p| self.directories[p].depth + 1);
+        let parent_id = parent.map(|p| self.directories[p].id);
+
+        self.conn
+            .prepare_cached("INSERT INTO directories (scan_id, parent_id, path, depth) VALUES (?1, ?2, ?3, ?4)")?
+            .execute(params![self.scan_id, parent_id, path.to_string_lossy(), depth as i64])?;
+        self.directories.push(Directory {
+            id: self.conn.last_insert_rowid(),
+            parent,
+            depth,
+            bytes: 0,
+            disk_bytes: 0,
+            file_count: 0,
+        });
+        let index = self.directories.len() - 1;
+        self.directory_index.insert(path.to_path_buf(), index);
+        Ok(index)
+    }
+
+    /// `categories.id` of the category with `key`.
+    fn category(&mut self, key: String) -> rusqlite::Result<i64> {
+        if let Some(&id) = self.categories.get(&key) {
+            return Ok(id);
+        }
+
+        self.conn
+            .prepare_cached("INSERT INTO categories (scan_id, key, label) VALUES (?1, ?2, ?3)")?
+            .execute(params![self.scan_id, key, self.categorizer.get_label(&key)])?;
+        let id = self.conn.last_insert_rowid();
+        self.categories.insert(key, id);
+        Ok(id)
+    }
+
+    fn finish(mut self, results: &ScanResults) -> rusqlite::Result<()> {
+        // Roll direct totals up so every directory covers everything below it
+        let mut order: Vec<usize> = (0..self.directories.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.directories[i].depth));
+        for i in order {
+            if let Some(parent) = self.directories[i].parent {
+                let (bytes, disk_bytes, file_count) = {
+                    let dir = &self.directories[i];
+                    (dir.bytes, dir.disk_bytes, dir.file_count)
+                };
+                let parent = &mut self.directories[parent];
+                parent.bytes += bytes;
+                parent.disk_bytes += disk_bytes;
+                parent.file_count += file_count;
+            }
+        }
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut update =
+                tx.prepare("UPDATE directories SET bytes = ?2, disk_bytes = ?3, file_count = ?4 WHERE id = ?1")?;
+            for dir in &self.directories {
+                update.execute(params![dir.id, dir.bytes as i64, dir.disk_bytes as i64, dir.file_count as i64])?;
+            }
+        }
+        tx.execute(
+            "UPDATE scans SET finished_at = ?2, mode = ?3, size_mode = ?4, total_bytes = ?5, disk_bytes = ?6,
+                 file_count = ?7, dir_count = ?8, warning_count = ?9
+             WHERE id = ?1",
+            params![
+                self.scan_id,
+                chrono::Local::now().to_rfc3339(),
+                results.mode,
+                results.size_mode.as_str(),
+                results.totals.total_bytes as i64,
+                results.totals.disk_bytes as i64,
+                results.totals.file_count as i64,
+                results.totals.dir_count as i64,
+                results.warnings.len() as i64,
+            ],
+        )?;
+        tx.execute_batch(INDEXES)?;
+        tx.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::ChangeKind;
+    use crate::ScanOptions;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn scan_into(db: &Path, root: &Path) -> i64 {
+        let options = ScanOptions::new();
+        let index = SqliteIndex::create(db, root, options.build_categorizer(root)).unwrap();
+        let results = crate::scan(root, options.on_file(Some(index.observer()))).unwrap();
+        index.finish(&results).unwrap()
+    }
+
+    #[test]
+    fn test_index_files_and_directories() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/main.rs"), vec![0u8; 300]).unwrap();
+        fs::write(root.join("src/nested/table.parquet"), vec![0u8; 2000]).unwrap();
+        fs::write(root.join("notes.txt"), vec![0u8; 10]).unwrap();
+        let db = dir.path().join("index.db");
+
+        let scan_id = scan_into(&db, &root);
+
+        let conn = Connection::open(&db).unwrap();
+        let (files, bytes): (i64, i64) = conn
+            .query_row("SELECT file_count, total_bytes FROM scans WHERE id = ?1", [scan_id], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((files, bytes), (3, 2310));
+
+        let path: String = conn
+            .query_row(
+                "SELECT path FROM file_paths WHERE scan_id = ?1 AND extension = 'parquet' AND size > 1000",
+                [scan_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(path, root.join("src/nested/table.parquet").to_string_lossy());
+
+        // Directory totals include everything below them
+        let totals: Vec<(String, i64, i64)> = conn
+            .prepare("SELECT path, bytes, file_count FROM directories WHERE scan_id = ?1 ORDER BY depth, path")
+            .unwrap()
+            .query_map([scan_id], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let root = root.to_string_lossy().into_owned();
+        assert_eq!(
+            totals,
+            [
+                (root.clone(), 2310, 3),
+                (format!("{}/src", root), 2300, 2),
+                (format!("{}/src/nested", root), 2000, 1),
+            ]
+        );
+    }
+
+    /// Type categorizer that counts how often it is asked.
+    #[derive(Clone)]
+    struct CountingCategorizer(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Categorizer for CountingCategorizer {
+        fn categorize(&self, metadata: &FileMetadata) -> std::borrow::Cow<'static, str> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            crate::categorize::TypeCategorizer::new().categorize(metadata)
+        }
+
+        fn get_label(&self, key: &str) -> String {
+            key.to_uppercase()
+        }
+
+        fn clone_box(&self) -> Box<dyn Categorizer> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_files_keep_the_scans_category() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        fs::write(root.join("notes.txt"), b"hello").unwrap();
+        fs::write(root.join("photo.png"), b"png").unwrap();
+        let db = dir.path().join("index.db");
+
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let options = ScanOptions::new().categorizer(Box::new(CountingCategorizer(calls.clone())));
+        let index = SqliteIndex::create(&db, &root, options.build_categorizer(&root)).unwrap();
+        let results = crate::scan(&root, options.on_file(Some(index.observer()))).unwrap();
+        let scan_id = index.finish(&results).unwrap();
+
+        // Each file is categorized once, by the scan
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 3);
+        let conn = Connection::open(&db).unwrap();
+        let categories: Vec<(String, String)> = conn
+            .prepare("SELECT key, label FROM categories WHERE scan_id = ?1 ORDER BY key")
+            .unwrap()
+            .query_map([scan_id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut keys: Vec<_> = results.buckets.iter().map(|b| (b.key.clone(), b.key.to_uppercase())).collect();
+        keys.sort();
+        assert_eq!(categories, keys);
+    }
+
+    #[test]
+    fn test_scans_are_appended() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.txt"), b"hello").unwrap();
+        let db = dir.path().join("index.db");
+
+        let first = scan_into(&db, &root);
+        fs::write(root.join("b.txt"), b"world!").unwrap();
+        let second = scan_into(&db, &root);
+        assert_ne!(first, second);
+
+        // New files show up by comparing the two scans
+        let conn = Connection::open(&db).unwrap();
+        let added: Vec<String> = conn
+            .prepare(
+                "SELECT name FROM files WHERE scan_id = ?2
+                 AND name NOT IN (SELECT name FROM files WHERE scan_id = ?1)",
+            )
+            .unwrap()
+            .query_map([first, second], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(added, ["b.txt"]);
+    }
+
+    #[test]
+    fn test_compare_loaded_scans() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("logs/a.log"), vec![0u8; 100]).unwrap();
+        fs::write(root.join("notes.txt"), vec![0u8; 10]).unwrap();
+        let db = dir.path().join("index.db");
+
+        let first = scan_into(&db, &root);
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        fs::write(root.join("logs/a.log"), vec![0u8; 150]).unwrap();
+        scan_into(&db, &root);
+
+        let (before, before_index) = crate::compare::load_scan(Path::new(&format!("{}#{}", db.display(), first))).unwrap();
+        let (after, after_index) = crate::compare::load_scan(&db).unwrap();
+        assert_eq!((before.totals.total_bytes, after.totals.total_bytes), (110, 150));
+        assert_eq!(after.buckets.len(), 1);
+        let top: Vec<_> = before.top_files.iter().map(|f| f.bytes).collect();
+        assert_eq!(top, [100, 10]);
+        assert_eq!(before.top_dirs.first().map(|d| d.bytes), Some(110));
+
+        // Every file is indexed, so even the smallest removal shows up
+        let comparison = crate::compare::compare_indexed(&before, &before_index, &after, &after_index);
+        assert!(comparison.complete);
+        let files: Vec<_> = comparison.files.iter().map(|c| (c.kind, c.delta)).collect();
+        assert_eq!(files, [(ChangeKind::Grown, 50), (ChangeKind::Removed, -10)]);
+        let dirs: Vec<_> = comparison.directories.iter().map(|c| (c.kind, c.delta)).collect();
+        assert_eq!(dirs, [(ChangeKind::Grown, 50), (ChangeKind::Grown, 40)]);
+    }
+}
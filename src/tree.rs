@@ -0,0 +1,467 @@
+use crate::categorize::Categorizer;
+use crate::types::FileMetadata;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+pub type NodeId = usize;
+
+/// Sizes and counts of a file, or of everything below a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeTotals {
+    pub bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    /// Most recent modification time of any file below
+    pub newest: Option<SystemTime>,
+}
+
+impl NodeTotals {
+    fn add(&mut self, other: &NodeTotals) {
+        self.bytes += other.bytes;
+        self.disk_bytes += other.disk_bytes;
+        self.file_count += other.file_count;
+        self.newest = self.newest.max(other.newest);
+    }
+
+    /// Take out `other`'s sizes; `newest` has to be recomputed by the caller.
+    fn subtract(&mut self, other: &NodeTotals) {
+        self.bytes = self.bytes.saturating_sub(other.bytes);
+        self.disk_bytes = self.disk_bytes.saturating_sub(other.disk_bytes);
+        self.file_count = self.file_count.saturating_sub(other.file_count);
+    }
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: OsString,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub is_dir: bool,
+    pub totals: NodeTotals,
+    /// Category of a file under the tree's categorizer
+    category: usize,
+    /// Totals of a directory per category, indexed like `FileTree::categories`
+    by_category: Vec<NodeTotals>,
+}
+
+/// A category seen by the tree's categorizer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeCategory {
+    pub key: String,
+    pub label: String,
+}
+
+/// In-memory tree of a scan, built from the files reported while scanning.
+///
+/// Every directory keeps running totals for its subtree, both overall and
+/// per category of the current [`Categorizer`], so browsing or filtering a
+/// directory only looks at its direct children. Directories without any
+/// counted files don't appear.
+pub struct FileTree {
+    root: PathBuf,
+    nodes: Vec<Node>,
+    dirs: HashMap<PathBuf, NodeId>,
+    categorizer: Box<dyn Categorizer>,
+    categories: Vec<TreeCategory>,
+    category_index: HashMap<String, usize>,
+}
+
+impl FileTree {
+    pub fn new(root: &Path, categorizer: Box<dyn Categorizer>) -> Self {
+        let root_node = Node {
+            name: root.as_os_str().to_os_string(),
+            parent: None,
+            children: Vec::new(),
+            is_dir: true,
+            totals: NodeTotals::default(),
+            category: 0,
+            by_category: Vec::new(),
+        };
+
+        Self {
+            root: root.to_path_buf(),
+            nodes: vec![root_node],
+            dirs: HashMap::from([(PathBuf::new(), 0)]),
+            categorizer,
+            categories: Vec::new(),
+            category_index: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// Categories of the current categorizer that at least one file fell into,
+    /// in the order they were first seen.
+    pub fn categories(&self) -> &[TreeCategory] {
+        &self.categories
+    }
+
+    /// Category of a file; `None` for directories.
+    pub fn category(&self, id: NodeId) -> Option<&TreeCategory> {
+        let node = &self.nodes[id];
+        (!node.is_dir).then(|| &self.categories[node.category])
+    }
+
+    /// The node at `path`, if it is in the tree.
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        if let Some(&id) = self.dirs.get(relative) {
+            return Some(id);
+        }
+
+        let parent = self.dirs.get(relative.parent()?)?;
+        let name = relative.file_name()?;
+        self.nodes[*parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].name == name)
+    }
+
+    /// Add a file reported by the scanner. Files outside the root are ignored.
+    pub fn insert(&mut self, file: &FileMetadata) {
+        if file.path.starts_with(&self.root) {
+            let key = self.categorizer.categorize(file);
+            self.insert_categorized(file, &key);
+        }
+    }
+
+    /// Like [`insert`](Self::insert), for a file already filed under the
+    /// category `key` of the tree's categorizer, e.g. by the scan.
+    pub fn insert_categorized(&mut self, file: &FileMetadata, key: &str) {
+        let Ok(relative) = file.path.strip_prefix(&self.root) else {
+            return;
+        };
+        let Some(name) = relative.file_name() else {
+            return;
+        };
+
+        let parent = self.dir(relative.parent().unwrap_or(Path::new("")));
+        let category = self.category_of(key);
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_os_string(),
+            parent: Some(parent),
+            children: Vec::new(),
+            is_dir: false,
+            totals: NodeTotals {
+                bytes: file.size,
+                disk_bytes: file.disk_size,
+                file_count: 1,
+                newest: file.modified,
+            },
+            category,
+            by_category: Vec::new(),
+        });
+        self.nodes[parent].children.push(id);
+        self.add_to_ancestors(id);
+    }
+
+    /// Take `id` and everything below it out of the tree, e.g. after it was
+    /// deleted. The root can't be removed.
+    pub fn remove(&mut self, id: NodeId) {
+        let Some(parent) = self.nodes[id].parent else {
+            return;
+        };
+
+        if self.nodes[id].is_dir {
+            if let Ok(relative) = self.path(id).strip_prefix(&self.root) {
+                // Directories below go too, so files added later get new nodes
+                self.dirs.retain(|dir, _| !dir.starts_with(relative));
+            }
+        }
+
+        let totals = self.nodes[id].totals;
+        let by_category: Vec<NodeTotals> = (0..self.categories.len())
+            .map(|c| self.totals(id, Some(c)))
+            .collect();
+        self.nodes[parent].children.retain(|&child| child != id);
+        self.nodes[id].parent = None;
+
+        let mut current = Some(parent);
+        while let Some(ancestor) = current {
+            let mut newest = None;
+            let mut newest_by_category = vec![None; by_category.len()];
+            for &child in &self.nodes[ancestor].children {
+                newest = newest.max(self.nodes[child].totals.newest);
+                for (c, slot) in newest_by_category.iter_mut().enumerate() {
+                    *slot = (*slot).max(self.totals(child, Some(c)).newest);
+                }
+            }
+
+            let node = &mut self.nodes[ancestor];
+            node.totals.subtract(&totals);
+            node.totals.newest = newest;
+            for (c, removed) in by_category.iter().enumerate() {
+                if let Some(category_totals) = node.by_category.get_mut(c) {
+                    category_totals.subtract(removed);
+                    category_totals.newest = newest_by_category[c];
+                }
+            }
+            current = node.parent;
+        }
+    }
+
+    /// Totals of `id`, counting only files in `category` if given.
+    pub fn totals(&self, id: NodeId, category: Option<usize>) -> NodeTotals {
+        let node = &self.nodes[id];
+        match category {
+            None => node.totals,
+            Some(c) if node.is_dir => node.by_category.get(c).copied().unwrap_or_default(),
+            Some(c) if node.category == c => node.totals,
+            Some(_) => NodeTotals::default(),
+        }
+    }
+
+    /// Full path of a node.
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(node_id) = current {
+            if node_id == self.root() {
+                break;
+            }
+            names.push(&self.nodes[node_id].name);
+            current = self.nodes[node_id].parent;
+        }
+
+        let mut path = self.root.clone();
+        path.extend(names.into_iter().rev());
+        path
+    }
+
+    /// Categorize every file again with `categorizer`.
+    pub fn set_categorizer(&mut self, categorizer: Box<dyn Categorizer>) {
+        self.categorizer = categorizer;
+        self.categories.clear();
+        self.category_index.clear();
+        for node in &mut self.nodes {
+            node.by_category.clear();
+        }
+
+        for id in 0..self.nodes.len() {
+            if self.nodes[id].is_dir || !self.attached(id) {
+                continue;
+            }
+            // The full path, for path rules and --sniff
+            let path = self.path(id);
+            let node = &self.nodes[id];
+            let file = FileMetadata {
+                extension: path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()),
+                path,
+                size: node.totals.bytes,
+                disk_size: node.totals.disk_bytes,
+                modified: node.totals.newest,
+            };
+            let key = self.categorizer.categorize(&file);
+            self.nodes[id].category = self.category_of(&key);
+            self.add_to_categories(id);
+        }
+    }
+
+    /// Whether `id` is still reachable from the root.
+    fn attached(&self, id: NodeId) -> bool {
+        let mut current = id;
+        while let Some(parent) = self.nodes[current].parent {
+            current = parent;
+        }
+        current == self.root()
+    }
+
+    /// The directory node for `relative`, created along with its missing
+    /// ancestors.
+    fn dir(&mut self, relative: &Path) -> NodeId {
+        if let Some(&id) = self.dirs.get(relative) {
+            return id;
+        }
+
+        let parent = match relative.parent() {
+            Some(parent) => self.dir(parent),
+            None => self.root(),
+        };
+        let name = match relative.components().next_back() {
+            Some(Component::Normal(name)) => name.to_os_string(),
+            _ => relative.as_os_str().to_os_string(),
+        };
+
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name,
+            parent: Some(parent),
+            children: Vec::new(),
+            is_dir: true,
+            totals: NodeTotals::default(),
+            category: 0,
+            by_category: Vec::new(),
+        });
+        self.nodes[parent].children.push(id);
+        self.dirs.insert(relative.to_path_buf(), id);
+        id
+    }
+
+    /// Index into `categories` of the category `key`.
+    fn category_of(&mut self, key: &str) -> usize {
+        if let Some(&index) = self.category_index.get(key) {
+            return index;
+        }
+
+        let index = self.categories.len();
+        self.categories.push(TreeCategory {
+            key: key.to_string(),
+            label: self.categorizer.get_label(key),
+        });
+        self.category_index.insert(key.to_string(), index);
+        index
+    }
+
+    fn add_to_ancestors(&mut self, file: NodeId) {
+        let totals = self.nodes[file].totals;
+        let mut current = self.nodes[file].parent;
+        while let Some(id) = current {
+            self.nodes[id].totals.add(&totals);
+            current = self.nodes[id].parent;
+        }
+        self.add_to_categories(file);
+    }
+
+    fn add_to_categories(&mut self, file: NodeId) {
+        let totals = self.nodes[file].totals;
+        let category = self.nodes[file].category;
+        let mut current = self.nodes[file].parent;
+        while let Some(id) = current {
+            let node = &mut self.nodes[id];
+            if node.by_category.len() <= category {
+                node.by_category.resize(category + 1, NodeTotals::default());
+            }
+            node.by_category[category].add(&totals);
+            current = node.parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::categorize::{SizeCategorizer, TypeCategorizer};
+
+    fn file(path: &str, size: u64) -> FileMetadata {
+        let path = PathBuf::from(path);
+        FileMetadata {
+            extension: path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()),
+            path,
+            size,
+            disk_size: size,
+            modified: None,
+        }
+    }
+
+    fn sample_tree() -> FileTree {
+        let mut tree = FileTree::new(Path::new("/data"), Box::new(TypeCategorizer::new()));
+        tree.insert(&file("/data/src/main.rs", 300));
+        tree.insert(&file("/data/src/lib/mod.rs", 200));
+        tree.insert(&file("/data/notes.txt", 100));
+        tree.insert(&file("/elsewhere/skipped.txt", 1000));
+        tree
+    }
+
+    fn child(tree: &FileTree, parent: NodeId, name: &str) -> NodeId {
+        *tree.node(parent).children.iter().find(|&&id| tree.node(id).name == name).unwrap()
+    }
+
+    #[test]
+    fn test_totals_roll_up() {
+        let tree = sample_tree();
+        let root = tree.root();
+        assert_eq!(tree.totals(root, None).bytes, 600);
+        assert_eq!(tree.totals(root, None).file_count, 3);
+
+        let src = child(&tree, root, "src");
+        assert!(tree.node(src).is_dir);
+        assert_eq!(tree.totals(src, None).bytes, 500);
+        let lib = child(&tree, src, "lib");
+        assert_eq!(tree.path(child(&tree, lib, "mod.rs")), PathBuf::from("/data/src/lib/mod.rs"));
+    }
+
+    #[test]
+    fn test_remove_subtracts_from_ancestors() {
+        let mut tree = sample_tree();
+        let root = tree.root();
+        let src = child(&tree, root, "src");
+        let lib = child(&tree, src, "lib");
+        let code = tree.categories().iter().position(|c| c.key == "Code").unwrap();
+
+        tree.remove(lib);
+        assert_eq!(tree.totals(src, None).bytes, 300);
+        assert_eq!(tree.totals(root, None).file_count, 2);
+        assert_eq!(tree.totals(root, Some(code)).bytes, 300);
+        assert_eq!(tree.node(src).children.len(), 1);
+
+        // Recategorizing doesn't bring it back
+        tree.set_categorizer(Box::new(TypeCategorizer::new()));
+        let code = tree.categories().iter().position(|c| c.key == "Code").unwrap();
+        assert_eq!(tree.totals(root, Some(code)).bytes, 300);
+    }
+
+    #[test]
+    fn test_find_and_reinsert_after_remove() {
+        let mut tree = sample_tree();
+        let src = tree.find(Path::new("/data/src")).unwrap();
+        assert_eq!(tree.find(Path::new("/data/src/lib/mod.rs")).map(|id| tree.path(id)), Some(PathBuf::from("/data/src/lib/mod.rs")));
+        assert_eq!(tree.find(Path::new("/data/missing.rs")), None);
+
+        // Files put back below a removed directory are counted again
+        tree.remove(src);
+        assert_eq!(tree.find(Path::new("/data/src/lib")), None);
+        tree.insert(&file("/data/src/lib/mod.rs", 250));
+        assert_eq!(tree.totals(tree.root(), None).bytes, 350);
+        let lib = tree.find(Path::new("/data/src/lib")).unwrap();
+        assert_eq!(tree.totals(lib, None).bytes, 250);
+    }
+
+    #[test]
+    fn test_category_totals() {
+        let mut tree = sample_tree();
+        let code = tree.categories().iter().position(|c| c.key == "Code").unwrap();
+        let root = tree.root();
+        assert_eq!(tree.totals(root, Some(code)).bytes, 500);
+        assert_eq!(tree.totals(child(&tree, root, "notes.txt"), Some(code)).bytes, 0);
+
+        // Switching categorizers regroups the same files
+        tree.set_categorizer(Box::new(SizeCategorizer::new(Some(vec![0, 150]))));
+        let total: u64 = (0..tree.categories().len()).map(|c| tree.totals(root, Some(c)).bytes).sum();
+        assert_eq!(total, 600);
+        assert_eq!(tree.categories().len(), 2);
+    }
+
+    #[test]
+    fn test_recategorize_matches_full_paths() {
+        let config: crate::config::SpacemapConfig =
+            toml::from_str("[[categories]]\nname = \"Build\"\npaths = [\"/data/src/lib/**\"]\n").unwrap();
+        let mut tree = sample_tree();
+        tree.set_categorizer(Box::new(TypeCategorizer::with_config(Some(&config))));
+        let build = tree.categories().iter().position(|c| c.key == "Build").unwrap();
+        let root = tree.root();
+        assert_eq!(tree.totals(root, Some(build)).bytes, 200);
+    }
+
+    #[test]
+    fn test_insert_categorized_keeps_the_given_category() {
+        let mut tree = FileTree::new(Path::new("/data"), Box::new(TypeCategorizer::new()));
+        tree.insert_categorized(&file("/data/main.rs", 300), "Build");
+        tree.insert_categorized(&file("/elsewhere/skipped.rs", 1000), "Build");
+        let build = tree.categories().iter().position(|c| c.key == "Build").unwrap();
+        assert_eq!(tree.totals(tree.root(), Some(build)).bytes, 300);
+    }
+}
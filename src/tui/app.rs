@@ -0,0 +1,389 @@
+use crate::categorize::Categorizer;
+use crate::clean::{self, CleanPlan, Cleaner, Outcome, RemovalMethod};
+use crate::engine::{ScanMode, ScanOptions};
+use crate::tree::{FileTree, NodeId, NodeTotals};
+use crate::types::{FileMetadata, ScanResults, SizeMode};
+use humansize::{format_size, BINARY};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::widgets::ListState;
+use std::cmp::Reverse;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Order of the entries in the current directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    /// Largest first
+    #[default]
+    Size,
+    /// Most files first
+    Count,
+    /// Least recently modified first
+    Age,
+}
+
+impl SortKey {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Size => "size",
+            SortKey::Count => "count",
+            SortKey::Age => "age",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            SortKey::Size => SortKey::Count,
+            SortKey::Count => SortKey::Age,
+            SortKey::Age => SortKey::Size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanStatus {
+    Running { started: Instant },
+    Done { elapsed: Duration, warnings: usize },
+}
+
+/// A removal waiting for the user to confirm it.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub id: NodeId,
+    pub method: RemovalMethod,
+    pub plan: CleanPlan,
+}
+
+/// A row of the directory listing.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub id: NodeId,
+    pub name: String,
+    pub is_dir: bool,
+    pub totals: NodeTotals,
+}
+
+/// State of the browser: the tree built so far and where the user is in it.
+pub struct App {
+    tree: FileTree,
+    size_mode: SizeMode,
+    /// Built-in categorizers available for the filter, with the scan's config
+    categorizers: Vec<(ScanMode, Box<dyn Categorizer>)>,
+    categorizer: usize,
+    /// Index of the categorizer the scan files with, unless it is a custom one
+    scan_categorizer: Option<usize>,
+    filter: Option<usize>,
+    sort: SortKey,
+    cwd: NodeId,
+    /// Selected entry, tracked by node so it stays put while sizes change
+    selected: Option<NodeId>,
+    pub(super) list_state: ListState,
+    status: ScanStatus,
+    /// Results of the finished scan, kept up to date as entries are removed
+    results: Option<ScanResults>,
+    /// Options used to measure entries before removing them
+    options: ScanOptions,
+    cleaner: Cleaner,
+    prompt: Option<Prompt>,
+    /// Outcome of the last action, shown until the next key press
+    message: Option<String>,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(root: &Path, options: &ScanOptions) -> Self {
+        let categorizers: Vec<_> = [ScanMode::Type, ScanMode::Size, ScanMode::Age]
+            .into_iter()
+            .map(|mode| (mode, options.categorizer_for(mode, root)))
+            .collect();
+        let scan_categorizer = categorizers
+            .iter()
+            .position(|(mode, _)| *mode == options.mode)
+            .filter(|_| !options.has_categorizer());
+        let categorizer = scan_categorizer.unwrap_or(0);
+
+        let tree = FileTree::new(root, categorizers[categorizer].1.clone_box());
+        Self {
+            cwd: tree.root(),
+            tree,
+            size_mode: options.size_mode,
+            categorizers,
+            categorizer,
+            scan_categorizer,
+            filter: None,
+            sort: SortKey::Size,
+            selected: None,
+            list_state: ListState::default(),
+            status: ScanStatus::Running { started: Instant::now() },
+            results: None,
+            options: options.clone().on_file(None),
+            cleaner: Cleaner::default(),
+            prompt: None,
+            message: None,
+            quit: false,
+        }
+    }
+
+    /// Use `cleaner` instead of the default trash and audit log.
+    pub fn with_cleaner(mut self, cleaner: Cleaner) -> Self {
+        self.cleaner = cleaner;
+        self
+    }
+
+    pub fn add_file(&mut self, file: &FileMetadata) {
+        self.tree.insert(file);
+    }
+
+    /// Add a file the scan filed under `category`, which is reused unless the
+    /// user has switched to another categorizer since.
+    pub fn add_scanned_file(&mut self, file: &FileMetadata, category: &str) {
+        if self.scan_categorizer == Some(self.categorizer) {
+            self.tree.insert_categorized(file, category);
+        } else {
+            self.tree.insert(file);
+        }
+    }
+
+    pub fn finish(&mut self, results: ScanResults) {
+        if let ScanStatus::Running { started } = self.status {
+            self.status = ScanStatus::Done {
+                elapsed: started.elapsed(),
+                warnings: results.warnings.len(),
+            };
+        }
+        self.results = Some(results);
+    }
+
+    pub fn tree(&self) -> &FileTree {
+        &self.tree
+    }
+
+    pub fn status(&self) -> ScanStatus {
+        self.status
+    }
+
+    pub fn sort(&self) -> SortKey {
+        self.sort
+    }
+
+    pub fn size_mode(&self) -> SizeMode {
+        self.size_mode
+    }
+
+    pub fn cwd(&self) -> NodeId {
+        self.cwd
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Results of the finished scan, less anything removed since.
+    pub fn results(&self) -> Option<&ScanResults> {
+        self.results.as_ref()
+    }
+
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// The categorizer behind the filter, and the category filtered on.
+    pub fn filter(&self) -> (ScanMode, Option<&str>) {
+        let label = self
+            .filter
+            .and_then(|c| self.tree.categories().get(c))
+            .map(|c| c.label.as_str());
+        (self.categorizers[self.categorizer].0, label)
+    }
+
+    /// Size of `totals` in the selected size mode.
+    pub fn size_of(&self, totals: &NodeTotals) -> u64 {
+        self.size_mode.select(totals.bytes, totals.disk_bytes)
+    }
+
+    /// Totals of the current directory under the active filter.
+    pub fn cwd_totals(&self) -> NodeTotals {
+        self.tree.totals(self.cwd, self.filter)
+    }
+
+    /// Children of the current directory under the active filter, sorted.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .tree
+            .node(self.cwd)
+            .children
+            .iter()
+            .map(|&id| {
+                let node = self.tree.node(id);
+                Entry {
+                    id,
+                    name: node.name.to_string_lossy().into_owned(),
+                    is_dir: node.is_dir,
+                    totals: self.tree.totals(id, self.filter),
+                }
+            })
+            .filter(|entry| entry.totals.file_count > 0)
+            .collect();
+
+        match self.sort {
+            SortKey::Size => entries.sort_by_key(|e| (Reverse(self.size_of(&e.totals)), e.name.clone())),
+            SortKey::Count => entries.sort_by_key(|e| (Reverse(e.totals.file_count), e.name.clone())),
+            // Entries without a known time go last
+            SortKey::Age => entries.sort_by_key(|e| (e.totals.newest.is_none(), e.totals.newest, e.name.clone())),
+        }
+        entries
+    }
+
+    /// Index of the selected entry in `entries`.
+    pub fn selected_index(&self, entries: &[Entry]) -> Option<usize> {
+        if entries.is_empty() {
+            return None;
+        }
+        let index = self
+            .selected
+            .and_then(|id| entries.iter().position(|e| e.id == id))
+            .unwrap_or(0);
+        Some(index)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        self.message = None;
+        if let Some(prompt) = self.prompt.take() {
+            if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                self.remove(prompt);
+            } else {
+                self.message = Some("Nothing was removed".to_string());
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-20),
+            KeyCode::PageDown => self.move_selection(20),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open_selected(),
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => self.go_up(),
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('c') => self.next_category(),
+            KeyCode::Char('m') => self.next_categorizer(),
+            KeyCode::Char('d') => self.ask_remove(RemovalMethod::Trash),
+            KeyCode::Char('D') => self.ask_remove(RemovalMethod::Delete),
+            _ => {}
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let entries = self.entries();
+        let Some(current) = self.selected_index(&entries) else {
+            return;
+        };
+        let target = current.saturating_add_signed(delta).min(entries.len() - 1);
+        self.selected = Some(entries[target].id);
+    }
+
+    fn open_selected(&mut self) {
+        let entries = self.entries();
+        if let Some(entry) = self.selected_index(&entries).map(|i| &entries[i]) {
+            if entry.is_dir {
+                self.cwd = entry.id;
+                self.selected = None;
+                self.list_state = ListState::default();
+            }
+        }
+    }
+
+    fn go_up(&mut self) {
+        if let Some(parent) = self.tree.node(self.cwd).parent {
+            // Come back to the directory we were in
+            self.selected = Some(self.cwd);
+            self.cwd = parent;
+            self.list_state = ListState::default();
+        }
+    }
+
+    /// Measure the selected entry and ask before removing it.
+    fn ask_remove(&mut self, method: RemovalMethod) {
+        if let ScanStatus::Running { .. } = self.status {
+            self.message = Some("Wait for the scan to finish before removing anything".to_string());
+            return;
+        }
+        let entries = self.entries();
+        let Some(id) = self.selected_index(&entries).map(|i| entries[i].id) else {
+            return;
+        };
+
+        let path = self.tree.path(id);
+        match CleanPlan::new(&[path], &self.options, self.cleaner.trash()) {
+            Ok(plan) => self.prompt = Some(Prompt { id, method, plan }),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn remove(&mut self, prompt: Prompt) {
+        let report = match self.cleaner.execute(&prompt.plan, prompt.method) {
+            Ok(report) => report,
+            Err(e) => {
+                self.message = Some(e.to_string());
+                return;
+            }
+        };
+
+        let Some((target, outcome)) = report.outcomes.first() else {
+            return;
+        };
+        let name = target.path.display();
+        self.message = Some(match outcome {
+            Outcome::Failed(e) => format!("Could not remove {}: {}", name, e),
+            Outcome::Trashed(_) => format!(
+                "Moved {} to the trash, freeing {}",
+                name,
+                format_size(report.freed_bytes(), BINARY)
+            ),
+            Outcome::Deleted => format!(
+                "Deleted {}, freeing {}",
+                name,
+                format_size(report.freed_bytes(), BINARY)
+            ),
+        });
+        if matches!(outcome, Outcome::Failed(_)) {
+            return;
+        }
+
+        self.tree.remove(prompt.id);
+        if let Some(results) = self.results.as_mut() {
+            clean::update_results(results, report.removed());
+        }
+        if self.selected == Some(prompt.id) {
+            self.selected = None;
+        }
+    }
+
+    /// Cycle the filter through "all" and each category seen so far.
+    fn next_category(&mut self) {
+        let count = self.tree.categories().len();
+        self.filter = match self.filter {
+            None if count > 0 => Some(0),
+            Some(c) if c + 1 < count => Some(c + 1),
+            _ => None,
+        };
+    }
+
+    fn next_categorizer(&mut self) {
+        self.categorizer = (self.categorizer + 1) % self.categorizers.len();
+        self.tree.set_categorizer(self.categorizers[self.categorizer].1.clone_box());
+        self.filter = None;
+    }
+}
@@ -0,0 +1,299 @@
+//! Interactive browser (`spacemap tui`).
+//!
+//! The scan runs on a background thread and reports every file through
+//! [`ScanOptions::on_file`]; the UI thread folds them into a [`FileTree`]
+//! between frames, so the tree can be browsed while the scan is running.
+//!
+//! [`FileTree`]: crate::FileTree
+
+mod app;
+mod ui;
+
+pub use app::{App, Entry, Prompt, ScanStatus, SortKey};
+pub use ui::{draw, format_age};
+
+use crate::engine::{scan, FileObserver, ScanError, ScanOptions};
+use crate::types::FileMetadata;
+use ratatui::backend::Backend;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::Terminal;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Files folded into the tree per frame, so input stays responsive during
+/// large scans.
+const FILES_PER_FRAME: usize = 200_000;
+
+/// How long to wait for input before redrawing.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum TuiError {
+    Io(io::Error),
+    Scan(ScanError),
+}
+
+impl std::fmt::Display for TuiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TuiError::Io(e) => write!(f, "Terminal error: {}", e),
+            TuiError::Scan(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TuiError {}
+
+/// Scan `path` with `options` and browse the results until the user quits.
+///
+/// Quitting before the scan finishes abandons it. A scan that fails (e.g. on
+/// an invalid exclude pattern) closes the browser and returns its error.
+pub fn run(path: &Path, options: ScanOptions) -> Result<(), TuiError> {
+    let mut app = App::new(path, &options);
+
+    let (sender, receiver) = mpsc::channel();
+    let observer: FileObserver = Arc::new(move |file: &FileMetadata, category: &str| {
+        // The receiver is gone once the user quits
+        let _ = sender.send((file.clone(), category.to_string()));
+    });
+    let scan_path = path.to_path_buf();
+    let handle = thread::spawn(move || scan(scan_path, options.on_file(Some(observer))));
+
+    let mut terminal = ratatui::try_init().map_err(TuiError::Io)?;
+    let result = event_loop(&mut terminal, &mut app, &receiver, handle);
+    ratatui::restore();
+    result
+}
+
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    receiver: &Receiver<(FileMetadata, String)>,
+    handle: JoinHandle<Result<crate::types::ScanResults, ScanError>>,
+) -> Result<(), TuiError> {
+    let mut scan_thread = Some(handle);
+
+    while !app.should_quit() {
+        for (file, category) in receiver.try_iter().take(FILES_PER_FRAME) {
+            app.add_scanned_file(&file, &category);
+        }
+
+        if scan_thread.as_ref().is_some_and(|h| h.is_finished()) {
+            let results = scan_thread
+                .take()
+                .map(|h| h.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+                .expect("scan thread checked above");
+            let results = results.map_err(TuiError::Scan)?;
+
+            // Everything was sent before the scan returned
+            for (file, category) in receiver.try_iter() {
+                app.add_scanned_file(&file, &category);
+            }
+            app.finish(results);
+        }
+
+        terminal.draw(|frame| draw(frame, app)).map_err(TuiError::Io)?;
+
+        if event::poll(FRAME_INTERVAL).map_err(TuiError::Io)? {
+            if let Event::Key(key) = event::read().map_err(TuiError::Io)? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clean::{Cleaner, Trash};
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+    use std::path::PathBuf;
+
+    fn file(path: &str, size: u64) -> FileMetadata {
+        let path = PathBuf::from(path);
+        FileMetadata {
+            extension: path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()),
+            path,
+            size,
+            disk_size: size,
+            modified: None,
+        }
+    }
+
+    fn sample_app() -> App {
+        let mut app = App::new(Path::new("/data"), &ScanOptions::new());
+        app.add_file(&file("/data/videos/a.mp4", 4000));
+        app.add_file(&file("/data/videos/b.mp4", 2000));
+        app.add_file(&file("/data/src/main.rs", 300));
+        app.add_file(&file("/data/src/lib.rs", 200));
+        app.add_file(&file("/data/src/notes.txt", 900));
+        app.add_file(&file("/data/readme.md", 100));
+        app
+    }
+
+    fn render(app: &mut App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 14)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::from(code));
+    }
+
+    fn row_of(screen: &str, name: &str) -> usize {
+        screen
+            .lines()
+            .position(|line| line.ends_with(name))
+            .unwrap_or_else(|| panic!("{} not on screen:\n{}", name, screen))
+    }
+
+    #[test]
+    fn test_renders_progress_header_while_scanning() {
+        let mut app = sample_app();
+        let screen = render(&mut app);
+
+        assert!(screen.contains("/data"), "{}", screen);
+        assert!(screen.contains("Scanning  6 files  7.32 KiB"), "{}", screen);
+        assert!(screen.contains("Sort: size   Filter: all (type)"), "{}", screen);
+        // Largest first
+        assert!(row_of(&screen, "videos/") < row_of(&screen, "src/"));
+        assert!(row_of(&screen, "src/") < row_of(&screen, "readme.md"));
+    }
+
+    #[test]
+    fn test_navigation_and_sorting() {
+        let mut app = sample_app();
+
+        // videos/ is selected first; move to src/ and open it
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        let screen = render(&mut app);
+        assert!(screen.contains("/data/src"), "{}", screen);
+        assert!(row_of(&screen, "notes.txt") < row_of(&screen, "main.rs"));
+
+        // Back up, with src/ still selected
+        press(&mut app, KeyCode::Backspace);
+        let entries = app.entries();
+        assert_eq!(entries[app.selected_index(&entries).unwrap()].name, "src");
+
+        press(&mut app, KeyCode::Char('s'));
+        let screen = render(&mut app);
+        assert!(screen.contains("Sort: count"), "{}", screen);
+        assert!(row_of(&screen, "src/") < row_of(&screen, "videos/"));
+    }
+
+    #[test]
+    fn test_category_filter() {
+        let mut app = sample_app();
+
+        // Cycle to the "Videos" category of the type categorizer
+        for _ in 0..app.tree().categories().len() {
+            if app.filter().1 == Some("Videos") {
+                break;
+            }
+            press(&mut app, KeyCode::Char('c'));
+        }
+        let screen = render(&mut app);
+        assert!(screen.contains("Filter: Videos (type)"), "{}", screen);
+        assert!(screen.contains("videos/"));
+        assert!(!screen.contains("src/"), "{}", screen);
+
+        // Switching to the size categorizer clears the filter
+        press(&mut app, KeyCode::Char('m'));
+        let screen = render(&mut app);
+        assert!(screen.contains("Filter: all (size)"), "{}", screen);
+        assert!(screen.contains("src/"));
+    }
+
+    #[test]
+    fn test_finished_scan_header() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("data.bin"), vec![0u8; 2048]).unwrap();
+
+        let options = ScanOptions::new();
+        let mut app = App::new(dir.path(), &options);
+        let (sender, receiver) = mpsc::channel();
+        let observer: FileObserver = Arc::new(move |file: &FileMetadata, category: &str| {
+            let _ = sender.send((file.clone(), category.to_string()));
+        });
+        let results = scan(dir.path(), options.on_file(Some(observer))).unwrap();
+        for (file, category) in receiver.try_iter() {
+            app.add_scanned_file(&file, &category);
+        }
+        app.finish(results);
+
+        let screen = render(&mut app);
+        assert!(screen.contains("Done  1 files  2 KiB"), "{}", screen);
+        assert!(screen.contains("today  data.bin"), "{}", screen);
+    }
+
+    #[test]
+    fn test_remove_to_trash() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().join("data");
+        std::fs::create_dir_all(root.join("junk")).unwrap();
+        std::fs::write(root.join("junk/big.bin"), vec![0u8; 4096]).unwrap();
+        std::fs::write(root.join("keep.txt"), vec![0u8; 100]).unwrap();
+        let cleaner = Cleaner::new(Trash::new(dir.path().join("Trash")), dir.path().join("removals.log"));
+
+        let options = ScanOptions::new();
+        let mut app = App::new(&root, &options).with_cleaner(cleaner);
+        press(&mut app, KeyCode::Char('d'));
+        assert!(app.message().unwrap().contains("Wait for the scan"));
+
+        let (sender, receiver) = mpsc::channel();
+        let observer: FileObserver = Arc::new(move |file: &FileMetadata, category: &str| {
+            let _ = sender.send((file.clone(), category.to_string()));
+        });
+        let results = scan(&root, options.on_file(Some(observer))).unwrap();
+        for (file, category) in receiver.try_iter() {
+            app.add_scanned_file(&file, &category);
+        }
+        app.finish(results);
+
+        // junk/ is largest, so selected first
+        press(&mut app, KeyCode::Char('d'));
+        let screen = render(&mut app);
+        assert!(screen.contains("junk to the trash (4 KiB in 1 files)? [y/N]"), "{}", screen);
+
+        press(&mut app, KeyCode::Char('y'));
+        let screen = render(&mut app);
+        assert!(screen.contains("Moved"), "{}", screen);
+        assert!(!screen.contains("junk/"), "{}", screen);
+        assert!(dir.path().join("Trash/files/junk/big.bin").exists());
+        assert!(!root.join("junk").exists());
+
+        let results = app.results().unwrap();
+        assert_eq!(results.totals.file_count, 1);
+        assert_eq!(results.totals.total_bytes, 100);
+        assert_eq!(app.tree().totals(app.tree().root(), None).bytes, 100);
+        let log = std::fs::read_to_string(dir.path().join("removals.log")).unwrap();
+        assert_eq!(log.lines().count(), 1);
+
+        // Anything but y cancels
+        press(&mut app, KeyCode::Char('D'));
+        press(&mut app, KeyCode::Char('n'));
+        assert!(root.join("keep.txt").exists());
+        assert_eq!(app.message(), Some("Nothing was removed"));
+    }
+}
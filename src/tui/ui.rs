@@ -0,0 +1,170 @@
+use super::app::{App, Entry, ScanStatus};
+use crate::clean::RemovalMethod;
+use humansize::{format_size, BINARY};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::Frame;
+use std::time::SystemTime;
+
+const BAR_WIDTH: usize = 20;
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, list, footer] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_header(frame, app, header);
+    draw_entries(frame, app, list);
+    draw_footer(frame, app, footer);
+}
+
+fn draw_footer(frame: &mut Frame, app: &App, area: Rect) {
+    let line = if let Some(prompt) = app.prompt() {
+        let action = match prompt.method {
+            RemovalMethod::Trash => "Move",
+            RemovalMethod::Delete => "Permanently delete",
+        };
+        let destination = match prompt.method {
+            RemovalMethod::Trash => " to the trash",
+            RemovalMethod::Delete => "",
+        };
+        let target = &prompt.plan.targets[0];
+        Line::styled(
+            format!(
+                "{} {}{} ({} in {} files)? [y/N]",
+                action,
+                target.path.display(),
+                destination,
+                format_size(app.size_mode().select(target.totals.total_bytes, target.totals.disk_bytes), BINARY),
+                target.totals.file_count
+            ),
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )
+    } else if let Some(message) = app.message() {
+        Line::raw(message.to_string())
+    } else {
+        Line::styled(
+            "↑↓ move  →/enter open  ←/backspace up  s sort  c category  m categorizer  d trash  D delete  q quit",
+            Style::default().fg(Color::DarkGray),
+        )
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
+    let tree = app.tree();
+    let root = tree.totals(tree.root(), None);
+
+    let status = match app.status() {
+        ScanStatus::Running { started } => {
+            let elapsed = started.elapsed();
+            let spinner = SPINNER[(elapsed.as_millis() / 250) as usize % SPINNER.len()];
+            Line::from(vec![
+                Span::styled(format!("{} Scanning", spinner), Style::default().fg(Color::Yellow)),
+                Span::raw(format!(
+                    "  {} files  {}  {}s",
+                    root.file_count,
+                    format_size(app.size_of(&root), BINARY),
+                    elapsed.as_secs()
+                )),
+            ])
+        }
+        ScanStatus::Done { elapsed, warnings } => {
+            let mut spans = vec![
+                Span::styled("Done", Style::default().fg(Color::Green)),
+                Span::raw(format!(
+                    "  {} files  {}  in {:.1}s",
+                    root.file_count,
+                    format_size(app.size_of(&root), BINARY),
+                    elapsed.as_secs_f64()
+                )),
+            ];
+            if warnings > 0 {
+                spans.push(Span::styled(
+                    format!("  {} unreadable", warnings),
+                    Style::default().fg(Color::Red),
+                ));
+            }
+            Line::from(spans)
+        }
+    };
+
+    let (categorizer, category) = app.filter();
+    let settings = Line::from(format!(
+        "Sort: {}   Filter: {} ({})   Size: {}",
+        app.sort().as_str(),
+        category.unwrap_or("all"),
+        categorizer.as_str(),
+        app.size_mode().as_str()
+    ));
+
+    let cwd = Line::styled(
+        tree.path(app.cwd()).display().to_string(),
+        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+    );
+
+    let block = Block::default().borders(Borders::ALL).title(" spacemap ");
+    frame.render_widget(Paragraph::new(vec![cwd, status, settings]).block(block), area);
+}
+
+fn draw_entries(frame: &mut Frame, app: &mut App, area: Rect) {
+    let entries = app.entries();
+    let total = app.size_of(&app.cwd_totals());
+    let now = SystemTime::now();
+
+    let items: Vec<ListItem> = entries.iter().map(|entry| row(app, entry, total, now)).collect();
+    let list = if items.is_empty() {
+        List::new(vec![ListItem::new("  (empty)")])
+    } else {
+        List::new(items)
+    }
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    app.list_state.select(app.selected_index(&entries));
+    frame.render_stateful_widget(list, area, &mut app.list_state);
+}
+
+fn row<'a>(app: &App, entry: &Entry, total: u64, now: SystemTime) -> ListItem<'a> {
+    let size = app.size_of(&entry.totals);
+    let fraction = if total > 0 { size as f64 / total as f64 } else { 0.0 };
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+    let bar = format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled.min(BAR_WIDTH)));
+
+    let (name, name_style) = if entry.is_dir {
+        (format!("{}/", entry.name), Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))
+    } else {
+        (entry.name.clone(), Style::default())
+    };
+
+    ListItem::new(Line::from(vec![
+        Span::styled(bar, Style::default().fg(Color::Green)),
+        Span::raw(format!(
+            " {:>10} {:>5.1}% {:>8} {:>6}  ",
+            format_size(size, BINARY),
+            fraction * 100.0,
+            entry.totals.file_count,
+            format_age(entry.totals.newest, now)
+        )),
+        Span::styled(name, name_style),
+    ]))
+}
+
+/// Time since the last modification, e.g. `today`, `12d`, `5mo`, `3y`.
+pub fn format_age(newest: Option<SystemTime>, now: SystemTime) -> String {
+    let Some(newest) = newest else {
+        return "-".to_string();
+    };
+    let days = now.duration_since(newest).map(|d| d.as_secs() / 86400).unwrap_or(0);
+    match days {
+        0 => "today".to_string(),
+        1..=59 => format!("{}d", days),
+        60..=729 => format!("{}mo", days / 30),
+        _ => format!("{}y", days / 365),
+    }
+}
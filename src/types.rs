@@ -1,3 +1,5 @@
+use crate::cleanup::CleanupSuggestion;
+use crate::policy::PolicyReport;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -5,14 +7,112 @@ use std::path::PathBuf;
 pub struct ScanResults {
     pub scanned_path: String,
     pub mode: String,
+    #[serde(default)]
+    pub size_mode: SizeMode,
+    #[serde(default = "DirTotals::legacy")]
+    pub dir_totals: DirTotals,
     pub totals: Totals,
     pub disk_usage: Option<DiskUsage>,
+    /// Every filesystem the scan touched, largest first
+    #[serde(default)]
+    pub mounts: Vec<MountUsage>,
     pub buckets: Vec<Bucket>,
     pub top_files: Vec<FileEntry>,
     pub top_dirs: Vec<DirEntry>,
     pub warnings: Vec<Warning>,
+    /// Mount points that were not crossed because of `--one-file-system`
+    #[serde(default)]
+    pub skipped_mounts: Vec<SkippedMount>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duplicates: Option<Vec<DuplicateGroup>>,
+    /// Directory cache usage, for `--cached` scans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStats>,
+    /// Regenerable directories, largest first, with `--suggest-cleanup`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<Vec<CleanupSuggestion>>,
+    /// Outcome of every policy check, with `--check-policy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyReport>,
+}
+
+/// Which size drives percentages and top-N rankings.
+///
+/// `Apparent` is the file length (`ls -l`), `Disk` is the space actually
+/// allocated on disk (`du`), which differs for sparse, compressed and small files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMode {
+    #[default]
+    Apparent,
+    Disk,
+}
+
+impl SizeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SizeMode::Apparent => "apparent",
+            SizeMode::Disk => "disk",
+        }
+    }
+
+    /// Pick the apparent or on-disk value according to this mode.
+    pub fn select(&self, apparent: u64, disk: u64) -> u64 {
+        match self {
+            SizeMode::Apparent => apparent,
+            SizeMode::Disk => disk,
+        }
+    }
+}
+
+impl std::str::FromStr for SizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "apparent" => Ok(SizeMode::Apparent),
+            "disk" => Ok(SizeMode::Disk),
+            _ => Err(format!("Invalid --size-mode: {}. Must be apparent or disk", s)),
+        }
+    }
+}
+
+/// How directory sizes in `top_dirs` are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirTotals {
+    /// Only files directly inside the directory
+    #[serde(rename = "self")]
+    SelfOnly,
+    /// Everything below the directory, rolled up to the scan root
+    #[default]
+    Cumulative,
+}
+
+impl DirTotals {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DirTotals::SelfOnly => "self",
+            DirTotals::Cumulative => "cumulative",
+        }
+    }
+
+    /// Results saved before cumulative totals existed only counted direct children.
+    fn legacy() -> Self {
+        DirTotals::SelfOnly
+    }
+}
+
+impl std::str::FromStr for DirTotals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "self" => Ok(DirTotals::SelfOnly),
+            "cumulative" => Ok(DirTotals::Cumulative),
+            _ => Err(format!("Invalid --dir-totals: {}. Must be self or cumulative", s)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,9 +131,27 @@ pub struct DiskUsage {
     pub used_percent: f64,
 }
 
+/// Bytes found on one filesystem, with its capacity when known.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountUsage {
+    pub mount_point: String,
+    pub file_system: String,
+    pub bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    /// `None` for filesystems not reported by the OS disk list (e.g. `/proc`)
+    pub total_space: Option<u64>,
+    pub available_space: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Totals {
     pub total_bytes: u64,
+    #[serde(default)]
+    pub disk_bytes: u64,
+    /// Bytes of extra hard links that were not counted again
+    #[serde(default)]
+    pub hardlink_bytes: u64,
     pub file_count: u64,
     pub dir_count: u64,
     pub skipped_paths: u64,
@@ -44,24 +162,40 @@ pub struct Bucket {
     pub key: String,
     pub label: String,
     pub bytes: u64,
+    #[serde(default)]
+    pub disk_bytes: u64,
     pub percent: f64,
     pub file_count: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub representative_extension: Option<String>,
+    /// Files (and their bytes) categorized by content with `--sniff`
+    /// rather than by extension
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub content_files: u64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub content_bytes: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
     pub path: String,
     pub bytes: u64,
+    #[serde(default)]
+    pub disk_bytes: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirEntry {
     pub path: String,
     pub bytes: u64,
+    #[serde(default)]
+    pub disk_bytes: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,10 +204,29 @@ pub struct Warning {
     pub error: String,
 }
 
+/// How many directories a cached scan reused versus re-read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Directories whose metadata was unchanged and whose listing was reused
+    pub hits: u64,
+    /// Directories that were new or changed and had to be read
+    pub misses: u64,
+}
+
+/// A directory on another filesystem that the scan did not descend into.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedMount {
+    pub path: String,
+    /// Device ID (`st_dev`) of the filesystem mounted there
+    pub device: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
     pub path: PathBuf,
     pub size: u64,
+    /// Allocated size on disk (`st_blocks * 512` on Unix, `size` elsewhere)
+    pub disk_size: u64,
     pub extension: Option<String>,
     pub modified: Option<std::time::SystemTime>,
 }
@@ -0,0 +1,604 @@
+//! `--watch`: keep the results of a scan up to date from filesystem events.
+//!
+//! After a full scan, every file lives in a [`FileTree`] whose category and
+//! directory totals are adjusted as files change, so a refresh only re-reads
+//! the paths the events name. When the OS runs out of watches the results
+//! are refreshed by rescanning on an interval instead. `[[alert]]` rules in
+//! the config run a command or call a webhook when a path or category grows
+//! past a threshold.
+
+use crate::bounded_heap::BoundedMinHeap;
+use crate::engine::{ScanError, ScanOptions};
+use crate::exclude::ExcludeMatcher;
+use crate::progress::ScanProgress;
+use crate::tree::{FileTree, NodeId, NodeTotals};
+use crate::types::{Bucket, DirEntry, DirTotals, FileEntry, ScanResults, Totals};
+use notify::event::{AccessKind, AccessMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Quiet time after an event before the results are refreshed.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest a busy filesystem can hold back a refresh.
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Alerts listed under the results.
+const RECENT_ALERTS: usize = 5;
+
+/// One `[[alert]]` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// File or directory to measure, absolute or relative to the watched
+    /// path; the whole tree if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Only count files in this category (key or label of the current `--by` mode)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Size that triggers the alert, in bytes or like `"10G"`
+    #[serde(default, deserialize_with = "crate::policy::deserialize_size", skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<u64>,
+    /// Shell command to run, with the details in `SPACEMAP_*` variables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// `http://` URL that receives the alert as a JSON POST
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+/// An alert that went off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    pub name: String,
+    /// The watched path
+    pub scanned_path: String,
+    /// What was measured
+    pub path: String,
+    pub category: Option<String>,
+    pub bytes: u64,
+    pub threshold: u64,
+}
+
+/// The `[[alert]]` rules and whether each is currently above its threshold.
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    above: Vec<bool>,
+}
+
+impl Alerts {
+    pub fn new(rules: &[AlertRule]) -> Result<Self, String> {
+        for rule in rules {
+            if rule.threshold.is_none() {
+                return Err(format!("Invalid alert '{}': threshold is required", rule.name));
+            }
+            if rule.command.is_none() && rule.webhook.is_none() {
+                return Err(format!("Invalid alert '{}': set a command or a webhook", rule.name));
+            }
+            if rule.webhook.as_ref().is_some_and(|url| !url.starts_with("http://")) {
+                return Err(format!("Invalid alert '{}': only http:// webhooks are supported", rule.name));
+            }
+        }
+        Ok(Self {
+            rules: rules.to_vec(),
+            above: vec![false; rules.len()],
+        })
+    }
+
+    /// Alerts whose rule went from below to above its threshold since the
+    /// last check. Rules already above on the first check go off too.
+    pub fn check(&mut self, live: &LiveScan) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (rule, above) in self.rules.iter().zip(self.above.iter_mut()) {
+            let threshold = rule.threshold.unwrap_or(u64::MAX);
+            let (path, bytes) = live.measure(rule.path.as_deref(), rule.category.as_deref());
+            let now_above = bytes > threshold;
+            if now_above && !*above {
+                alerts.push(Alert {
+                    name: rule.name.clone(),
+                    scanned_path: live.path.display().to_string(),
+                    path: path.display().to_string(),
+                    category: rule.category.clone(),
+                    bytes,
+                    threshold,
+                });
+            }
+            *above = now_above;
+        }
+        alerts
+    }
+
+    /// Run the command and call the webhook of `alert`'s rule in the
+    /// background. Failures are reported on stderr.
+    pub fn fire(&self, alert: &Alert) {
+        let Some(rule) = self.rules.iter().find(|r| r.name == alert.name) else {
+            return;
+        };
+
+        if let Some(ref command) = rule.command {
+            let mut shell = shell(command);
+            shell
+                .env("SPACEMAP_ALERT", &alert.name)
+                .env("SPACEMAP_SCANNED_PATH", &alert.scanned_path)
+                .env("SPACEMAP_PATH", &alert.path)
+                .env("SPACEMAP_CATEGORY", alert.category.as_deref().unwrap_or(""))
+                .env("SPACEMAP_BYTES", alert.bytes.to_string())
+                .env("SPACEMAP_THRESHOLD", alert.threshold.to_string());
+            match shell.spawn() {
+                Ok(mut child) => {
+                    std::thread::spawn(move || child.wait());
+                }
+                Err(e) => eprintln!("Error running alert '{}': {}", alert.name, e),
+            }
+        }
+
+        if let Some(ref url) = rule.webhook {
+            let url = url.clone();
+            let alert = alert.clone();
+            std::thread::spawn(move || {
+                let body = serde_json::to_value(&alert).unwrap_or_default();
+                match crate::http::post_json(&url, &body) {
+                    Ok(status) if status < 400 => {}
+                    Ok(status) => eprintln!("Webhook for alert '{}' answered {}", alert.name, status),
+                    Err(e) => eprintln!("Error calling webhook for alert '{}': {}", alert.name, e),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> std::process::Command {
+    let mut shell = std::process::Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> std::process::Command {
+    let mut shell = std::process::Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// A scan kept up to date by re-reading changed paths.
+pub struct LiveScan {
+    path: PathBuf,
+    options: ScanOptions,
+    exclude: Arc<ExcludeMatcher>,
+    tree: FileTree,
+    /// Results of the last full scan, for everything the tree doesn't track
+    base: ScanResults,
+    /// Directories in the tree right after the full scan
+    base_dirs: u64,
+}
+
+impl LiveScan {
+    /// Scan `path` in full.
+    pub fn scan(path: &Path, options: ScanOptions) -> Result<Self, ScanError> {
+        let tree = Arc::new(Mutex::new(FileTree::new(path, options.build_categorizer(path))));
+        let observer = {
+            let tree = tree.clone();
+            Arc::new(move |file: &crate::FileMetadata, category: &str| tree.lock().insert_categorized(file, category))
+        };
+        let base = crate::scan(path, options.clone().on_file(Some(observer)))?;
+        let tree = std::mem::replace(&mut *tree.lock(), FileTree::new(path, options.build_categorizer(path)));
+
+        let mut live = Self {
+            path: path.to_path_buf(),
+            exclude: Arc::new(options.exclude_matcher(path)?),
+            options,
+            tree,
+            base,
+            base_dirs: 0,
+        };
+        live.base_dirs = live.dir_count();
+        Ok(live)
+    }
+
+    pub fn tree(&self) -> &FileTree {
+        &self.tree
+    }
+
+    /// Read `changed` again: take out what the tree had there and add what
+    /// is on disk now.
+    pub fn apply(&mut self, changed: &Path) {
+        let Ok(relative) = changed.strip_prefix(&self.path) else {
+            return;
+        };
+        // Excluded directories are never scanned, so neither is anything below them
+        let mut ancestor = self.path.clone();
+        for component in relative.parent().into_iter().flat_map(|p| p.components()) {
+            ancestor.push(component);
+            if self.exclude.is_excluded(&ancestor, true) {
+                return;
+            }
+        }
+
+        if let Some(id) = self.tree.find(changed) {
+            self.tree.remove(id);
+        }
+        let depth = relative.components().count();
+        if let Some(scanner) = self.options.subtree_scanner(self.exclude.clone(), depth) {
+            let tree = &mut self.tree;
+            scanner.scan(changed, |file| tree.insert(&file), &ScanProgress::new(false));
+        }
+    }
+
+    /// The current results, in the shape of a full scan's.
+    pub fn results(&self) -> ScanResults {
+        let size_mode = self.base.size_mode;
+        let size = |totals: &NodeTotals| size_mode.select(totals.bytes, totals.disk_bytes);
+        let tree = &self.tree;
+        let root = tree.root();
+        let totals = tree.totals(root, None);
+        let total = size(&totals);
+
+        let mut buckets: Vec<Bucket> = tree
+            .categories()
+            .iter()
+            .enumerate()
+            .filter_map(|(c, category)| {
+                let totals = tree.totals(root, Some(c));
+                if totals.file_count == 0 {
+                    return None;
+                }
+                let base = self.base.buckets.iter().find(|b| b.key == category.key);
+                Some(Bucket {
+                    key: category.key.clone(),
+                    label: category.label.clone(),
+                    bytes: totals.bytes,
+                    disk_bytes: totals.disk_bytes,
+                    percent: if total > 0 { size(&totals) as f64 / total as f64 * 100.0 } else { 0.0 },
+                    file_count: totals.file_count,
+                    color: base.and_then(|b| b.color.clone()),
+                    representative_extension: base.and_then(|b| b.representative_extension.clone()),
+                    // Not tracked between full scans
+                    content_files: 0,
+                    content_bytes: 0,
+                })
+            })
+            .collect();
+        buckets.sort_by_key(|b| std::cmp::Reverse(size_mode.select(b.bytes, b.disk_bytes)));
+
+        let top_n = self.options.top_n();
+        let mut files = BoundedMinHeap::new(top_n);
+        let mut dirs = BoundedMinHeap::new(top_n);
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            let node = tree.node(id);
+            if !node.is_dir {
+                files.push((size(&node.totals), id));
+                continue;
+            }
+            let totals = self.dir_totals(id);
+            if totals.file_count > 0 {
+                dirs.push((size(&totals), id));
+            }
+            stack.extend(&node.children);
+        }
+
+        let dir_count = (self.base.totals.dir_count + self.dir_count()).saturating_sub(self.base_dirs);
+        ScanResults {
+            totals: Totals {
+                total_bytes: totals.bytes,
+                disk_bytes: totals.disk_bytes,
+                file_count: totals.file_count,
+                dir_count,
+                ..self.base.totals.clone()
+            },
+            buckets,
+            top_files: files
+                .into_sorted_vec()
+                .into_iter()
+                .map(|(_, id)| FileEntry {
+                    path: tree.path(id).display().to_string(),
+                    bytes: tree.node(id).totals.bytes,
+                    disk_bytes: tree.node(id).totals.disk_bytes,
+                })
+                .collect(),
+            top_dirs: dirs
+                .into_sorted_vec()
+                .into_iter()
+                .map(|(_, id)| {
+                    let totals = self.dir_totals(id);
+                    DirEntry {
+                        path: tree.path(id).display().to_string(),
+                        bytes: totals.bytes,
+                        disk_bytes: totals.disk_bytes,
+                    }
+                })
+                .collect(),
+            // Not kept up to date between full scans
+            duplicates: None,
+            cleanup: None,
+            policy: None,
+            ..self.base.clone()
+        }
+    }
+
+    /// Size of `path` (or the whole tree), counting only `category` if given.
+    fn measure(&self, path: Option<&str>, category: Option<&str>) -> (PathBuf, u64) {
+        let path = match path {
+            Some(path) => self.path.join(path),
+            None => self.path.clone(),
+        };
+        let category = match category {
+            Some(name) => {
+                let found = self.tree.categories().iter().position(|c| {
+                    c.key.eq_ignore_ascii_case(name) || c.label.eq_ignore_ascii_case(name)
+                });
+                match found {
+                    Some(c) => Some(c),
+                    // Nothing in that category yet
+                    None => return (path, 0),
+                }
+            }
+            None => None,
+        };
+        let bytes = self.tree.find(&path).map_or(0, |id| {
+            let totals = self.tree.totals(id, category);
+            self.base.size_mode.select(totals.bytes, totals.disk_bytes)
+        });
+        (path, bytes)
+    }
+
+    /// Totals of directory `id` as `--dir-totals` counts them.
+    fn dir_totals(&self, id: NodeId) -> NodeTotals {
+        if self.base.dir_totals == DirTotals::Cumulative {
+            return self.tree.node(id).totals;
+        }
+        let mut totals = NodeTotals::default();
+        for &child in &self.tree.node(id).children {
+            let child = self.tree.node(child);
+            if !child.is_dir {
+                totals.bytes += child.totals.bytes;
+                totals.disk_bytes += child.totals.disk_bytes;
+                totals.file_count += 1;
+            }
+        }
+        totals
+    }
+
+    fn dir_count(&self) -> u64 {
+        let mut count = 0;
+        let mut stack = vec![self.tree.root()];
+        while let Some(id) = stack.pop() {
+            let node = self.tree.node(id);
+            if node.is_dir {
+                count += 1;
+                stack.extend(&node.children);
+            }
+        }
+        count
+    }
+}
+
+/// What the watch view shows besides the results.
+#[derive(Debug, Clone, Default)]
+pub struct WatchStatus {
+    /// Why the results come from periodic rescans instead of events
+    pub polling: Option<String>,
+    /// Local time of the last refresh
+    pub updated: String,
+    /// The most recent alerts, newest last
+    pub alerts: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum WatchError {
+    Scan(ScanError),
+    Watch(notify::Error),
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchError::Scan(e) => write!(f, "{}", e),
+            WatchError::Watch(e) => write!(f, "Cannot watch for changes: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+impl From<ScanError> for WatchError {
+    fn from(e: ScanError) -> Self {
+        WatchError::Scan(e)
+    }
+}
+
+/// Scan `path`, then keep calling `render` with fresh results as files
+/// change. Falls back to a full rescan every `interval` when the OS watch
+/// limit is reached. Only returns on errors.
+pub fn watch<F>(path: &Path, options: ScanOptions, mut alerts: Alerts, interval: Duration, mut render: F) -> Result<(), WatchError>
+where
+    F: FnMut(&ScanResults, &WatchStatus),
+{
+    let mut status = WatchStatus::default();
+    let mut live = LiveScan::scan(path, options.clone())?;
+    refresh(&live, &mut alerts, &mut status, &mut render);
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(WatchError::Watch)?;
+    if let Err(e) = watcher.watch(path, RecursiveMode::Recursive) {
+        if !matches!(e.kind, notify::ErrorKind::MaxFilesWatch) {
+            return Err(WatchError::Watch(e));
+        }
+        drop(watcher);
+        return poll(path, options, alerts, interval, status, render);
+    }
+
+    loop {
+        // Gather events until things quiet down, then refresh once
+        let Ok(first) = events.recv() else {
+            return Ok(());
+        };
+        let started = Instant::now();
+        let mut batch = vec![first];
+        while started.elapsed() < MAX_DELAY {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => batch.push(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+
+        let mut changed = BTreeSet::new();
+        let mut rescan = false;
+        for event in batch {
+            match event {
+                Ok(event) => {
+                    // Reads don't change sizes
+                    if let EventKind::Access(kind) = event.kind {
+                        if kind != AccessKind::Close(AccessMode::Write) {
+                            continue;
+                        }
+                    }
+                    rescan |= event.need_rescan();
+                    changed.extend(event.paths);
+                }
+                Err(e) if matches!(e.kind, notify::ErrorKind::MaxFilesWatch) => {
+                    drop(watcher);
+                    return poll(path, options, alerts, interval, status, render);
+                }
+                Err(e) => eprintln!("Watch error: {}", e),
+            }
+        }
+
+        if rescan {
+            // Events were lost, so the tree can't be trusted any more
+            live = LiveScan::scan(path, options.clone())?;
+        } else {
+            for path in &changed {
+                live.apply(path);
+            }
+        }
+        refresh(&live, &mut alerts, &mut status, &mut render);
+    }
+}
+
+/// Rescan in full every `interval`.
+fn poll<F>(
+    path: &Path,
+    options: ScanOptions,
+    mut alerts: Alerts,
+    interval: Duration,
+    mut status: WatchStatus,
+    mut render: F,
+) -> Result<(), WatchError>
+where
+    F: FnMut(&ScanResults, &WatchStatus),
+{
+    status.polling = Some(format!(
+        "The filesystem watch limit was reached; rescanning every {}s",
+        interval.as_secs()
+    ));
+    loop {
+        let live = LiveScan::scan(path, options.clone())?;
+        refresh(&live, &mut alerts, &mut status, &mut render);
+        std::thread::sleep(interval);
+    }
+}
+
+fn refresh<F>(live: &LiveScan, alerts: &mut Alerts, status: &mut WatchStatus, render: &mut F)
+where
+    F: FnMut(&ScanResults, &WatchStatus),
+{
+    let now = chrono::Local::now();
+    for alert in alerts.check(live) {
+        alerts.fire(&alert);
+        status.alerts.push(format!(
+            "{} alert '{}': {} is {} bytes (threshold {})",
+            now.format("%H:%M:%S"),
+            alert.name,
+            alert.path,
+            alert.bytes,
+            alert.threshold
+        ));
+    }
+    let excess = status.alerts.len().saturating_sub(RECENT_ALERTS);
+    status.alerts.drain(..excess);
+    status.updated = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    render(&live.results(), status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_apply_keeps_totals_in_sync() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), vec![0u8; 300]).unwrap();
+        fs::write(root.join("notes.txt"), vec![0u8; 100]).unwrap();
+
+        let options = ScanOptions::new().collect_tops(true).exclude(vec!["target".to_string()]);
+        let mut live = LiveScan::scan(root, options.clone()).unwrap();
+        assert_eq!(live.results().totals.total_bytes, 400);
+
+        // Modified, created, deleted and moved files, and a new directory
+        fs::write(root.join("src/main.rs"), vec![0u8; 500]).unwrap();
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        fs::create_dir_all(root.join("docs/api")).unwrap();
+        fs::write(root.join("docs/api/index.md"), vec![0u8; 50]).unwrap();
+        fs::rename(root.join("src/main.rs"), root.join("src/lib.rs")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("target/app"), vec![0u8; 1000]).unwrap();
+        for path in ["src/main.rs", "src/lib.rs", "notes.txt", "docs", "target/app"] {
+            live.apply(&root.join(path));
+        }
+
+        let results = live.results();
+        let fresh = LiveScan::scan(root, options).unwrap().results();
+        assert_eq!(results.totals.total_bytes, 550);
+        assert_eq!(results.totals.file_count, fresh.totals.file_count);
+        assert_eq!(results.totals.dir_count, fresh.totals.dir_count);
+        let sizes = |r: &ScanResults| r.buckets.iter().map(|b| (b.key.clone(), b.bytes)).collect::<Vec<_>>();
+        assert_eq!(sizes(&results), sizes(&fresh));
+        assert_eq!(results.top_files[0].path, root.join("src/lib.rs").display().to_string());
+    }
+
+    #[test]
+    fn test_alerts_fire_when_crossing() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join("logs/app.log"), vec![0u8; 100]).unwrap();
+
+        let rule = |name: &str, path: Option<&str>, category: Option<&str>| AlertRule {
+            name: name.to_string(),
+            path: path.map(str::to_string),
+            category: category.map(str::to_string),
+            threshold: Some(1000),
+            command: Some("true".to_string()),
+            webhook: None,
+        };
+        let mut alerts = Alerts::new(&[rule("logs", Some("logs"), None), rule("text", None, Some("Documents"))]).unwrap();
+        let mut live = LiveScan::scan(root, ScanOptions::new()).unwrap();
+        assert!(alerts.check(&live).is_empty());
+
+        fs::write(root.join("logs/app.log"), vec![0u8; 2000]).unwrap();
+        live.apply(&root.join("logs/app.log"));
+        let fired = alerts.check(&live);
+        assert_eq!(fired.len(), 1);
+        assert_eq!((fired[0].name.as_str(), fired[0].bytes), ("logs", 2000));
+
+        // Staying above doesn't fire again
+        assert!(alerts.check(&live).is_empty());
+
+        assert!(Alerts::new(&[AlertRule { threshold: None, ..rule("x", None, None) }]).is_err());
+        assert!(Alerts::new(&[AlertRule { webhook: Some("https://example.com".to_string()), ..rule("x", None, None) }]).is_err());
+    }
+}